@@ -6,8 +6,10 @@
 //! separate process. Output is captured and returned in a structured form.
 
 use crate::model::{PreparedAgent, safe_name};
+use crate::runners::SamplingClient;
 use anyhow::{Context, anyhow};
 use async_trait::async_trait;
+use serde::Serialize;
 
 /// Structured result of running a Codex task.
 pub struct CodexRunOutput {
@@ -19,6 +21,62 @@ pub struct CodexRunOutput {
     pub stdout: String,
     /// Aggregated background/debug logs and error details.
     pub stderr: String,
+    /// What the run actually did (model, commands, tool calls, patches, usage).
+    pub trace: RunTrace,
+}
+
+/// Execution details collected from Codex events, recorded in run history.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunTrace {
+    pub session_id: Option<String>,
+    /// Model reported by `SessionConfigured`.
+    pub model: Option<String>,
+    pub provider: Option<String>,
+    pub commands: Vec<CommandRecord>,
+    pub mcp_calls: Vec<McpCallRecord>,
+    pub patches: Vec<PatchRecord>,
+    pub usage: Option<TokenUsageRecord>,
+    /// Last unified diff reported by the session.
+    pub diff: Option<String>,
+    /// Set when the run was interrupted for exceeding `run.max_tokens_per_call`.
+    pub budget_exceeded: bool,
+    /// Denied MCP tool (`server/tool`) whose call stopped the run.
+    pub denied_tool: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandRecord {
+    pub call_id: String,
+    pub command: Vec<String>,
+    pub cwd: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct McpCallRecord {
+    pub server: String,
+    pub tool: String,
+    pub ok: bool,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchRecord {
+    pub call_id: String,
+    pub files: Vec<String>,
+    pub auto_approved: bool,
+    pub success: Option<bool>,
+}
+
+/// Token usage summed over all `TokenCount` events of a run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TokenUsageRecord {
+    pub input_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_output_tokens: u64,
+    pub total_tokens: u64,
 }
 
 #[async_trait]
@@ -39,6 +97,20 @@ pub trait CodexRunner: Send + Sync {
         task: &str,
         cwd: &str,
     ) -> anyhow::Result<CodexRunOutput>;
+
+    /// Like `exec_task`, with the calling MCP client available for runners
+    /// that sample through it. `client` is `None` outside a tool call (e.g.
+    /// the `replay` command). Other runners ignore it.
+    async fn exec_task_with_client(
+        &self,
+        prepared: &PreparedAgent,
+        tool: &str,
+        task: &str,
+        cwd: &str,
+        _client: Option<&dyn SamplingClient>,
+    ) -> anyhow::Result<CodexRunOutput> {
+        self.exec_task(prepared, tool, task, cwd).await
+    }
 }
 
 /// Default in-process Codex runner.
@@ -122,6 +194,9 @@ impl CodexRunner for InprocCodexRunner {
         for (key, msc) in &prepared.mcp_servers {
             cfg.mcp_servers.insert(key.clone(), msc.clone());
         }
+        for (id, provider) in &prepared.model_providers {
+            cfg.model_providers.insert(id.clone(), provider.clone());
+        }
 
         // Minimal overrides: profile selection, cwd (force absolute), base_instructions, and select flags
         let mut overrides = ConfigOverrides {
@@ -161,6 +236,11 @@ impl CodexRunner for InprocCodexRunner {
             config.show_raw_agent_reasoning
         );
 
+        let mut trace = RunTrace {
+            provider: Some(config.model_provider_id.clone()),
+            ..Default::default()
+        };
+
         let conversation_manager = ConversationManager::new(AuthManager::shared(
             config.codex_home.clone(),
             config.preferred_auth_method,
@@ -179,6 +259,7 @@ impl CodexRunner for InprocCodexRunner {
         let mut stdout_buf = String::new();
         let mut stderr_buf = String::new();
         let mut ok = false;
+        let max_tokens = prepared.run.as_ref().and_then(|r| r.max_tokens_per_call);
 
         // Drain events until shutdown.
         loop {
@@ -229,6 +310,27 @@ impl CodexRunner for InprocCodexRunner {
                 }
                 EventMsg::TokenCount(token_usage) => {
                     tracing::info!("tokens used: {}", token_usage.blended_total());
+                    let usage = trace.usage.get_or_insert_with(TokenUsageRecord::default);
+                    usage.input_tokens += token_usage.input_tokens;
+                    usage.cached_input_tokens += token_usage.cached_input_tokens.unwrap_or(0);
+                    usage.output_tokens += token_usage.output_tokens;
+                    usage.reasoning_output_tokens +=
+                        token_usage.reasoning_output_tokens.unwrap_or(0);
+                    usage.total_tokens += token_usage.total_tokens;
+                    if let Some(limit) = max_tokens
+                        && usage.total_tokens > limit
+                        && !trace.budget_exceeded
+                    {
+                        trace.budget_exceeded = true;
+                        let msg = format!(
+                            "token budget exceeded: used {} of {} (run.max_tokens_per_call); interrupting",
+                            usage.total_tokens, limit
+                        );
+                        tracing::warn!("{}", msg);
+                        stderr_buf.push_str(&msg);
+                        stderr_buf.push('\n');
+                        conversation.submit(Op::Interrupt).await.ok();
+                    }
                 }
                 EventMsg::AgentReasoningSectionBreak(_) => {
                     tracing::debug!("reasoning section break");
@@ -248,6 +350,13 @@ impl CodexRunner for InprocCodexRunner {
                         cwd,
                         cmd_preview
                     );
+                    trace.commands.push(CommandRecord {
+                        call_id: ev.call_id.clone(),
+                        command: ev.command.clone(),
+                        cwd: ev.cwd.display().to_string(),
+                        exit_code: None,
+                        duration_ms: None,
+                    });
                 }
                 EventMsg::ExecCommandOutputDelta(_) => {
                     tracing::debug!("exec output delta received");
@@ -261,6 +370,15 @@ impl CodexRunner for InprocCodexRunner {
                         code,
                         dur_ms
                     );
+                    if let Some(rec) = trace
+                        .commands
+                        .iter_mut()
+                        .rev()
+                        .find(|c| c.call_id == ev.call_id)
+                    {
+                        rec.exit_code = Some(code);
+                        rec.duration_ms = Some(dur_ms as u64);
+                    }
                 }
                 EventMsg::McpToolCallBegin(ev) => {
                     tracing::info!(
@@ -269,6 +387,20 @@ impl CodexRunner for InprocCodexRunner {
                         ev.invocation.tool,
                         ev.call_id
                     );
+                    if trace.denied_tool.is_none()
+                        && prepared
+                            .denied_tools
+                            .iter()
+                            .any(|d| d.matches(&ev.invocation.server, &ev.invocation.tool))
+                    {
+                        let denied = format!("{}/{}", ev.invocation.server, ev.invocation.tool);
+                        let msg = format!("agent called denied tool {denied}; interrupting");
+                        tracing::warn!("{}", msg);
+                        stderr_buf.push_str(&msg);
+                        stderr_buf.push('\n');
+                        trace.denied_tool = Some(denied);
+                        conversation.submit(Op::Interrupt).await.ok();
+                    }
                 }
                 EventMsg::McpToolCallEnd(ev) => {
                     let ok = ev.is_success();
@@ -280,6 +412,12 @@ impl CodexRunner for InprocCodexRunner {
                         ok,
                         dur_ms
                     );
+                    trace.mcp_calls.push(McpCallRecord {
+                        server: ev.invocation.server.clone(),
+                        tool: ev.invocation.tool.clone(),
+                        ok,
+                        duration_ms: dur_ms as u64,
+                    });
                 }
                 EventMsg::WebSearchBegin(ev) => {
                     tracing::debug!("web search begin call_id={}", ev.call_id);
@@ -300,6 +438,15 @@ impl CodexRunner for InprocCodexRunner {
                         ev.auto_approved,
                         changes
                     );
+                    let mut files: Vec<String> =
+                        ev.changes.keys().map(|p| p.display().to_string()).collect();
+                    files.sort();
+                    trace.patches.push(PatchRecord {
+                        call_id: ev.call_id.clone(),
+                        files,
+                        auto_approved: ev.auto_approved,
+                        success: None,
+                    });
                 }
                 EventMsg::PatchApplyEnd(ev) => {
                     tracing::info!(
@@ -309,9 +456,18 @@ impl CodexRunner for InprocCodexRunner {
                         ev.stdout.len(),
                         ev.stderr.len()
                     );
+                    if let Some(rec) = trace
+                        .patches
+                        .iter_mut()
+                        .rev()
+                        .find(|p| p.call_id == ev.call_id)
+                    {
+                        rec.success = Some(ev.success);
+                    }
                 }
                 EventMsg::TurnDiff(ev) => {
                     tracing::debug!("turn diff len={}", ev.unified_diff.len());
+                    trace.diff = Some(ev.unified_diff);
                 }
                 EventMsg::ExecApprovalRequest(_) => {
                     tracing::info!("exec approval requested");
@@ -328,6 +484,8 @@ impl CodexRunner for InprocCodexRunner {
                         ev.session_id,
                         ev.model
                     );
+                    trace.session_id = Some(ev.session_id.to_string());
+                    trace.model = Some(ev.model.clone());
                 }
                 EventMsg::PlanUpdate(ev) => {
                     let steps = ev.plan.len();
@@ -347,20 +505,34 @@ impl CodexRunner for InprocCodexRunner {
                 EventMsg::ListCustomPromptsResponse(_) => {
                     tracing::debug!("list custom prompts response received");
                 }
-                EventMsg::TurnAborted(reason) => match reason.reason {
-                    codex_core::protocol::TurnAbortReason::Interrupted => {
-                        tracing::warn!("task interrupted")
+                EventMsg::TurnAborted(reason) => {
+                    match reason.reason {
+                        codex_core::protocol::TurnAbortReason::Interrupted => {
+                            tracing::warn!("task interrupted")
+                        }
+                        codex_core::protocol::TurnAbortReason::Replaced => {
+                            tracing::warn!("task aborted: replaced by new task")
+                        }
                     }
-                    codex_core::protocol::TurnAbortReason::Replaced => {
-                        tracing::warn!("task aborted: replaced by new task")
+                    // A budget or denylist interrupt ends the task; shut the session down.
+                    if trace.budget_exceeded || trace.denied_tool.is_some() {
+                        conversation.submit(Op::Shutdown).await.ok();
                     }
-                },
+                }
                 _ => {
                     // Ignore other events for now.
                 }
             }
         }
 
+        if trace.budget_exceeded {
+            ok = false;
+            stdout_buf.push_str("[run interrupted: token budget exceeded]\n");
+        }
+        if let Some(tool) = &trace.denied_tool {
+            ok = false;
+            stdout_buf.push_str(&format!("[run interrupted: denied tool {tool}]\n"));
+        }
         let status_code = if ok { 0 } else { 1 };
         tracing::info!(
             "in-proc codex finished: ok={}, status={}, stdout_len={}, stderr_len={}",
@@ -374,6 +546,7 @@ impl CodexRunner for InprocCodexRunner {
             status: status_code,
             stdout: stdout_buf,
             stderr: stderr_buf,
+            trace,
         })
     }
 }
@@ -5,6 +5,13 @@ use std::path::{Path, PathBuf};
 pub struct UserConfig {
     pub logging: Option<LoggingCfg>,
     pub agents: Option<AgentsCfg>,
+    pub server: Option<ServerCfg>,
+    pub history: Option<HistoryCfg>,
+    pub replays: Option<ReplaysCfg>,
+    pub budgets: Option<BudgetsCfg>,
+    pub runner: Option<RunnerCfg>,
+    pub runners: Option<std::collections::HashMap<String, CommandRunnerCfg>>,
+    pub model_providers: Option<toml::Value>, // [model_providers.<id>] shared by all agents
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -17,19 +24,79 @@ pub struct LoggingCfg {
     pub level: Option<String>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct ServerCfg {
+    pub tls: Option<TlsCfg>,
+    pub allowed_origins: Option<Vec<String>>, // browser origins; "*" allows any
+    pub allowed_hosts: Option<Vec<String>>,   // Host header values; "*" disables the check
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TlsCfg {
+    pub cert: Option<String>,      // PEM certificate chain
+    pub key: Option<String>,       // PEM private key
+    pub client_ca: Option<String>, // PEM CA bundle; enables mTLS when set
+    pub reload_on_sighup: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryCfg {
+    pub enabled: Option<bool>,
+    pub retention_days: Option<u32>, // 0 keeps everything
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReplaysCfg {
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BudgetsCfg {
+    pub daily_per_agent: Option<u64>, // default daily token cap for every agent
+    pub agents: Option<std::collections::HashMap<String, u64>>, // tool or agent name -> daily cap
+    pub tags: Option<std::collections::HashMap<String, u64>>, // tag -> daily cap
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RunnerCfg {
+    pub kind: Option<String>, // "inproc" (default), "subprocess" or "mock"
+    pub mock_fixtures: Option<Vec<String>>, // fixture files or directories
+    pub codex_bin: Option<String>, // codex CLI for the subprocess runner
+    pub providers: Option<std::collections::HashMap<String, String>>, // codex/anthropic/vscode -> runner
+    pub sampling_max_turns: Option<usize>, // model turns per call for the sampling runner
+    pub sampling_max_tokens: Option<u64>,  // maxTokens per sampling request
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CommandRunnerCfg {
+    pub command: String,
+    pub args: Option<Vec<String>>, // templated: {task} {instructions} {instructions_file} {model} {cwd} {agent}
+    pub timeout_secs: Option<u64>,
+    pub env_allowlist: Option<Vec<String>>, // inherited env vars; defaults to PATH/HOME/USER/LANG/TERM/TMPDIR
+    pub env: Option<std::collections::HashMap<String, String>>,
+}
+
 #[derive(Debug, Default, Deserialize)]
 pub struct AgentsCfg {
     pub enable_codex: Option<bool>,
     pub enable_anthropic: Option<bool>,
     pub enable_vscode: Option<bool>,
+    pub enable_cursor: Option<bool>,
+    pub enable_commands: Option<bool>,
 
     pub codex_dirs: Option<Vec<String>>, // absolute paths preferred
     pub anthropic_dirs: Option<Vec<String>>, // absolute paths preferred
     pub vscode_dirs: Option<Vec<String>>, // absolute paths preferred
+    pub cursor_dirs: Option<Vec<String>>, // rule dirs or context files
+    pub command_dirs: Option<Vec<String>>, // Codex prompts / Claude commands
+    pub command_base: Option<String>,    // agent prompts/commands run with
+    pub template_env_allow: Option<Vec<String>>, // env vars `{{env.NAME}}` may read
 
     pub prefix_codex: Option<String>,
     pub prefix_anthropic: Option<String>,
     pub prefix_vscode: Option<String>,
+    pub prefix_cursor: Option<String>,
+    pub prefix_command: Option<String>,
 
     pub filter: Option<String>,
 
@@ -44,12 +111,37 @@ pub struct AgentsCfg {
 
     pub toolmap_enable: Option<bool>,
     pub toolmap_allow_custom_servers: Option<bool>,
+    pub toolmap_file: Option<String>,
 
     pub model_map_enable: Option<bool>,
     pub model_map_file: Option<String>,
     pub model_map_strict: Option<bool>,
     pub model_map_override_provider: Option<bool>,
     pub model_map_normalize_provider: Option<bool>,
+
+    pub fragment_dirs: Option<Vec<String>>, // searched before the defaults
+    pub fragment_max_bytes: Option<usize>,
+
+    pub scan: Option<ScanCfgs>,
+}
+
+/// `[agents.scan.<variant>]`: recursive directory scanning per variant.
+#[derive(Debug, Default, Deserialize)]
+pub struct ScanCfgs {
+    pub codex: Option<ScanCfg>,
+    pub anthropic: Option<ScanCfg>,
+    pub vscode: Option<ScanCfg>,
+    pub cursor: Option<ScanCfg>,
+    pub commands: Option<ScanCfg>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ScanCfg {
+    pub depth: Option<usize>,
+    pub include: Option<Vec<String>>, // globs relative to the scanned dir
+    pub exclude: Option<Vec<String>>,
+    pub folder_tags: Option<bool>,
+    pub folder_namespace: Option<bool>,
 }
 
 pub fn load_user_config(sb_home: &Path) -> anyhow::Result<Option<UserConfig>> {
@@ -0,0 +1,274 @@
+//! Instruction includes: `{{> name}}` and `@include path` directives.
+//!
+//! - `{{> name}}` may appear anywhere in a line; `@include <target>` must be
+//!   the only thing on its line.
+//! - A bare name resolves to `<name>.md` (or `<name>`) in the fragment
+//!   directories (`<workspace>/.agents/fragments`, then
+//!   `$SWITCHBOARD_HOME/fragments`), then next to the including file.
+//! - A path (`shared/rules.md`, `../x.md`, `~/x.md`) resolves relative to the
+//!   file holding the directive.
+//!
+//! Fragments may include other fragments. Cycles, nesting deeper than
+//! [`MAX_DEPTH`] and expanded text larger than the size limit are errors. A
+//! directive whose target does not exist (SCSS `@include mixin;`, Handlebars
+//! partials in examples) is kept as written, with a warning.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+
+use crate::model::PreparedAgent;
+
+/// Maximum include nesting.
+const MAX_DEPTH: usize = 8;
+
+/// Instructions after includes, with the fragment files that were used.
+pub struct Expanded {
+    pub text: String,
+    pub fragments: Vec<PathBuf>,
+}
+
+pub struct FragmentResolver {
+    dirs: Vec<PathBuf>,
+    max_bytes: usize,
+}
+
+impl FragmentResolver {
+    pub fn new(dirs: Vec<PathBuf>, max_bytes: usize) -> Self {
+        Self { dirs, max_bytes }
+    }
+
+    /// Expand every directive in `text`, which was read from `origin`.
+    pub fn expand(&self, text: &str, origin: &Path) -> anyhow::Result<Expanded> {
+        let mut out = Expanded {
+            text: String::with_capacity(text.len()),
+            fragments: Vec::new(),
+        };
+        if !text.contains("{{>") && !text.contains("@include") {
+            out.text.push_str(text);
+            return Ok(out);
+        }
+        let origin = origin
+            .canonicalize()
+            .unwrap_or_else(|_| origin.to_path_buf());
+        let base = origin.parent().unwrap_or(Path::new(".")).to_path_buf();
+        let mut stack = vec![origin];
+        self.expand_into(text, &base, &mut stack, &mut out)?;
+        Ok(out)
+    }
+
+    fn expand_into(
+        &self,
+        text: &str,
+        base: &Path,
+        stack: &mut Vec<PathBuf>,
+        out: &mut Expanded,
+    ) -> anyhow::Result<()> {
+        for line in text.split_inclusive('\n') {
+            let body = line.trim_end_matches(['\n', '\r']);
+            if let Some(target) = body.trim().strip_prefix("@include ") {
+                if !self.include(target.trim(), base, stack, out)? {
+                    out.text.push_str(body);
+                }
+                out.text.push_str(&line[body.len()..]);
+            } else {
+                let mut rest = line;
+                while let Some(start) = rest.find("{{>") {
+                    let after = &rest[start + 3..];
+                    let Some(end) = after.find("}}") else {
+                        break;
+                    };
+                    out.text.push_str(&rest[..start]);
+                    if !self.include(after[..end].trim(), base, stack, out)? {
+                        out.text.push_str(&rest[start..start + 3 + end + 2]);
+                    }
+                    rest = &after[end + 2..];
+                }
+                out.text.push_str(rest);
+            }
+            if out.text.len() > self.max_bytes {
+                bail!(
+                    "instructions exceed {} bytes after includes",
+                    self.max_bytes
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand one directive; `false` when its target does not exist and the
+    /// directive should stay as text.
+    fn include(
+        &self,
+        target: &str,
+        base: &Path,
+        stack: &mut Vec<PathBuf>,
+        out: &mut Expanded,
+    ) -> anyhow::Result<bool> {
+        let path = match self.locate(target, base) {
+            Ok(path) => path,
+            Err(e) => {
+                tracing::warn!("{:#} (in {}); kept as text", e, base.display());
+                return Ok(false);
+            }
+        };
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("resolve include '{}'", path.display()))?;
+        if stack.contains(&path) {
+            let chain: Vec<String> = stack
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|p| p.display().to_string())
+                .collect();
+            bail!("include cycle: {}", chain.join(" -> "));
+        }
+        if stack.len() > MAX_DEPTH {
+            bail!(
+                "includes nested deeper than {} at '{}'",
+                MAX_DEPTH,
+                path.display()
+            );
+        }
+        let len = std::fs::metadata(&path)
+            .with_context(|| format!("stat fragment {}", path.display()))?
+            .len();
+        if len > self.max_bytes as u64 {
+            bail!(
+                "fragment {} is larger than {} bytes",
+                path.display(),
+                self.max_bytes
+            );
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("read fragment {}", path.display()))?;
+        if !out.fragments.contains(&path) {
+            out.fragments.push(path.clone());
+        }
+        let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        stack.push(path);
+        self.expand_into(content.trim_end_matches(['\n', '\r']), &dir, stack, out)?;
+        stack.pop();
+        Ok(true)
+    }
+
+    fn locate(&self, target: &str, base: &Path) -> anyhow::Result<PathBuf> {
+        if target.is_empty() {
+            bail!("empty include target");
+        }
+        let is_path = target.contains('/') || target.contains('\\') || target.starts_with('~');
+        if is_path {
+            let p = crate::config::expand_home(target);
+            let p = if p.is_absolute() { p } else { base.join(p) };
+            if p.is_file() {
+                return Ok(p);
+            }
+            bail!("include '{}' not found at {}", target, p.display());
+        }
+        let candidates = self
+            .dirs
+            .iter()
+            .flat_map(|d| [d.join(format!("{target}.md")), d.join(target)])
+            .chain([base.join(format!("{target}.md")), base.join(target)]);
+        for c in candidates {
+            if c.is_file() {
+                return Ok(c);
+            }
+        }
+        let dirs: Vec<String> = self
+            .dirs
+            .iter()
+            .chain(std::iter::once(&base.to_path_buf()))
+            .map(|d| d.display().to_string())
+            .collect();
+        bail!("fragment '{}' not found in {}", target, dirs.join(", "))
+    }
+}
+
+/// Text for `switchboard-mcp instructions <agent>`: the agent's resolved
+/// instructions preceded by a header naming its source and fragments.
+pub fn instructions_view(agent: &PreparedAgent) -> String {
+    let mut out = format!(
+        "# {} ({})\n# source: {}\n",
+        agent.name,
+        agent.tool_name,
+        agent.source_path.display()
+    );
+    if agent.fragments.is_empty() {
+        out.push_str("# fragments: none\n");
+    } else {
+        out.push_str("# fragments:\n");
+        for f in &agent.fragments {
+            out.push_str(&format!("#   {}\n", f.display()));
+        }
+    }
+    out.push('\n');
+    out.push_str(agent.instructions.as_deref().unwrap_or("(no instructions)"));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, rel: &str, content: &str) -> PathBuf {
+        let p = dir.join(rel);
+        std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+        std::fs::write(&p, content).unwrap();
+        p
+    }
+
+    #[test]
+    fn expands_named_and_relative_includes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let frag_dir = tmp.path().join("fragments");
+        write(&frag_dir, "no-secrets.md", "Never print secrets.\n");
+        write(&frag_dir, "policy.md", "Policy:\n{{> no-secrets}}\n");
+        write(tmp.path(), "agents/shared/churn.md", "Keep diffs small.\n");
+        let agent = write(tmp.path(), "agents/dev.toml", "");
+
+        let r = FragmentResolver::new(vec![frag_dir.clone()], 64 * 1024);
+        let text = "Intro.\n@include shared/churn.md\nRules: {{> policy }}\nDone.";
+        let out = r.expand(text, &agent).unwrap();
+        assert_eq!(
+            out.text,
+            "Intro.\nKeep diffs small.\nRules: Policy:\nNever print secrets.\nDone."
+        );
+        let names: Vec<String> = out
+            .fragments
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["churn.md", "policy.md", "no-secrets.md"]);
+    }
+
+    #[test]
+    fn rejects_cycles_and_oversized_output() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "a.md", "{{> b}}");
+        write(tmp.path(), "b.md", "@include ./a.md");
+        write(tmp.path(), "big.md", &"x".repeat(200));
+        let agent = write(tmp.path(), "dev.toml", "");
+        let r = FragmentResolver::new(vec![tmp.path().to_path_buf()], 100);
+
+        let err = r.expand("{{> a}}", &agent).unwrap_err().to_string();
+        assert!(err.contains("include cycle"), "{err}");
+        assert!(r.expand("{{> big}}", &agent).is_err());
+        assert_eq!(r.expand("no includes", &agent).unwrap().text, "no includes");
+    }
+
+    #[test]
+    fn missing_targets_stay_as_text() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "rules.md", "Be kind.");
+        let agent = write(tmp.path(), "dev.toml", "");
+        let r = FragmentResolver::new(vec![tmp.path().to_path_buf()], 1024);
+        let text = ".btn {\n  @include mixin;\n}\nUse {{> partial}} and {{>}}.\n@include ./gone.md\n{{> rules}}";
+        let out = r.expand(text, &agent).unwrap();
+        assert_eq!(
+            out.text,
+            ".btn {\n  @include mixin;\n}\nUse {{> partial}} and {{>}}.\n@include ./gone.md\nBe kind."
+        );
+        assert_eq!(out.fragments.len(), 1);
+    }
+}
@@ -0,0 +1,462 @@
+//! Front listener for the HTTP/SSE transport.
+//!
+//! The SDK's hyper server binds an ephemeral loopback port itself while this
+//! module owns the public socket: it validates `Host`/`Origin` headers (DNS
+//! rebinding and browser-origin protection), optionally terminates TLS
+//! (with client-certificate verification and SIGHUP reload), and forwards each
+//! request to the upstream server. Response bodies are streamed, so SSE and
+//! JSON response modes pass through as-is.
+//!
+//! The upstream only accepts a random per-process `Host` value that the
+//! gateway substitutes when forwarding, so local processes that connect to the
+//! loopback port directly cannot bypass these checks.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::Context as _;
+use http_body_util::{BodyExt, Full, combinators::BoxBody};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{HOST, HeaderValue, ORIGIN};
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode, Uri};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+type ProxyBody = BoxBody<Bytes, hyper::Error>;
+type UpstreamClient = Client<HttpConnector, Incoming>;
+
+/// TLS material for the public listener (PEM files).
+#[derive(Debug, Clone)]
+pub struct TlsSettings {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Optional CA bundle; when set, clients must present a certificate signed by it.
+    pub client_ca: Option<PathBuf>,
+    /// Re-read `cert`/`key`/`client_ca` when the process receives SIGHUP.
+    pub reload_on_sighup: bool,
+}
+
+/// Public listener settings and the internal upstream it forwards to.
+#[derive(Debug, Clone)]
+pub struct GatewaySettings {
+    pub host: String,
+    pub port: u16,
+    pub upstream: SocketAddr,
+    /// `Host` value the upstream accepts (see [`upstream_host`]).
+    pub upstream_host: String,
+    pub tls: Option<TlsSettings>,
+    pub policy: OriginPolicy,
+}
+
+/// `Host`/`Origin` allowlists applied to every request before forwarding.
+///
+/// Requests without an `Origin` header (CLI and desktop MCP clients) only need
+/// a valid `Host`. Requests carrying an `Origin` come from a browser and are
+/// denied unless the origin is listed; `*` in either list disables that check.
+#[derive(Debug, Clone, Default)]
+pub struct OriginPolicy {
+    pub allowed_origins: Vec<String>,
+    pub allowed_hosts: Vec<String>,
+}
+
+impl OriginPolicy {
+    /// Default host allowlist for a bind address: the literal host for a
+    /// named address, loopback names otherwise. Wildcard binds need the names
+    /// remote clients use listed explicitly.
+    pub fn default_hosts(host: &str, port: u16) -> Vec<String> {
+        if Self::is_wildcard(host) {
+            return Self::default_hosts("127.0.0.1", port);
+        }
+        let bare = host.trim_start_matches('[').trim_end_matches(']');
+        match bare {
+            "127.0.0.1" | "localhost" | "::1" => ["localhost", "127.0.0.1", "[::1]"]
+                .iter()
+                .flat_map(|h| [h.to_string(), format!("{h}:{port}")])
+                .collect(),
+            _ => vec![host.to_string(), format!("{host}:{port}")],
+        }
+    }
+
+    /// Whether `host` binds every interface (`0.0.0.0`, `::`).
+    pub fn is_wildcard(host: &str) -> bool {
+        matches!(
+            host.trim_start_matches('[').trim_end_matches(']'),
+            "0.0.0.0" | "::"
+        )
+    }
+
+    /// Validate request headers; the error is a human-readable rejection reason.
+    pub fn check(&self, host: Option<&str>, origin: Option<&str>) -> Result<(), String> {
+        if !self.allowed_hosts.iter().any(|h| h == "*") {
+            let Some(host) = host else {
+                return Err("missing Host header".to_string());
+            };
+            let host_only = strip_port(host);
+            let ok = self.allowed_hosts.iter().any(|allowed| {
+                allowed.eq_ignore_ascii_case(host)
+                    || (strip_port(allowed) == allowed && allowed.eq_ignore_ascii_case(host_only))
+            });
+            if !ok {
+                return Err(format!("Host '{host}' not allowed"));
+            }
+        }
+        if let Some(origin) = origin {
+            let origin = origin.trim_end_matches('/');
+            let ok = self.allowed_origins.iter().any(|allowed| {
+                allowed == "*" || allowed.trim_end_matches('/').eq_ignore_ascii_case(origin)
+            });
+            if !ok {
+                return Err(format!("Origin '{origin}' not allowed"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Strip a trailing `:port` from a host header value (IPv6 literals keep brackets).
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(idx) if !host[idx..].contains(']') && host[..idx].matches(':').count() == 0 => {
+            &host[..idx]
+        }
+        Some(idx) if host.starts_with('[') && host[..idx].ends_with(']') => &host[..idx],
+        _ => host,
+    }
+}
+
+/// Random `Host` value for the internal upstream. It never leaves the process,
+/// so only the gateway can produce requests the upstream accepts.
+pub fn upstream_host() -> String {
+    format!("{}.switchboard.internal", uuid::Uuid::new_v4().simple())
+}
+
+/// Accept connections on the public socket and forward them to the upstream.
+pub async fn serve(settings: GatewaySettings) -> anyhow::Result<()> {
+    let tls_config = match settings.tls.as_ref() {
+        Some(tls) => {
+            let slot = Arc::new(RwLock::new(Arc::new(load_tls_config(tls)?)));
+            if tls.reload_on_sighup {
+                spawn_reload_on_sighup(tls.clone(), slot.clone());
+            }
+            Some(slot)
+        }
+        None => None,
+    };
+
+    let listener = TcpListener::bind((settings.host.as_str(), settings.port))
+        .await
+        .with_context(|| format!("bind {}:{}", settings.host, settings.port))?;
+    tracing::info!(
+        "{} listener on {}:{} → upstream {} (allowed_hosts=[{}], allowed_origins=[{}], client_ca={})",
+        if tls_config.is_some() {
+            "https"
+        } else {
+            "http"
+        },
+        settings.host,
+        settings.port,
+        settings.upstream,
+        settings.policy.allowed_hosts.join(", "),
+        settings.policy.allowed_origins.join(", "),
+        settings
+            .tls
+            .as_ref()
+            .and_then(|t| t.client_ca.as_ref())
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "<none>".to_string())
+    );
+
+    let client: UpstreamClient = Client::builder(TokioExecutor::new()).build_http();
+    let upstream = Upstream {
+        addr: settings.upstream,
+        host: HeaderValue::from_str(&settings.upstream_host).context("invalid upstream host")?,
+    };
+    let policy = Arc::new(settings.policy);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!("accept failed: {}", e);
+                continue;
+            }
+        };
+        let client = client.clone();
+        let upstream = upstream.clone();
+        let policy = policy.clone();
+        match tls_config.as_ref() {
+            Some(slot) => {
+                let acceptor = TlsAcceptor::from(
+                    slot.read()
+                        .map(|cfg| cfg.clone())
+                        .map_err(|_| anyhow::anyhow!("tls config lock poisoned"))?,
+                );
+                tokio::spawn(async move {
+                    match acceptor.accept(stream).await {
+                        Ok(s) => serve_connection(s, peer, client, upstream, policy).await,
+                        Err(e) => tracing::warn!("tls handshake with {} failed: {}", peer, e),
+                    }
+                });
+            }
+            None => {
+                tokio::spawn(serve_connection(stream, peer, client, upstream, policy));
+            }
+        }
+    }
+}
+
+/// Where requests are forwarded, and the `Host` value that upstream accepts.
+#[derive(Clone)]
+struct Upstream {
+    addr: SocketAddr,
+    host: HeaderValue,
+}
+
+async fn serve_connection<IO>(
+    io: IO,
+    peer: SocketAddr,
+    client: UpstreamClient,
+    upstream: Upstream,
+    policy: Arc<OriginPolicy>,
+) where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let svc = service_fn(move |req: Request<Incoming>| {
+        let client = client.clone();
+        let upstream = upstream.clone();
+        let policy = policy.clone();
+        async move {
+            let host = req.headers().get(HOST).and_then(|v| v.to_str().ok());
+            let origin = req.headers().get(ORIGIN).and_then(|v| v.to_str().ok());
+            if let Err(reason) = policy.check(host, origin) {
+                tracing::warn!(
+                    "rejected {} {} from {}: {}",
+                    req.method(),
+                    req.uri().path(),
+                    peer,
+                    reason
+                );
+                return Ok::<_, Infallible>(plain_response(StatusCode::FORBIDDEN, "forbidden"));
+            }
+            Ok(forward(&client, &upstream, req).await)
+        }
+    });
+    if let Err(e) = hyper::server::conn::http1::Builder::new()
+        .serve_connection(TokioIo::new(io), svc)
+        .await
+    {
+        tracing::debug!("connection from {} closed with error: {}", peer, e);
+    }
+}
+
+/// Forward a single request to the upstream, mapping transport failures to 502.
+async fn forward(
+    client: &UpstreamClient,
+    upstream: &Upstream,
+    mut req: Request<Incoming>,
+) -> Response<ProxyBody> {
+    let uri = match upstream_uri(upstream.addr, req.uri()) {
+        Ok(u) => u,
+        Err(e) => {
+            tracing::warn!("invalid request uri {}: {}", req.uri(), e);
+            return plain_response(StatusCode::BAD_REQUEST, "invalid request uri");
+        }
+    };
+    *req.uri_mut() = uri;
+    req.headers_mut().insert(HOST, upstream.host.clone());
+    match client.request(req).await {
+        Ok(resp) => resp.map(|b| b.boxed()),
+        Err(e) => {
+            tracing::warn!("upstream request failed: {}", e);
+            plain_response(StatusCode::BAD_GATEWAY, "upstream unavailable")
+        }
+    }
+}
+
+fn upstream_uri(upstream: SocketAddr, original: &Uri) -> Result<Uri, hyper::http::uri::InvalidUri> {
+    let path = original.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    format!("http://{upstream}{path}").parse()
+}
+
+fn plain_response(status: StatusCode, msg: &'static str) -> Response<ProxyBody> {
+    let mut resp = Response::new(
+        Full::new(Bytes::from_static(msg.as_bytes()))
+            .map_err(|never| match never {})
+            .boxed(),
+    );
+    *resp.status_mut() = status;
+    resp
+}
+
+fn load_tls_config(tls: &TlsSettings) -> anyhow::Result<rustls::ServerConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let certs = load_certs(&tls.cert)?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key)
+        .with_context(|| format!("read TLS key {}", tls.key.display()))?;
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .context("select TLS protocol versions")?;
+    let builder = match tls.client_ca.as_ref() {
+        Some(ca) => {
+            let mut roots = rustls::RootCertStore::empty();
+            for cert in load_certs(ca)? {
+                roots
+                    .add(cert)
+                    .with_context(|| format!("add client CA from {}", ca.display()))?;
+            }
+            let verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(
+                Arc::new(roots),
+                provider,
+            )
+            .build()
+            .context("build client certificate verifier")?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut cfg = builder
+        .with_single_cert(certs, key)
+        .context("invalid TLS certificate/key pair")?;
+    cfg.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(cfg)
+}
+
+fn load_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .with_context(|| format!("read certificates {}", path.display()))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("parse certificates {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificates found in {}", path.display());
+    }
+    Ok(certs)
+}
+
+#[cfg(unix)]
+fn spawn_reload_on_sighup(tls: TlsSettings, slot: Arc<RwLock<Arc<rustls::ServerConfig>>>) {
+    use tokio::signal::unix::{SignalKind, signal};
+    tokio::spawn(async move {
+        let mut hup = match signal(SignalKind::hangup()) {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::warn!("failed to install SIGHUP handler: {}", e);
+                return;
+            }
+        };
+        while hup.recv().await.is_some() {
+            match load_tls_config(&tls) {
+                Ok(cfg) => {
+                    if let Ok(mut guard) = slot.write() {
+                        *guard = Arc::new(cfg);
+                        tracing::info!("reloaded TLS certificate from {}", tls.cert.display());
+                    }
+                }
+                Err(e) => tracing::warn!("TLS reload failed (keeping previous): {:#}", e),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn spawn_reload_on_sighup(_tls: TlsSettings, _slot: Arc<RwLock<Arc<rustls::ServerConfig>>>) {
+    tracing::warn!("TLS reload on SIGHUP is only supported on unix");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upstream_uri_keeps_path_and_query() {
+        let upstream: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let original: Uri = "/mcp?session=abc".parse().unwrap();
+        let uri = upstream_uri(upstream, &original).unwrap();
+        assert_eq!(uri.to_string(), "http://127.0.0.1:4000/mcp?session=abc");
+    }
+
+    fn loopback_policy(origins: &[&str]) -> OriginPolicy {
+        OriginPolicy {
+            allowed_origins: origins.iter().map(|s| s.to_string()).collect(),
+            allowed_hosts: OriginPolicy::default_hosts("127.0.0.1", 8081),
+        }
+    }
+
+    #[test]
+    fn non_browser_clients_need_only_a_valid_host() {
+        let policy = loopback_policy(&[]);
+        assert!(policy.check(Some("127.0.0.1:8081"), None).is_ok());
+        assert!(policy.check(Some("localhost:8081"), None).is_ok());
+        assert!(policy.check(Some("[::1]:8081"), None).is_ok());
+        assert!(policy.check(None, None).is_err());
+    }
+
+    #[test]
+    fn rebinding_host_is_rejected() {
+        let policy = loopback_policy(&[]);
+        let err = policy.check(Some("evil.example:8081"), None).unwrap_err();
+        assert!(err.contains("evil.example"));
+    }
+
+    #[test]
+    fn browser_origins_denied_unless_listed() {
+        let policy = loopback_policy(&["http://localhost:6274"]);
+        assert!(
+            policy
+                .check(Some("127.0.0.1:8081"), Some("https://evil.example"))
+                .is_err()
+        );
+        assert!(policy.check(Some("127.0.0.1:8081"), Some("null")).is_err());
+        assert!(
+            policy
+                .check(Some("127.0.0.1:8081"), Some("http://LOCALHOST:6274/"))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn wildcard_binds_default_to_loopback_names() {
+        let policy = OriginPolicy {
+            allowed_origins: Vec::new(),
+            allowed_hosts: OriginPolicy::default_hosts("0.0.0.0", 8081),
+        };
+        assert!(policy.check(Some("localhost:8081"), None).is_ok());
+        assert!(policy.check(Some("evil.example:8081"), None).is_err());
+        assert!(OriginPolicy::is_wildcard("[::]"));
+        assert!(!OriginPolicy::is_wildcard("box.lan"));
+        assert!(upstream_host() != upstream_host());
+    }
+
+    #[test]
+    fn wildcards_disable_checks() {
+        let policy = OriginPolicy {
+            allowed_origins: vec!["*".to_string()],
+            allowed_hosts: vec!["*".to_string()],
+        };
+        assert!(
+            policy
+                .check(Some("box.lan:8081"), Some("https://a.example"))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn missing_cert_file_is_reported() {
+        let tls = TlsSettings {
+            cert: PathBuf::from("/nonexistent/cert.pem"),
+            key: PathBuf::from("/nonexistent/key.pem"),
+            client_ca: None,
+            reload_on_sighup: false,
+        };
+        let err = load_tls_config(&tls).unwrap_err();
+        assert!(format!("{:#}", err).contains("/nonexistent/cert.pem"));
+    }
+}
@@ -1,10 +1,12 @@
 //! MCP server handler that exposes prepared agents as callable tools.
 //!
 //! This handler validates protocol initialization, lists tools derived from
-//! discovered/prepared agents, and routes `call_tool` requests to the in-process
-//! Codex runner.
+//! discovered/prepared agents, and routes `call_tool` requests to the configured
+//! runner (in-process Codex by default).
 
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use rust_mcp_sdk::schema::{
@@ -18,15 +20,39 @@ use rust_mcp_sdk::{
 use serde_json::{Map as JsonMap, Value as JsonValue, json};
 // no external process management here; runners handle process or in-proc logic
 
-use crate::codex_runner::{CodexRunner, InprocCodexRunner};
-use crate::model::PreparedAgent;
+use crate::codex_runner::{CodexRunOutput, CodexRunner, InprocCodexRunner, RunTrace};
+use crate::history::{HistoryEntry, HistoryStore, current_host, current_user};
+use crate::model::{AgentRun, AgentVariant, PreparedAgent};
+use crate::replay::{ReplayRecord, ReplayStore, RerunOverrides, render_comparison};
+use crate::runners::{McpSamplingClient, SamplingClient};
+use crate::usage::UsageLedger;
 
-// No external `codex` binary usage; always run Codex in-process.
+/// Meta tool that re-executes a recorded run and compares the results.
+const RERUN_TOOL: &str = "switchboard_rerun";
 
 /// Routes MCP requests and maintains an index of agent tool definitions.
 pub struct AgentsServerHandler {
     /// Mapping from tool name to prepared agent configuration.
     agents_by_tool: HashMap<String, PreparedAgent>,
+    /// Default backend that executes agent tasks.
+    runner: Arc<dyn CodexRunner>,
+    /// Backends agents may select by name via `runner = "..."`.
+    named_runners: HashMap<String, Arc<dyn CodexRunner>>,
+    /// Named runner used for every agent of a provider unless the agent picks one.
+    provider_runners: HashMap<AgentVariant, String>,
+    /// Send every agent to the default runner, ignoring agent and provider
+    /// choices (mock mode, so no call reaches a real backend).
+    runner_exclusive: bool,
+    /// Optional audit log of every invocation.
+    history: Option<HistoryStore>,
+    /// Optional store of replayable run records.
+    replays: Option<ReplayStore>,
+    /// Optional per-day token ledger enforcing daily budgets.
+    usage: Option<UsageLedger>,
+    /// Workspace root exposed to templated instructions as `{{workspace}}`.
+    workspace: Option<PathBuf>,
+    /// Environment variables templated instructions may read as `{{env.NAME}}`.
+    template_env_allow: Vec<String>,
 }
 
 impl AgentsServerHandler {
@@ -38,6 +64,283 @@ impl AgentsServerHandler {
         tracing::debug!("initialized AgentsServerHandler (tools={})", map.len());
         Self {
             agents_by_tool: map,
+            runner: Arc::new(InprocCodexRunner::new()),
+            named_runners: HashMap::from([(
+                "inproc".to_string(),
+                Arc::new(InprocCodexRunner::new()) as Arc<dyn CodexRunner>,
+            )]),
+            provider_runners: HashMap::new(),
+            runner_exclusive: false,
+            history: None,
+            replays: None,
+            usage: None,
+            workspace: None,
+            template_env_allow: Vec::new(),
+        }
+    }
+
+    /// Replace the default in-process runner (e.g. with the mock runner).
+    pub fn with_runner(mut self, runner: Arc<dyn CodexRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Make `runner` the only backend: agent `runner` choices and provider
+    /// routes are ignored.
+    pub fn with_exclusive_runner(mut self, runner: Arc<dyn CodexRunner>) -> Self {
+        self.runner = runner;
+        self.runner_exclusive = true;
+        self
+    }
+
+    /// Register a backend that agents can select with `runner = "<name>"`.
+    pub fn with_named_runner(mut self, name: &str, runner: Arc<dyn CodexRunner>) -> Self {
+        self.named_runners.insert(name.to_ascii_lowercase(), runner);
+        self
+    }
+
+    /// Route agents of `provider` to the named runner `name` by default.
+    pub fn with_provider_runner(mut self, provider: AgentVariant, name: &str) -> Self {
+        self.provider_runners
+            .insert(provider, name.to_ascii_lowercase());
+        self
+    }
+
+    /// Resolve the runner for an agent: its `runner` choice, else its
+    /// provider's runner, else the default. An exclusive runner always wins.
+    fn runner_for(&self, ra: &PreparedAgent) -> anyhow::Result<&Arc<dyn CodexRunner>> {
+        if self.runner_exclusive {
+            return Ok(&self.runner);
+        }
+        let choice = ra
+            .runner
+            .as_deref()
+            .or_else(|| self.provider_runners.get(&ra.provider).map(|s| s.as_str()));
+        match choice {
+            None => Ok(&self.runner),
+            Some(name) => self
+                .named_runners
+                .get(&name.to_ascii_lowercase())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("agent '{}' requests unknown runner '{}'", ra.name, name)
+                }),
+        }
+    }
+
+    /// Record every tool call in the given history store.
+    pub fn with_history(mut self, store: HistoryStore) -> Self {
+        self.history = Some(store);
+        self
+    }
+
+    /// Save a replay record for every tool call and expose `switchboard_rerun`.
+    pub fn with_replays(mut self, store: ReplayStore) -> Self {
+        self.replays = Some(store);
+        self
+    }
+
+    /// Track token usage per agent and day and refuse calls past daily caps.
+    pub fn with_usage(mut self, ledger: UsageLedger) -> Self {
+        self.usage = Some(ledger);
+        self
+    }
+
+    /// Workspace root used when rendering templated instructions.
+    pub fn with_workspace(mut self, dir: PathBuf) -> Self {
+        self.workspace = Some(dir);
+        self
+    }
+
+    /// Environment variables `{{env.NAME}}` may expose; all others stay unrendered.
+    pub fn with_template_env_allow(mut self, names: Vec<String>) -> Self {
+        self.template_env_allow = names;
+        self
+    }
+
+    /// Check daily budgets for an agent; the error explains which cap was hit.
+    fn check_budget(&self, ra: &PreparedAgent) -> Result<(), String> {
+        match self.usage.as_ref() {
+            Some(ledger) => ledger.check(ra),
+            None => Ok(()),
+        }
+    }
+
+    /// Run an agent, then record history, usage and a replay record for the call.
+    async fn invoke(
+        &self,
+        ra: &PreparedAgent,
+        tool: &str,
+        task: &str,
+        cwd: &str,
+        rerun_of: Option<&str>,
+        client: Option<&dyn SamplingClient>,
+    ) -> (ReplayRecord, anyhow::Result<CodexRunOutput>) {
+        let run_id = uuid::Uuid::new_v4().to_string();
+        let started_at = chrono::Utc::now();
+        // Templated instructions are rendered per call so cwd, date and git
+        // state reflect this invocation.
+        let rendered;
+        let ra = match crate::template::render_instructions(
+            ra,
+            cwd,
+            self.workspace.as_deref(),
+            &self.template_env_allow,
+        )
+        .await
+        {
+            Some(text) => {
+                let mut agent = ra.clone();
+                agent.instructions = Some(text);
+                rendered = agent;
+                &rendered
+            }
+            None => ra,
+        };
+        // Prompt/command agents run their template with the task as arguments;
+        // history and replays keep the task as given.
+        let filled = ra
+            .task_template
+            .as_deref()
+            .map(|t| crate::template::render_task(t, task));
+        let run_task = filled.as_deref().unwrap_or(task);
+        let outcome = match self.runner_for(ra) {
+            Ok(runner) => {
+                runner
+                    .exec_task_with_client(ra, tool, run_task, cwd, client)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        self.record_history(&run_id, ra, tool, task, cwd, started_at, outcome.as_ref());
+        if let (Some(ledger), Ok(Some(usage))) = (
+            self.usage.as_ref(),
+            outcome.as_ref().map(|r| r.trace.usage.as_ref()),
+        ) && let Err(e) = ledger.record(ra, usage)
+        {
+            tracing::error!("usage: failed to record tokens for {}: {:#}", tool, e);
+        }
+        let trace = outcome.as_ref().map(|r| &r.trace).ok();
+        let record = ReplayRecord {
+            run_id,
+            recorded_at: started_at,
+            tool: tool.to_string(),
+            agent: ra.name.clone(),
+            source: ra.source_path.display().to_string(),
+            task: task.to_string(),
+            cwd: cwd.to_string(),
+            model: trace.and_then(|t| t.model.clone()),
+            provider: trace.and_then(|t| t.provider.clone()),
+            ok: outcome.as_ref().map(|r| r.ok).unwrap_or(false),
+            output: outcome
+                .as_ref()
+                .map(|r| r.stdout.clone())
+                .unwrap_or_default(),
+            diff: trace.and_then(|t| t.diff.clone()),
+            rerun_of: rerun_of.map(|s| s.to_string()),
+        };
+        if let Some(store) = self.replays.as_ref()
+            && let Err(e) = store.save(&record)
+        {
+            tracing::warn!("replay: failed to save run {}: {:#}", record.run_id, e);
+        }
+        (record, outcome)
+    }
+
+    /// Re-execute a recorded run (same task and cwd) and render a comparison.
+    ///
+    /// The agent is resolved from the current definitions, so prompt changes
+    /// are picked up; `overrides` can swap the model or the agent tool.
+    pub async fn rerun(
+        &self,
+        run_id: &str,
+        overrides: &RerunOverrides,
+        client: Option<&dyn SamplingClient>,
+    ) -> anyhow::Result<(ReplayRecord, String)> {
+        let store = self
+            .replays
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("replays are disabled (REPLAY_ENABLE=false)"))?;
+        let original = store.load(run_id)?;
+        let tool = overrides.tool.as_deref().unwrap_or(&original.tool);
+        let Some(agent) = self.agents_by_tool.get(tool) else {
+            anyhow::bail!("agent tool '{tool}' is not loaded");
+        };
+        if let Err(msg) = self.check_budget(agent) {
+            anyhow::bail!(msg);
+        }
+        let mut agent = agent.clone();
+        if let Some(model) = overrides.model.as_ref() {
+            agent.run.get_or_insert_with(AgentRun::default).model = Some(model.clone());
+        }
+        tracing::info!(
+            "re-running {} with tool={} model={}",
+            run_id,
+            tool,
+            overrides.model.as_deref().unwrap_or("<agent default>")
+        );
+        let (record, outcome) = self
+            .invoke(
+                &agent,
+                tool,
+                &original.task,
+                &original.cwd,
+                Some(run_id),
+                client,
+            )
+            .await;
+        if let Err(e) = outcome {
+            anyhow::bail!("re-run failed: {e:#}");
+        }
+        let report = render_comparison(&original, &record);
+        Ok((record, report))
+    }
+
+    /// Append a history entry for a finished (or failed-to-launch) run.
+    #[allow(clippy::too_many_arguments)]
+    fn record_history(
+        &self,
+        run_id: &str,
+        ra: &PreparedAgent,
+        tool: &str,
+        task: &str,
+        cwd: &str,
+        started_at: chrono::DateTime<chrono::Utc>,
+        result: Result<&CodexRunOutput, &anyhow::Error>,
+    ) {
+        let Some(store) = self.history.as_ref() else {
+            return;
+        };
+        let ended_at = chrono::Utc::now();
+        let (ok, status, error, trace, output) = match result {
+            Ok(r) => (r.ok, r.status, None, r.trace.clone(), r.stdout.clone()),
+            Err(e) => (
+                false,
+                1,
+                Some(format!("{e:#}")),
+                RunTrace::default(),
+                String::new(),
+            ),
+        };
+        let entry = HistoryEntry {
+            run_id: run_id.to_string(),
+            tool: tool.to_string(),
+            agent: ra.name.clone(),
+            source: ra.source_path.display().to_string(),
+            task: task.to_string(),
+            cwd: cwd.to_string(),
+            user: current_user(),
+            host: current_host(),
+            started_at,
+            ended_at,
+            duration_ms: (ended_at - started_at).num_milliseconds().max(0) as u64,
+            ok,
+            status,
+            error,
+            trace,
+            output,
+        };
+        if let Err(e) = store.append(&entry) {
+            tracing::warn!("history: failed to record run for {}: {:#}", tool, e);
         }
     }
 
@@ -72,7 +375,14 @@ impl AgentsServerHandler {
                 task_schema.insert("type".to_string(), JsonValue::String("string".to_string()));
                 task_schema.insert(
                     "description".to_string(),
-                    JsonValue::String("Task to perform".to_string()),
+                    JsonValue::String(
+                        if ra.task_template.is_some() {
+                            "Arguments for the prompt ($ARGUMENTS, $1, $2, ...)"
+                        } else {
+                            "Task to perform"
+                        }
+                        .to_string(),
+                    ),
                 );
                 props.insert("task".to_string(), task_schema);
                 let mut cwd_schema = JsonMap::new();
@@ -82,6 +392,13 @@ impl AgentsServerHandler {
                     JsonValue::String("Working directory (must be an absolute path).".to_string()),
                 );
                 props.insert("cwd".to_string(), cwd_schema);
+                // Claude agent colors are passed through for clients that show them.
+                let meta = ra.color.as_ref().map(|c| {
+                    JsonMap::from_iter([(
+                        "switchboard/color".to_string(),
+                        JsonValue::String(c.clone()),
+                    )])
+                });
                 Tool {
                     annotations: None,
                     description: Some(desc),
@@ -89,16 +406,52 @@ impl AgentsServerHandler {
                         vec!["task".to_string(), "cwd".to_string()],
                         Some(props),
                     ),
-                    meta: None,
+                    meta,
                     name: tool_name.clone(),
                     output_schema: None,
                     title: None,
                 }
             })
+            .chain(self.replays.is_some().then(rerun_tool_definition))
             .collect()
     }
 }
 
+/// Definition of the `switchboard_rerun` meta tool.
+fn rerun_tool_definition() -> Tool {
+    let mut props = HashMap::<String, JsonMap<String, JsonValue>>::new();
+    for (key, desc) in [
+        ("run_id", "Run id returned by a previous agent call"),
+        (
+            "model",
+            "Optional model to use instead of the agent's configured one",
+        ),
+        (
+            "agent",
+            "Optional agent tool name to run instead (e.g. a new version)",
+        ),
+    ] {
+        let mut schema = JsonMap::new();
+        schema.insert("type".to_string(), JsonValue::String("string".to_string()));
+        schema.insert(
+            "description".to_string(),
+            JsonValue::String(desc.to_string()),
+        );
+        props.insert(key.to_string(), schema);
+    }
+    Tool {
+        annotations: None,
+        description: Some(
+            "run_id[, model, agent]: string — Re-run a recorded agent call with the same task and cwd and compare outputs and diffs side by side".to_string(),
+        ),
+        input_schema: ToolInputSchema::new(vec!["run_id".to_string()], Some(props)),
+        meta: None,
+        name: RERUN_TOOL.to_string(),
+        output_schema: None,
+        title: None,
+    }
+}
+
 #[async_trait]
 impl ServerHandlerCore for AgentsServerHandler {
     async fn handle_request(
@@ -158,6 +511,41 @@ impl ServerHandlerCore for AgentsServerHandler {
                         .map(|m| m.keys().cloned().collect::<Vec<_>>())
                         .unwrap_or_default();
                     tracing::info!("call_tool request: tool={}, arg_keys={:?}", tool, arg_keys);
+                    if tool == RERUN_TOOL && self.replays.is_some() {
+                        let arg = |k: &str| {
+                            request
+                                .params
+                                .arguments
+                                .as_ref()
+                                .and_then(|m| m.get(k))
+                                .and_then(|v| v.as_str())
+                                .map(|s| s.to_string())
+                        };
+                        let Some(run_id) = arg("run_id") else {
+                            return Err(RpcError::invalid_params()
+                                .with_message("missing required 'run_id' string".to_string()));
+                        };
+                        let overrides = RerunOverrides {
+                            model: arg("model"),
+                            tool: arg("agent"),
+                        };
+                        let sampling = McpSamplingClient::new(runtime);
+                        let payload = match self.rerun(&run_id, &overrides, Some(&sampling)).await {
+                            Ok((record, report)) => json!({
+                                "ok": record.ok,
+                                "run_id": record.run_id,
+                                "output": report,
+                            }),
+                            Err(e) => {
+                                tracing::warn!("rerun of {} failed: {:#}", run_id, e);
+                                json!({ "ok": false, "output": format!("{e:#}") })
+                            }
+                        };
+                        return Ok(rust_mcp_sdk::schema::CallToolResult::text_content(vec![
+                            TextContent::from(payload.to_string()),
+                        ])
+                        .into());
+                    }
                     let Some(ra) = self.agents_by_tool.get(&tool) else {
                         tracing::warn!("unknown tool: {}", tool);
                         return Err(RpcError::method_not_found()
@@ -231,15 +619,25 @@ impl ServerHandlerCore for AgentsServerHandler {
                         "invoking agent"
                     );
 
-                    // Always use the in-process Codex runner
-                    let runner = InprocCodexRunner::new();
-                    let result = match runner.exec_task(ra, &tool, task, cwd).await {
+                    if let Err(msg) = self.check_budget(ra) {
+                        tracing::warn!("refusing call to {}: {}", tool, msg);
+                        return Err(RpcError::invalid_request().with_message(msg));
+                    }
+
+                    let sampling = McpSamplingClient::new(runtime);
+                    let (record, outcome) = self
+                        .invoke(ra, &tool, task, cwd, None, Some(&sampling))
+                        .await;
+                    let result = match outcome {
                         Ok(r) => r,
                         Err(e) => {
-                            tracing::error!("codex execution failed: {}", e);
+                            tracing::error!("codex execution failed: {:#}", e);
+                            // The reason tells callers a refusal from a crash
                             let payload = json!({
                                 "ok": false,
+                                "run_id": record.run_id,
                                 "output": "",
+                                "error": format!("{e:#}"),
                             });
                             return Ok(rust_mcp_sdk::schema::CallToolResult::text_content(vec![
                                 TextContent::from(payload.to_string()),
@@ -259,7 +657,9 @@ impl ServerHandlerCore for AgentsServerHandler {
 
                     let payload = json!({
                         "ok": result.ok,
+                        "run_id": record.run_id,
                         "output": result.stdout,
+                        "usage": result.trace.usage,
                     });
                     if result.ok {
                         tracing::info!("codex finished successfully (code={})", result.status);
@@ -321,19 +721,15 @@ impl ServerHandlerCore for AgentsServerHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::{PreparedAgent, naming::AgentVariant};
-    use std::collections::HashMap as Map;
+    use crate::model::PreparedAgent;
 
     fn sample_agent() -> PreparedAgent {
         PreparedAgent {
             tool_name: "agent_sample".to_string(),
             name: "Sample".to_string(),
             description: "Sample agent".to_string(),
-            tags: None,
-            provider: AgentVariant::Codex,
-            mcp_servers: Map::new(),
-            instructions: None,
-            run: None,
+            source_path: "/tmp/sample.toml".into(),
+            ..Default::default()
         }
     }
 
@@ -360,4 +756,67 @@ mod tests {
         assert!(req_set.contains("task"), "required should include 'task'");
         assert!(req_set.contains("cwd"), "required should include 'cwd'");
     }
+
+    #[tokio::test]
+    async fn invoke_uses_injected_runner() {
+        let tmp = tempfile::tempdir().unwrap();
+        let fixture = tmp.path().join("mock.toml");
+        std::fs::write(
+            &fixture,
+            "[[responses]]\nagent = \"agent_sample\"\ntask = \"hello\"\noutput = \"scripted\"\n",
+        )
+        .unwrap();
+        let runner = crate::runners::MockRunner::from_paths(&[fixture]).unwrap();
+        let h = AgentsServerHandler::new(vec![sample_agent()])
+            .with_runner(Arc::new(runner))
+            .with_replays(ReplayStore::open(tmp.path().join("replays")).unwrap());
+        let (record, outcome) = h
+            .invoke(
+                &sample_agent(),
+                "agent_sample",
+                "say hello",
+                "/tmp",
+                None,
+                None,
+            )
+            .await;
+        let out = outcome.unwrap();
+        assert_eq!(out.stdout, "scripted");
+        assert_eq!(record.output, "scripted");
+
+        let (rerun, report) = h
+            .rerun(&record.run_id, &RerunOverrides::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(rerun.rerun_of.as_deref(), Some(record.run_id.as_str()));
+        assert!(report.contains("== output: identical =="));
+    }
+
+    #[tokio::test]
+    async fn exclusive_runner_overrides_agent_and_provider_choices() {
+        let tmp = tempfile::tempdir().unwrap();
+        let fixture = tmp.path().join("mock.toml");
+        std::fs::write(&fixture, "[[responses]]\noutput = \"scripted\"\n").unwrap();
+        let runner = crate::runners::MockRunner::from_paths(&[fixture]).unwrap();
+        let mut agent = sample_agent();
+        agent.runner = Some("subprocess".to_string());
+        let h = AgentsServerHandler::new(vec![agent.clone()])
+            .with_provider_runner(agent.provider, "inproc")
+            .with_exclusive_runner(Arc::new(runner));
+        let (_, outcome) = h
+            .invoke(&agent, "agent_sample", "anything", "/tmp", None, None)
+            .await;
+        assert_eq!(outcome.unwrap().stdout, "scripted");
+    }
+
+    #[test]
+    fn rerun_tool_listed_only_with_replays() {
+        let h = AgentsServerHandler::new(vec![sample_agent()]);
+        assert!(!h.tool_definitions().iter().any(|t| t.name == RERUN_TOOL));
+        let tmp = tempfile::tempdir().unwrap();
+        let h = h.with_replays(ReplayStore::open(tmp.path().join("replays")).unwrap());
+        let tools = h.tool_definitions();
+        assert_eq!(tools.len(), 2);
+        assert!(tools.iter().any(|t| t.name == RERUN_TOOL));
+    }
 }
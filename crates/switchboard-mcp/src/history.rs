@@ -0,0 +1,206 @@
+//! Persistent run history (audit log).
+//!
+//! Every agent invocation is appended as one JSON line to
+//! `SWITCHBOARD_HOME/history/YYYY-MM-DD.jsonl` (UTC date of the call). Files
+//! older than the configured retention are pruned when the store is opened.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::codex_runner::RunTrace;
+
+/// One recorded agent invocation.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub run_id: String,
+    pub tool: String,
+    pub agent: String,
+    /// Agent definition file the tool was loaded from.
+    pub source: String,
+    pub task: String,
+    pub cwd: String,
+    /// OS user and host that executed the run.
+    pub user: Option<String>,
+    pub host: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub ok: bool,
+    pub status: i32,
+    /// Launch error when the runner failed before producing output.
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub trace: RunTrace,
+    pub output: String,
+}
+
+/// Append-only JSONL store partitioned by day.
+pub struct HistoryStore {
+    dir: PathBuf,
+    /// Serializes appends from concurrent tool calls.
+    lock: Mutex<()>,
+}
+
+impl HistoryStore {
+    /// Open (and create) the history directory, pruning files older than
+    /// `retention_days`. A retention of 0 keeps everything.
+    pub fn open(dir: PathBuf, retention_days: u32) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("create history dir {}", dir.display()))?;
+        if retention_days > 0 {
+            let cutoff = Utc::now().date_naive() - chrono::Days::new(u64::from(retention_days));
+            let removed = prune_before(&dir, cutoff);
+            if removed > 0 {
+                tracing::info!(
+                    "history: pruned {} file(s) older than {} day(s)",
+                    removed,
+                    retention_days
+                );
+            }
+        }
+        Ok(Self {
+            dir,
+            lock: Mutex::new(()),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Append an entry to the file for its start date.
+    pub fn append(&self, entry: &HistoryEntry) -> anyhow::Result<()> {
+        let path = self
+            .dir
+            .join(format!("{}.jsonl", entry.started_at.format("%Y-%m-%d")));
+        let mut line = serde_json::to_string(entry).context("serialize history entry")?;
+        line.push('\n');
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| anyhow::anyhow!("history lock poisoned"))?;
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("open {}", path.display()))?;
+        f.write_all(line.as_bytes())
+            .with_context(|| format!("write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Remove `YYYY-MM-DD.jsonl` files dated strictly before `cutoff`.
+fn prune_before(dir: &Path, cutoff: NaiveDate) -> usize {
+    let Ok(rd) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for entry in rd.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let Some(date) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+        else {
+            continue;
+        };
+        if date < cutoff {
+            match std::fs::remove_file(&path) {
+                Ok(()) => removed += 1,
+                Err(e) => tracing::warn!("history: failed to remove {}: {}", path.display(), e),
+            }
+        }
+    }
+    removed
+}
+
+/// Best-effort OS user name.
+pub fn current_user() -> Option<String> {
+    ["USER", "USERNAME"]
+        .iter()
+        .find_map(|k| std::env::var(k).ok().filter(|v| !v.is_empty()))
+}
+
+/// Best-effort host name.
+pub fn current_host() -> Option<String> {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|k| std::env::var(k).ok().filter(|v| !v.is_empty()))
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(started_at: DateTime<Utc>) -> HistoryEntry {
+        HistoryEntry {
+            run_id: "r1".to_string(),
+            tool: "agent_sample".to_string(),
+            agent: "Sample".to_string(),
+            source: "/tmp/sample.toml".to_string(),
+            task: "do it".to_string(),
+            cwd: "/tmp".to_string(),
+            user: None,
+            host: None,
+            started_at,
+            ended_at: started_at,
+            duration_ms: 0,
+            ok: true,
+            status: 0,
+            error: None,
+            trace: RunTrace::default(),
+            output: "done".to_string(),
+        }
+    }
+
+    #[test]
+    fn append_writes_one_line_per_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = HistoryStore::open(tmp.path().join("history"), 30).unwrap();
+        let now = Utc::now();
+        store.append(&entry(now)).unwrap();
+        store.append(&entry(now)).unwrap();
+        let path = store
+            .dir()
+            .join(format!("{}.jsonl", now.format("%Y-%m-%d")));
+        let content = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let v: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(v["tool"], "agent_sample");
+        assert_eq!(v["output"], "done");
+        assert!(v["commands"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn open_prunes_expired_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("history");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("2000-01-01.jsonl"), "{}\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "keep").unwrap();
+        let today = dir.join(format!("{}.jsonl", Utc::now().format("%Y-%m-%d")));
+        std::fs::write(&today, "{}\n").unwrap();
+
+        HistoryStore::open(dir.clone(), 7).unwrap();
+        assert!(!dir.join("2000-01-01.jsonl").exists());
+        assert!(dir.join("notes.txt").exists());
+        assert!(today.exists());
+    }
+}
@@ -0,0 +1,202 @@
+//! `${...}` interpolation for agent and MCP server configs.
+//!
+//! Supported forms:
+//! - `${VAR}` / `${env:VAR}`: environment variable (required)
+//! - `${VAR:-default}` / `${env:VAR:-default}`: default when unset or empty
+//! - `${file:/path}`: file contents without the trailing newline (`~` and
+//!   workspace-relative paths allowed), for secrets kept out of the repo
+//! - `${workspaceFolder}`, `${userHome}`: VS Code built-ins
+//!
+//! `$${` produces a literal `${`. Anything else inside `${...}` (e.g. VS
+//! Code's `${input:...}`) is an error, as is a missing required variable.
+
+use std::path::PathBuf;
+
+type Lookup = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+pub struct Interpolator {
+    workspace: PathBuf,
+    lookup: Lookup,
+}
+
+impl Interpolator {
+    /// Interpolator reading the process environment.
+    pub fn new(workspace: PathBuf) -> Self {
+        Self::with_lookup(workspace, |name| std::env::var(name).ok())
+    }
+
+    pub fn with_lookup(
+        workspace: PathBuf,
+        lookup: impl Fn(&str) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            workspace,
+            lookup: Box::new(lookup),
+        }
+    }
+
+    /// Expand every placeholder in `s`. The error lists all failures.
+    pub fn expand(&self, s: &str) -> Result<String, String> {
+        if !s.contains('$') {
+            return Ok(s.to_string());
+        }
+        let mut out = String::with_capacity(s.len());
+        let mut errors: Vec<String> = Vec::new();
+        let mut rest = s;
+        while let Some(i) = rest.find('$') {
+            out.push_str(&rest[..i]);
+            let after = &rest[i + 1..];
+            if let Some(tail) = after.strip_prefix("${") {
+                out.push_str("${");
+                rest = tail;
+                continue;
+            }
+            let end = after.find('}').filter(|_| after.starts_with('{'));
+            let Some(end) = end else {
+                out.push('$');
+                rest = after;
+                continue;
+            };
+            match self.resolve(&after[1..end]) {
+                Ok(v) => out.push_str(&v),
+                Err(e) => errors.push(e),
+            }
+            rest = &after[end + 1..];
+        }
+        out.push_str(rest);
+        if errors.is_empty() {
+            Ok(out)
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Expand all strings inside a TOML value in place.
+    pub fn expand_toml(&self, value: &mut toml::Value) -> Result<(), String> {
+        let mut errors: Vec<String> = Vec::new();
+        self.walk_toml(value, &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    fn walk_toml(&self, value: &mut toml::Value, errors: &mut Vec<String>) {
+        match value {
+            toml::Value::String(s) => match self.expand(s) {
+                Ok(v) => *s = v,
+                Err(e) => errors.push(e),
+            },
+            toml::Value::Array(items) => {
+                for item in items {
+                    self.walk_toml(item, errors);
+                }
+            }
+            toml::Value::Table(t) => {
+                for (_, v) in t.iter_mut() {
+                    self.walk_toml(v, errors);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn resolve(&self, expr: &str) -> Result<String, String> {
+        match expr {
+            "workspaceFolder" => return Ok(self.workspace.display().to_string()),
+            "userHome" => {
+                return (self.lookup)("HOME").ok_or_else(|| "HOME is not set".to_string());
+            }
+            _ => {}
+        }
+        if let Some(path) = expr.strip_prefix("file:") {
+            let p = crate::config::expand_home(path.trim());
+            let p = if p.is_absolute() {
+                p
+            } else {
+                self.workspace.join(p)
+            };
+            return std::fs::read_to_string(&p)
+                .map(|s| s.trim_end_matches(['\n', '\r']).to_string())
+                .map_err(|e| format!("cannot read '{}': {}", p.display(), e));
+        }
+        let expr = expr.strip_prefix("env:").unwrap_or(expr);
+        let (name, default) = match expr.split_once(":-") {
+            Some((n, d)) => (n, Some(d)),
+            None => (expr, None),
+        };
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!("unsupported placeholder '${{{expr}}}'"));
+        }
+        match ((self.lookup)(name), default) {
+            (Some(v), Some(d)) if v.is_empty() => Ok(d.to_string()),
+            (Some(v), _) => Ok(v),
+            (None, Some(d)) => Ok(d.to_string()),
+            (None, None) => Err(format!("environment variable '{name}' is not set")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interp(ws: &std::path::Path) -> Interpolator {
+        Interpolator::with_lookup(ws.to_path_buf(), |name| match name {
+            "TOKEN" => Some("t0k".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn expands_env_defaults_and_builtins() {
+        let tmp = tempfile::tempdir().unwrap();
+        let i = interp(tmp.path());
+        assert_eq!(i.expand("Bearer ${TOKEN}").unwrap(), "Bearer t0k");
+        assert_eq!(i.expand("${env:TOKEN}").unwrap(), "t0k");
+        assert_eq!(i.expand("${MISSING:-fallback}").unwrap(), "fallback");
+        assert_eq!(i.expand("${EMPTY:-d}").unwrap(), "d");
+        assert_eq!(i.expand("${EMPTY}").unwrap(), "");
+        assert_eq!(
+            i.expand("${workspaceFolder}/x").unwrap(),
+            format!("{}/x", tmp.path().display())
+        );
+        assert_eq!(
+            i.expand("cost $5, $${literal}").unwrap(),
+            "cost $5, ${literal}"
+        );
+    }
+
+    #[test]
+    fn reads_secret_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("secret.txt"), "s3cret\n").unwrap();
+        let i = interp(tmp.path());
+        assert_eq!(i.expand("${file:secret.txt}").unwrap(), "s3cret");
+        assert!(i.expand("${file:nope.txt}").is_err());
+    }
+
+    #[test]
+    fn missing_variables_are_all_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        let i = interp(tmp.path());
+        let err = i.expand("${A} ${TOKEN} ${input:key}").unwrap_err();
+        assert!(err.contains("'A' is not set"), "{err}");
+        assert!(
+            err.contains("unsupported placeholder '${input:key}'"),
+            "{err}"
+        );
+
+        let mut v: toml::Value =
+            toml::from_str("[srv]\nargs = [\"${TOKEN}\"]\nenv = { K = \"${B}\" }\n").unwrap();
+        assert!(i.expand_toml(&mut v).unwrap_err().contains("'B'"));
+        assert_eq!(v["srv"]["args"][0].as_str(), Some("t0k"));
+    }
+}
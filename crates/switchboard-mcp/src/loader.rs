@@ -12,10 +12,13 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use anyhow::Context as _;
+use codex_protocol::config_types::SandboxMode;
 
+use crate::fragments::FragmentResolver;
+use crate::interpolate::Interpolator;
 use crate::mcp::enumerator::enumerate_stdio;
-use crate::mcp::{DiscoveredServers, NormalizedMcpServer, discover_stdio_servers};
+use crate::mcp::types::McpToolRef;
+use crate::mcp::{DiscoveredServers, McpTransport, NormalizedMcpServer, discover_stdio_servers};
 use crate::model::{
     AgentConfig, AgentSource, AgentVariant, EnvPrefixes, PreparedAgent, ResolvedAgent, safe_name,
     tool_name_for, tool_prefix_for,
@@ -24,9 +27,13 @@ use crate::modelmap::{ApplyOptions as ModelApplyOptions, ModelMap};
 use crate::parser::AgentParser;
 use crate::parser::anthropic_frontmatter::AnthropicFrontmatterParser;
 use crate::parser::codex_toml::CodexTomlParser;
+use crate::parser::cursor_rule::CursorRuleParser;
+use crate::parser::prompt_command::PromptCommandParser;
+use crate::parser::structured::StructuredAgentParser;
 use crate::parser::vscode_chatmode::VscodeChatmodeParser;
+use crate::scan::{ScanOptions, ScannedFile, scan_dir};
 use crate::toolmap::types::ProviderId;
-use crate::toolmap::{apply_mapping, default_mapping};
+use crate::toolmap::{ToolSets, apply_denials, apply_mapping, load_layered};
 
 /// Loader configuration controlling which providers/paths to scan and how to
 /// resolve and attach MCP servers.
@@ -40,13 +47,25 @@ pub struct LoaderSettings {
     pub enable_anthropic: bool,
     /// Enable VSCode chatmode agents and scan `vscode_dirs`.
     pub enable_vscode: bool,
+    /// Enable Cursor rule agents and scan `cursor_dirs`.
+    pub enable_cursor: bool,
+    /// Enable prompt/command agents and scan `command_dirs`.
+    pub enable_commands: bool,
     pub codex_dirs: Vec<PathBuf>,
     pub anthropic_dirs: Vec<PathBuf>,
     pub vscode_dirs: Vec<PathBuf>,
+    /// Cursor rule directories and project context files (`AGENTS.md`, `CLAUDE.md`).
+    pub cursor_dirs: Vec<PathBuf>,
+    /// Codex prompt and Claude command directories.
+    pub command_dirs: Vec<PathBuf>,
+    /// Agent (tool or display name) whose instructions and settings prompt/command agents run with.
+    pub command_base: Option<String>,
     /// Tool name prefixes per provider (e.g., `agent_`).
     pub prefix_codex: String,
     pub prefix_anthropic: String,
     pub prefix_vscode: String,
+    pub prefix_cursor: String,
+    pub prefix_command: String,
     /// Optional filter by agent name/safe_name/tag.
     pub filter: Option<String>,
     /// Discover stdio MCP servers from user/project config files.
@@ -69,6 +88,8 @@ pub struct LoaderSettings {
     pub toolmap_enable: bool,
     /// Allow mapping to inject custom stdio servers.
     pub toolmap_allow_custom_servers: bool,
+    /// `tool-map.toml` files layered over the default mapping, in order (later wins).
+    pub toolmap_files: Vec<PathBuf>,
     /// Enable model mapping (normalize model/provider tokens to canonical IDs).
     pub model_map_enable: bool,
     /// Optional mapping file path; if None, defaults to `<workspace>/.agents/model-map.toml`.
@@ -79,6 +100,18 @@ pub struct LoaderSettings {
     pub model_map_override_provider: bool,
     /// Normalize provider aliases (e.g., "Claude" -> "anthropic").
     pub model_map_normalize_provider: bool,
+    /// Global `[model_providers]` from `config.toml`; agent definitions win per id.
+    pub model_providers: Option<toml::Value>,
+    /// Directories searched for `{{> name}}` instruction fragments.
+    pub fragment_dirs: Vec<PathBuf>,
+    /// Maximum size of an agent's instructions after includes.
+    pub fragment_max_bytes: usize,
+    /// Recursive scan options per variant.
+    pub scan_codex: ScanOptions,
+    pub scan_anthropic: ScanOptions,
+    pub scan_vscode: ScanOptions,
+    pub scan_cursor: ScanOptions,
+    pub scan_commands: ScanOptions,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -87,12 +120,19 @@ pub fn default_settings(
     enable_codex: bool,
     enable_anthropic: bool,
     enable_vscode: bool,
+    enable_cursor: bool,
+    enable_commands: bool,
     codex_dirs: Vec<PathBuf>,
     anthropic_dirs: Vec<PathBuf>,
     vscode_dirs: Vec<PathBuf>,
+    cursor_dirs: Vec<PathBuf>,
+    command_dirs: Vec<PathBuf>,
+    command_base: Option<String>,
     prefix_codex: String,
     prefix_anthropic: String,
     prefix_vscode: String,
+    prefix_cursor: String,
+    prefix_command: String,
     filter: Option<String>,
     enable_mcp_discovery: bool,
     vscode_user_mcp_path: Option<PathBuf>,
@@ -104,23 +144,39 @@ pub fn default_settings(
     enum_fallback_all: bool,
     toolmap_enable: bool,
     toolmap_allow_custom_servers: bool,
+    toolmap_files: Vec<PathBuf>,
     model_map_enable: bool,
     model_map_file: Option<PathBuf>,
     model_map_strict: bool,
     model_map_override_provider: bool,
     model_map_normalize_provider: bool,
+    model_providers: Option<toml::Value>,
+    fragment_dirs: Vec<PathBuf>,
+    fragment_max_bytes: usize,
+    scan_codex: ScanOptions,
+    scan_anthropic: ScanOptions,
+    scan_vscode: ScanOptions,
+    scan_cursor: ScanOptions,
+    scan_commands: ScanOptions,
 ) -> LoaderSettings {
     LoaderSettings {
         workspace_dir,
         enable_codex,
         enable_anthropic,
         enable_vscode,
+        enable_cursor,
+        enable_commands,
         codex_dirs,
         anthropic_dirs,
         vscode_dirs,
+        cursor_dirs,
+        command_dirs,
+        command_base,
         prefix_codex,
         prefix_anthropic,
         prefix_vscode,
+        prefix_cursor,
+        prefix_command,
         filter,
         enable_mcp_discovery,
         vscode_user_mcp_path,
@@ -132,11 +188,20 @@ pub fn default_settings(
         enum_fallback_all,
         toolmap_enable,
         toolmap_allow_custom_servers,
+        toolmap_files,
         model_map_enable,
         model_map_file,
         model_map_strict,
         model_map_override_provider,
         model_map_normalize_provider,
+        model_providers,
+        fragment_dirs,
+        fragment_max_bytes,
+        scan_codex,
+        scan_anthropic,
+        scan_vscode,
+        scan_cursor,
+        scan_commands,
     }
 }
 
@@ -147,12 +212,15 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
         codex: settings.prefix_codex.as_str(),
         anthropic: settings.prefix_anthropic.as_str(),
         vscode: settings.prefix_vscode.as_str(),
+        cursor: settings.prefix_cursor.as_str(),
+        command: settings.prefix_command.as_str(),
     };
 
     if settings.enable_codex {
         agents.extend(load_variant(
             AgentVariant::Codex,
             &settings.codex_dirs,
+            &settings.scan_codex,
             &env_prefixes,
         )?);
     }
@@ -160,6 +228,7 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
         agents.extend(load_variant(
             AgentVariant::Anthropic,
             &settings.anthropic_dirs,
+            &settings.scan_anthropic,
             &env_prefixes,
         )?);
     }
@@ -167,9 +236,41 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
         agents.extend(load_variant(
             AgentVariant::Vscode,
             &settings.vscode_dirs,
+            &settings.scan_vscode,
             &env_prefixes,
         )?);
     }
+    if settings.enable_cursor {
+        agents.extend(load_variant(
+            AgentVariant::Cursor,
+            &settings.cursor_dirs,
+            &settings.scan_cursor,
+            &env_prefixes,
+        )?);
+    }
+    if settings.enable_commands {
+        agents.extend(load_variant(
+            AgentVariant::Command,
+            &settings.command_dirs,
+            &settings.scan_commands,
+            &env_prefixes,
+        )?);
+    }
+
+    // Interpolate ${...} in agent configs; a missing value makes the agent invalid.
+    let interp = Interpolator::new(settings.workspace_dir.clone());
+    agents.retain_mut(|ra| match interpolate_agent(&mut ra.config, &interp) {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::error!(
+                "agent '{}' ({}) is invalid: {}",
+                ra.config.name,
+                ra.source.describe(),
+                e
+            );
+            false
+        }
+    });
 
     dedupe_tool_names(&mut agents);
 
@@ -182,6 +283,7 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
     } else {
         DiscoveredServers::default()
     };
+    interpolate_servers(&mut discovered, &interp);
     // Avoid recursive self-attachment: skip any discovered server that looks like
     // this Switchboard MCP itself (e.g., command name "switchboard-mcp" or key "switchboard").
     // Can be disabled by setting SWITCHBOARD_SKIP_SELF=false.
@@ -239,17 +341,32 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
         }
     }
 
+    // Expand VS Code tool sets (`.vscode/*.toolsets.jsonc`) in chatmode tools
+    let toolsets = ToolSets::load(&settings.workspace_dir.join(".vscode"));
+    for ra in agents.iter_mut() {
+        if ra.source.variant == AgentVariant::Vscode
+            && let Some(refs) = ra.config.mcp_tool_refs.take()
+        {
+            ra.config.mcp_tool_refs = Some(toolsets.expand(refs));
+        }
+    }
+
     // Apply provider tool mapping first (transform bare refs to toggles or
-    // namespaced refs and optionally inject custom servers)
-    if settings.toolmap_enable {
-        let mapping = default_mapping();
-        for ra in agents.iter_mut() {
-            let provider = match ra.source.variant {
-                AgentVariant::Codex => ProviderId::Codex,
-                AgentVariant::Anthropic => ProviderId::Anthropic,
-                AgentVariant::Vscode => ProviderId::Vscode,
-            };
-            let mut cfg = ra.config.clone();
+    // namespaced refs and optionally inject custom servers), then the agent's
+    // denylist, which always applies so it can switch builtins off.
+    let mapping = load_layered(&settings.toolmap_files);
+    for ra in agents.iter_mut() {
+        let provider = match ra.source.variant {
+            AgentVariant::Codex => ProviderId::Codex,
+            AgentVariant::Anthropic => ProviderId::Anthropic,
+            AgentVariant::Vscode => ProviderId::Vscode,
+            // Rules carry no tool lists; only denials could apply
+            AgentVariant::Cursor => ProviderId::Codex,
+            // `allowed-tools` uses Claude tool names
+            AgentVariant::Command => ProviderId::Anthropic,
+        };
+        let mut cfg = ra.config.clone();
+        if settings.toolmap_enable {
             let customs = apply_mapping(
                 provider,
                 &mut cfg,
@@ -260,9 +377,11 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
             for srv in customs {
                 discovered.by_key.entry(srv.key.clone()).or_insert(srv);
             }
-            ra.config = cfg;
         }
+        apply_denials(provider, &mut cfg, &mapping);
+        ra.config = cfg;
     }
+    inherit_command_base(&mut agents, settings.command_base.as_deref());
 
     // Load model mapping if enabled
     let model_map: Option<ModelMap> = if settings.model_map_enable {
@@ -305,11 +424,9 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
                     let has_bare = refs
                         .iter()
                         .any(|r| matches!(r, crate::mcp::types::McpToolRef::Bare { .. }));
-                    // Namespaced refs: include exact servers
-                    for r in refs {
-                        if let crate::mcp::types::McpToolRef::Namespaced { server_key, .. } = r {
-                            candidates.insert(server_key.clone());
-                        }
+                    // Namespaced and whole-server refs: include exact servers
+                    for key in refs.iter().filter_map(|r| r.server_key()) {
+                        candidates.insert(key.to_string());
                     }
                     // Bare refs: include all discovered for now
                     if has_bare {
@@ -387,6 +504,7 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
                     settings.enum_fallback_all,
                     settings.limit_mcp_to_referenced,
                 );
+                deny_fully_denied_servers(ra, &inventory);
             }
         } else {
             for ra in agents.iter_mut() {
@@ -401,9 +519,54 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
         agents.retain(|ra| match_agent(&ra.config, &allowed));
     }
     // Build PreparedAgent list
+    let global_providers = settings
+        .model_providers
+        .as_ref()
+        .map(|v| {
+            let mut v = v.clone();
+            if let Some(t) = v.as_table_mut() {
+                let mut invalid = Vec::new();
+                for (id, def) in t.iter_mut() {
+                    if let Err(e) = interp.expand_toml(def) {
+                        tracing::error!("dropping model provider '{}' (config.toml): {}", id, e);
+                        invalid.push(id.clone());
+                    }
+                }
+                for id in invalid {
+                    t.remove(&id);
+                }
+            }
+            parse_model_providers(&v, "config.toml")
+        })
+        .unwrap_or_default();
+    let fragments =
+        FragmentResolver::new(settings.fragment_dirs.clone(), settings.fragment_max_bytes);
     let mut prepared: Vec<PreparedAgent> = Vec::with_capacity(agents.len());
     for ra in &agents {
-        let instructions = read_instructions(&ra.config);
+        let (instructions, fragment_files) = match read_instructions(&ra.config, &ra.source.path) {
+            Some((text, origin)) => match fragments.expand(&text, &origin) {
+                Ok(e) => {
+                    if !e.fragments.is_empty() {
+                        tracing::debug!(
+                            "agent '{}' includes {} fragment(s)",
+                            ra.config.name,
+                            e.fragments.len()
+                        );
+                    }
+                    (Some(e.text), e.fragments)
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "agent '{}' ({}) is invalid: {:#}",
+                        ra.config.name,
+                        ra.source.describe(),
+                        e
+                    );
+                    continue;
+                }
+            },
+            None => (None, Vec::new()),
+        };
         let servers_cfg = convert_servers_for_agent(ra);
         // Optionally apply model mapping to the agent config before preparing
         let mut cfg = ra.config.clone();
@@ -418,15 +581,30 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
                 },
             );
         }
+        let mut model_providers = global_providers.clone();
+        if let Some(v) = &cfg.model_providers {
+            model_providers.extend(parse_model_providers(v, &ra.source.describe()));
+        }
         prepared.push(PreparedAgent {
             tool_name: ra.tool_name.clone(),
             name: cfg.name.clone(),
             description: cfg.description.clone(),
             tags: cfg.tags.clone(),
             provider: ra.source.variant,
+            source_path: ra.source.path.clone(),
             mcp_servers: servers_cfg,
+            model_providers,
             instructions,
+            fragments: fragment_files,
+            color: cfg.color.clone(),
+            template_vars: cfg
+                .templating
+                .unwrap_or(false)
+                .then(|| cfg.vars.clone().unwrap_or_default()),
             run: cfg.run.clone(),
+            runner: cfg.runner.clone(),
+            denied_tools: cfg.deny_tools.clone().unwrap_or_default(),
+            task_template: cfg.task_template.clone(),
         });
     }
 
@@ -437,11 +615,7 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
             .config
             .mcp_tool_refs
             .as_ref()
-            .map(|v| {
-                v.iter()
-                    .filter(|r| matches!(r, crate::mcp::types::McpToolRef::Namespaced { .. }))
-                    .count()
-            })
+            .map(|v| v.iter().filter(|r| r.server_key().is_some()).count())
             .unwrap_or(0);
         let ns_matched = ra
             .config
@@ -449,12 +623,7 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
             .as_ref()
             .map(|v| {
                 v.iter()
-                    .filter_map(|r| match r {
-                        crate::mcp::types::McpToolRef::Namespaced { server_key, .. } => {
-                            Some(server_key)
-                        }
-                        _ => None,
-                    })
+                    .filter_map(|r| r.server_key())
                     .filter(|k| ra.mcp_servers.contains_key(*k))
                     .count()
             })
@@ -487,6 +656,7 @@ pub async fn prepare_all(settings: &LoaderSettings) -> anyhow::Result<Vec<Prepar
 fn load_variant(
     variant: AgentVariant,
     dirs: &[PathBuf],
+    scan: &ScanOptions,
     env_prefixes: &EnvPrefixes,
 ) -> anyhow::Result<Vec<ResolvedAgent>> {
     let prefix = tool_prefix_for(variant, env_prefixes).to_string();
@@ -494,19 +664,25 @@ fn load_variant(
         AgentVariant::Codex => 0,
         AgentVariant::Anthropic => 1,
         AgentVariant::Vscode => 2,
+        AgentVariant::Cursor => 3,
+        AgentVariant::Command => 4,
     };
     let mut out = Vec::new();
     for dir in dirs.iter() {
-        if !dir.exists() || !dir.is_dir() {
+        // Entries may also name single files (e.g. a workspace `AGENTS.md`)
+        let files = if dir.is_file() {
+            vec![ScannedFile {
+                path: dir.clone(),
+                folders: Vec::new(),
+            }]
+        } else if dir.is_dir() {
+            tracing::debug!("scanning {:?} dir {}", variant, dir.display());
+            scan_dir(dir, scan)?
+        } else {
             continue;
-        }
-        tracing::debug!("scanning {:?} dir {}", variant, dir.display());
-        for entry in fs::read_dir(dir).with_context(|| format!("read_dir {}", dir.display()))? {
-            let entry = entry?;
-            let path = entry.path();
-            if !path.is_file() {
-                continue;
-            }
+        };
+        for file in files {
+            let path = file.path;
             let content = match fs::read_to_string(&path) {
                 Ok(s) => s,
                 Err(e) => {
@@ -514,76 +690,221 @@ fn load_variant(
                     continue;
                 }
             };
-            let parsed: Option<AgentConfig> = match parser_kind {
-                0 if CodexTomlParser::supports(&path) => {
-                    Some(match CodexTomlParser::parse(&content, &path) {
-                        Ok(cfg) => cfg,
+            // (entry label within multi-agent files, agent)
+            let unlabeled = |cfgs: Vec<AgentConfig>| -> Vec<(Option<String>, AgentConfig)> {
+                cfgs.into_iter().map(|cfg| (None, cfg)).collect()
+            };
+            let parsed: Vec<(Option<String>, AgentConfig)> = match parser_kind {
+                0 if !CodexTomlParser::supports(&path)
+                    && !StructuredAgentParser::supports(&path) =>
+                {
+                    Vec::new()
+                }
+                0 if CodexTomlParser::is_abstract(&content, &path) => {
+                    tracing::debug!("skipping abstract base {}", path.display());
+                    continue;
+                }
+                0 => match CodexTomlParser::parse_entries(&content, &path, dirs) {
+                    Ok(cfgs) => cfgs,
+                    Err(e) => {
+                        tracing::warn!("skipping {}: {:#}", path.display(), e);
+                        continue;
+                    }
+                },
+                1 if AnthropicFrontmatterParser::supports(&path)
+                    && !AnthropicFrontmatterParser::has_frontmatter(&content)
+                    && !path.to_string_lossy().ends_with(".agent.md") =>
+                {
+                    // Plain Markdown (READMEs, notes) next to Claude subagents.
+                    tracing::debug!("skipping {}: no frontmatter", path.display());
+                    continue;
+                }
+                1 if AnthropicFrontmatterParser::supports(&path) => {
+                    match AnthropicFrontmatterParser::parse(&content, &path) {
+                        Ok(cfgs) => unlabeled(cfgs),
                         Err(e) => {
                             tracing::warn!("skipping {}: {:#}", path.display(), e);
                             continue;
                         }
-                    })
+                    }
                 }
-                1 if AnthropicFrontmatterParser::supports(&path) => {
-                    Some(match AnthropicFrontmatterParser::parse(&content, &path) {
-                        Ok(cfg) => cfg,
+                2 if VscodeChatmodeParser::supports(&path) => {
+                    match VscodeChatmodeParser::parse(&content, &path) {
+                        Ok(cfgs) => unlabeled(cfgs),
                         Err(e) => {
                             tracing::warn!("skipping {}: {:#}", path.display(), e);
                             continue;
                         }
-                    })
+                    }
                 }
-                2 if VscodeChatmodeParser::supports(&path) => {
-                    Some(match VscodeChatmodeParser::parse(&content, &path) {
-                        Ok(cfg) => cfg,
+                3 if CursorRuleParser::supports(&path) => {
+                    match CursorRuleParser::parse(&content, &path) {
+                        Ok(cfgs) => unlabeled(cfgs),
                         Err(e) => {
                             tracing::warn!("skipping {}: {:#}", path.display(), e);
                             continue;
                         }
-                    })
+                    }
                 }
-                _ => None,
+                4 if PromptCommandParser::supports(&path) => {
+                    match PromptCommandParser::parse(&content, &path) {
+                        Ok(cfgs) => unlabeled(cfgs),
+                        Err(e) => {
+                            tracing::warn!("skipping {}: {:#}", path.display(), e);
+                            continue;
+                        }
+                    }
+                }
+                _ => Vec::new(),
             };
-            let Some(mut cfg) = parsed else { continue };
+            for (entry, mut cfg) in parsed {
+                // Namespaced commands (`frontend/review.md`) are `frontend:review`
+                if variant == AgentVariant::Command && !file.folders.is_empty() {
+                    cfg.name = format!("{}:{}", file.folders.join(":"), cfg.name);
+                }
+                // Provide default description if empty
+                if cfg.description.trim().is_empty() {
+                    cfg.description = format!("Agent '{}': Execute tasks via Codex", cfg.name);
+                }
 
-            // Provide default description if empty
-            if cfg.description.trim().is_empty() {
-                cfg.description = format!("Agent '{}': Execute tasks via Codex", cfg.name);
-            }
+                // Map codex toggles (if provided) into run policy
+                if matches!(variant, AgentVariant::Codex)
+                    && let Some(toggles) = cfg.toggles.take()
+                {
+                    let mut run = cfg.run.take().unwrap_or_default();
+                    if toggles.include_plan_tool.is_some() {
+                        run.include_plan_tool = toggles.include_plan_tool;
+                    }
+                    if toggles.include_apply_patch_tool.is_some() {
+                        run.include_apply_patch_tool = toggles.include_apply_patch_tool;
+                    }
+                    if toggles.include_view_image_tool.is_some() {
+                        run.include_view_image_tool = toggles.include_view_image_tool;
+                    }
+                    if toggles.tools_web_search_request.is_some() {
+                        run.tools_web_search_request = toggles.tools_web_search_request;
+                    }
+                    cfg.run = Some(run);
+                }
 
-            // Map codex toggles (if provided) into run policy
-            if matches!(variant, AgentVariant::Codex)
-                && let Some(toggles) = cfg.toggles.take()
-            {
-                let mut run = cfg.run.take().unwrap_or_default();
-                if toggles.include_plan_tool.is_some() {
-                    run.include_plan_tool = toggles.include_plan_tool;
+                if scan.folder_tags && !file.folders.is_empty() {
+                    let tags = cfg.tags.get_or_insert_with(Vec::new);
+                    for folder in &file.folders {
+                        if !tags.iter().any(|t| t.eq_ignore_ascii_case(folder)) {
+                            tags.push(folder.clone());
+                        }
+                    }
                 }
-                if toggles.include_apply_patch_tool.is_some() {
-                    run.include_apply_patch_tool = toggles.include_apply_patch_tool;
+                let tool_name = if scan.folder_namespace
+                    && !file.folders.is_empty()
+                    && variant != AgentVariant::Command
+                {
+                    tool_name_for(&prefix, &format!("{}_{}", file.folders.join("_"), cfg.name))
+                } else {
+                    tool_name_for(&prefix, &cfg.name)
+                };
+                out.push(ResolvedAgent {
+                    source: AgentSource {
+                        variant,
+                        path: path.clone(),
+                        entry,
+                    },
+                    config: cfg,
+                    tool_name,
+                    mcp_servers: HashMap::new(),
+                });
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Give prompt/command agents the base agent's instructions, run settings,
+/// servers and runner. Their own `model` and `allowed-tools` win; with
+/// `allowed-tools`, built-ins the list does not grant stay off. Runs after
+/// mapping and denials, so the base's restrictions are re-applied on top:
+/// built-ins it switches off, a read-only sandbox and its MCP denials hold
+/// whatever `allowed-tools` grants.
+fn inherit_command_base(agents: &mut [ResolvedAgent], base: Option<&str>) {
+    let Some(key) = base.map(str::trim).filter(|k| !k.is_empty()) else {
+        return;
+    };
+    let Some(base) = agents
+        .iter()
+        .find(|a| {
+            a.source.variant != AgentVariant::Command
+                && (a.tool_name == key || a.config.name.eq_ignore_ascii_case(key))
+        })
+        .map(|a| a.config.clone())
+    else {
+        tracing::warn!(
+            "command base agent '{}' not found; prompts run without it",
+            key
+        );
+        return;
+    };
+    for ra in agents
+        .iter_mut()
+        .filter(|a| a.source.variant == AgentVariant::Command)
+    {
+        let cfg = &mut ra.config;
+        cfg.instructions_file = base.instructions_file.clone();
+        cfg.instructions = base.instructions.clone();
+        cfg.templating = base.templating;
+        cfg.vars = base.vars.clone();
+        cfg.mcp_servers = base.mcp_servers.clone();
+        cfg.model_providers = base.model_providers.clone();
+        if cfg.runner.is_none() {
+            cfg.runner = base.runner.clone();
+        }
+        let mut run = cfg.run.take().unwrap_or_default();
+        if cfg.mcp_tool_refs.is_some() {
+            run.include_plan_tool.get_or_insert(false);
+            run.include_apply_patch_tool.get_or_insert(false);
+            run.include_view_image_tool.get_or_insert(false);
+            run.tools_web_search_request.get_or_insert(false);
+        } else {
+            cfg.mcp_tool_refs = base.mcp_tool_refs.clone();
+            cfg.inherit_tools = base.inherit_tools;
+        }
+        if let Some(b) = &base.run {
+            run = run.or_base(b);
+            for (own, theirs) in [
+                (&mut run.include_plan_tool, b.include_plan_tool),
+                (
+                    &mut run.include_apply_patch_tool,
+                    b.include_apply_patch_tool,
+                ),
+                (&mut run.include_view_image_tool, b.include_view_image_tool),
+                (
+                    &mut run.tools_web_search_request,
+                    b.tools_web_search_request,
+                ),
+            ] {
+                if theirs == Some(false) {
+                    *own = Some(false);
                 }
-                if toggles.include_view_image_tool.is_some() {
-                    run.include_view_image_tool = toggles.include_view_image_tool;
+            }
+            if matches!(b.sandbox_mode, Some(SandboxMode::ReadOnly)) {
+                run.sandbox_mode = Some(SandboxMode::ReadOnly);
+            }
+        }
+        cfg.run = Some(run);
+        if let Some(denied) = &base.deny_tools {
+            let own = cfg.deny_tools.get_or_insert_with(Vec::new);
+            for d in denied {
+                if !own.contains(d) {
+                    own.push(d.clone());
                 }
-                if toggles.tools_web_search_request.is_some() {
-                    run.tools_web_search_request = toggles.tools_web_search_request;
+            }
+            if let Some(refs) = cfg.mcp_tool_refs.as_mut() {
+                refs.retain(|r| !own.iter().any(|d| d.covers(r)));
+                if refs.is_empty() {
+                    cfg.mcp_tool_refs = None;
                 }
-                cfg.run = Some(run);
             }
-
-            let tool_name = tool_name_for(&prefix, &cfg.name);
-            out.push(ResolvedAgent {
-                source: AgentSource {
-                    variant,
-                    path: path.clone(),
-                },
-                config: cfg,
-                tool_name,
-                mcp_servers: HashMap::new(),
-            });
         }
     }
-    Ok(out)
 }
 
 fn summarize_tools(set: &HashSet<String>, max_show: usize) -> String {
@@ -641,14 +962,18 @@ fn match_agent(cfg: &AgentConfig, allowed: &HashSet<String>) -> bool {
     false
 }
 
-fn read_instructions(agent: &AgentConfig) -> Option<String> {
+/// Instructions text and the file it came from (for resolving includes).
+fn read_instructions(agent: &AgentConfig, source: &Path) -> Option<(String, PathBuf)> {
     if let Some(instr_path) = agent.instructions_file.as_ref()
         && let Ok(s) = std::fs::read_to_string(instr_path)
         && !s.trim().is_empty()
     {
-        return Some(s);
+        return Some((s, instr_path.clone()));
     }
-    agent.instructions.clone()
+    agent
+        .instructions
+        .clone()
+        .map(|s| (s, source.to_path_buf()))
 }
 
 fn to_mcp_server_config(
@@ -716,6 +1041,149 @@ fn convert_servers_for_agent(
             ),
         }
     }
+    // Denied whole servers are never attached, whatever their origin
+    if let Some(denied) = &ra.config.deny_tools {
+        out.retain(|k, _| {
+            let keep = !denied
+                .iter()
+                .any(|d| matches!(d, McpToolRef::Server { server_key } if server_key == k));
+            if !keep {
+                tracing::info!(
+                    "agent '{}': not attaching denied server '{}'",
+                    ra.config.name,
+                    k
+                );
+            }
+            keep
+        });
+        // Servers with single denied tools run behind the filtering proxy so
+        // those tools are never listed; a server that can't be wrapped is dropped.
+        out = out
+            .into_iter()
+            .filter_map(
+                |(k, cfg)| match crate::mcp::filter::filtered_server(&k, cfg, denied) {
+                    Ok(cfg) => Some((k, cfg)),
+                    Err(e) => {
+                        tracing::warn!(
+                            "agent '{}': not attaching server '{}' with denied tools: {:#}",
+                            ra.config.name,
+                            k,
+                            e
+                        );
+                        None
+                    }
+                },
+            )
+            .collect();
+    }
+    out
+}
+
+/// Expand `${...}` in an agent's run settings, instructions path, embedded
+/// MCP servers and model providers.
+fn interpolate_agent(cfg: &mut AgentConfig, interp: &Interpolator) -> Result<(), String> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut expand = |s: &mut String| match interp.expand(s) {
+        Ok(v) => *s = v,
+        Err(e) => errors.push(e),
+    };
+    if let Some(run) = cfg.run.as_mut() {
+        for field in [
+            &mut run.model,
+            &mut run.model_provider,
+            &mut run.chatgpt_base_url,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            expand(field);
+        }
+    }
+    if let Some(path) = cfg.instructions_file.as_mut()
+        && let Some(s) = path.to_str()
+    {
+        let mut s = s.to_string();
+        expand(&mut s);
+        *path = PathBuf::from(s);
+    }
+    for table in [&mut cfg.mcp_servers, &mut cfg.model_providers]
+        .into_iter()
+        .flatten()
+    {
+        if let Err(e) = interp.expand_toml(table) {
+            errors.push(e);
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+/// Expand `${...}` in discovered servers; servers with missing values are dropped.
+fn interpolate_servers(discovered: &mut DiscoveredServers, interp: &Interpolator) {
+    discovered.by_key.retain(|key, srv| {
+        let McpTransport::Stdio { command, args, env } = &mut srv.transport;
+        let mut errors: Vec<String> = Vec::new();
+        for s in std::iter::once(command)
+            .chain(args.iter_mut())
+            .chain(env.values_mut())
+        {
+            match interp.expand(s) {
+                Ok(v) => *s = v,
+                Err(e) => errors.push(e),
+            }
+        }
+        if errors.is_empty() {
+            return true;
+        }
+        tracing::error!(
+            "dropping MCP server '{}' ({}): {}",
+            key,
+            srv.origin
+                .path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "<unknown>".to_string()),
+            errors.join("; ")
+        );
+        false
+    });
+}
+
+/// Parse a `[model_providers]` table into Codex provider definitions.
+/// `name` defaults to the provider id and `headers` is accepted for
+/// `http_headers`; invalid entries are skipped with a warning.
+fn parse_model_providers(
+    val: &toml::Value,
+    origin: &str,
+) -> HashMap<String, codex_core::ModelProviderInfo> {
+    let mut out = HashMap::new();
+    let Some(tbl) = val.as_table() else {
+        tracing::warn!(
+            "ignoring non-table model_providers in {} (found: {})",
+            origin,
+            val.type_str()
+        );
+        return out;
+    };
+    for (id, def) in tbl {
+        let mut def = def.clone();
+        if let Some(t) = def.as_table_mut() {
+            t.entry("name")
+                .or_insert_with(|| toml::Value::String(id.clone()));
+            if let Some(headers) = t.remove("headers") {
+                t.entry("http_headers").or_insert(headers);
+            }
+        }
+        match def.try_into::<codex_core::ModelProviderInfo>() {
+            Ok(p) => {
+                out.insert(id.clone(), p);
+            }
+            Err(e) => tracing::warn!("invalid model provider '{}' in {}: {}", id, origin, e),
+        }
+    }
     out
 }
 
@@ -727,7 +1195,7 @@ fn attach_mcp_servers_to_agent(
     if discovered.by_key.is_empty() {
         return;
     }
-    if !limit_to_referenced {
+    if !limit_to_referenced || ra.config.inherit_tools == Some(true) {
         ra.mcp_servers = discovered.by_key.clone();
         return;
     }
@@ -737,7 +1205,8 @@ fn attach_mcp_servers_to_agent(
         let mut has_bare = false;
         for r in refs {
             match r {
-                crate::mcp::types::McpToolRef::Namespaced { server_key, .. } => {
+                crate::mcp::types::McpToolRef::Namespaced { server_key, .. }
+                | crate::mcp::types::McpToolRef::Server { server_key } => {
                     if let Some(srv) = discovered.by_key.get(server_key) {
                         selected.insert(server_key.clone(), srv.clone());
                     }
@@ -756,6 +1225,28 @@ fn attach_mcp_servers_to_agent(
     }
 }
 
+/// Turn servers whose every enumerated tool is denied into whole-server denials.
+fn deny_fully_denied_servers(ra: &mut ResolvedAgent, inventory: &HashMap<String, HashSet<String>>) {
+    let Some(denied) = ra.config.deny_tools.as_mut() else {
+        return;
+    };
+    let mut keys: Vec<&String> = ra.mcp_servers.keys().collect();
+    keys.sort();
+    for key in keys {
+        let all_denied = inventory.get(key).is_some_and(|tools| {
+            !tools.is_empty()
+                && tools
+                    .iter()
+                    .all(|tool| denied.iter().any(|d| d.matches(key, tool)))
+        });
+        if all_denied {
+            denied.push(McpToolRef::Server {
+                server_key: key.clone(),
+            });
+        }
+    }
+}
+
 fn gate_by_inventory(
     ra: &mut ResolvedAgent,
     discovered: &DiscoveredServers,
@@ -784,6 +1275,14 @@ fn gate_by_inventory(
                         missing_namespaced.push(format!("{}/{} (no inventory)", server_key, tool));
                     }
                 }
+                crate::mcp::types::McpToolRef::Server { server_key } => {
+                    match (inventory.get(server_key), discovered.by_key.get(server_key)) {
+                        (Some(_), Some(srv)) => {
+                            selected.insert(server_key.clone(), srv.clone());
+                        }
+                        _ => missing_namespaced.push(format!("{}/* (no inventory)", server_key)),
+                    }
+                }
                 crate::mcp::types::McpToolRef::Bare { tool } => {
                     // Bare refs: find all servers exposing 'tool'
                     let matches: Vec<&String> = inventory
@@ -829,10 +1328,176 @@ fn gate_by_inventory(
             );
         }
     }
-    if selected.is_empty() && !limit_to_referenced {
+    if selected.is_empty() && (!limit_to_referenced || ra.config.inherit_tools == Some(true)) {
         // If nothing selected (no refs or filtered out) but not limited, include all
         ra.mcp_servers = discovered.by_key.clone();
     } else {
         ra.mcp_servers = selected;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::AgentRun;
+
+    fn resolved(variant: AgentVariant, tool_name: &str, config: AgentConfig) -> ResolvedAgent {
+        ResolvedAgent {
+            source: AgentSource {
+                variant,
+                path: PathBuf::from(format!("/tmp/{tool_name}")),
+                entry: None,
+            },
+            config,
+            tool_name: tool_name.to_string(),
+            mcp_servers: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn commands_keep_the_base_agents_restrictions() {
+        let base = AgentConfig {
+            name: "reviewer".to_string(),
+            instructions: Some("Review carefully.".to_string()),
+            mcp_tool_refs: Some(vec![McpToolRef::parse("github/get_pr")]),
+            run: Some(AgentRun {
+                model: Some("o3".to_string()),
+                include_apply_patch_tool: Some(false),
+                sandbox_mode: Some(SandboxMode::ReadOnly),
+                ..Default::default()
+            }),
+            deny_tools: Some(vec![McpToolRef::parse("github/delete_repo")]),
+            ..Default::default()
+        };
+        // `allowed-tools: Edit, mcp__github__delete_repo, mcp__github__list_prs` after mapping
+        let granted = AgentConfig {
+            name: "fix".to_string(),
+            mcp_tool_refs: Some(vec![
+                McpToolRef::parse("github/delete_repo"),
+                McpToolRef::parse("github/list_prs"),
+            ]),
+            run: Some(AgentRun {
+                model: Some("haiku".to_string()),
+                include_apply_patch_tool: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let plain = AgentConfig {
+            name: "summarize".to_string(),
+            ..Default::default()
+        };
+        let mut agents = vec![
+            resolved(AgentVariant::Codex, "agent_reviewer", base),
+            resolved(AgentVariant::Command, "cmd_fix", granted),
+            resolved(AgentVariant::Command, "cmd_summarize", plain),
+        ];
+        inherit_command_base(&mut agents, Some("agent_reviewer"));
+
+        let fix = &agents[1].config;
+        assert_eq!(fix.instructions.as_deref(), Some("Review carefully."));
+        let run = fix.run.as_ref().unwrap();
+        assert_eq!(run.model.as_deref(), Some("haiku"));
+        assert_eq!(run.include_apply_patch_tool, Some(false));
+        assert_eq!(run.include_plan_tool, Some(false));
+        assert!(matches!(run.sandbox_mode, Some(SandboxMode::ReadOnly)));
+        assert_eq!(
+            fix.mcp_tool_refs.as_deref(),
+            Some(&[McpToolRef::parse("github/list_prs")][..])
+        );
+        assert_eq!(
+            fix.deny_tools.as_deref(),
+            Some(&[McpToolRef::parse("github/delete_repo")][..])
+        );
+
+        let summarize = &agents[2].config;
+        assert_eq!(
+            summarize.mcp_tool_refs.as_deref(),
+            Some(&[McpToolRef::parse("github/get_pr")][..])
+        );
+        assert_eq!(summarize.run.as_ref().unwrap().model.as_deref(), Some("o3"));
+
+        // An unknown base leaves commands untouched
+        let mut agents = vec![resolved(
+            AgentVariant::Command,
+            "cmd_x",
+            AgentConfig::default(),
+        )];
+        inherit_command_base(&mut agents, Some("missing"));
+        assert!(agents[0].config.instructions.is_none());
+    }
+
+    #[test]
+    fn nested_commands_keep_their_folder_in_the_name() {
+        let dir = tempfile::tempdir().unwrap();
+        for folder in ["frontend", "backend"] {
+            fs::create_dir(dir.path().join(folder)).unwrap();
+            fs::write(
+                dir.path().join(folder).join("review.md"),
+                "Review $ARGUMENTS",
+            )
+            .unwrap();
+        }
+        fs::write(dir.path().join("deploy.md"), "Deploy $1").unwrap();
+        let prefixes = EnvPrefixes {
+            codex: "agent_",
+            anthropic: "claude_",
+            vscode: "vscode_",
+            cursor: "cur_",
+            command: "cmd_",
+        };
+        let scan = ScanOptions {
+            depth: 1,
+            folder_namespace: true,
+            ..ScanOptions::default()
+        };
+        let agents = load_variant(
+            AgentVariant::Command,
+            &[dir.path().to_path_buf()],
+            &scan,
+            &prefixes,
+        )
+        .unwrap();
+        let mut names: Vec<_> = agents
+            .iter()
+            .map(|a| (a.config.name.as_str(), a.tool_name.as_str()))
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                ("backend:review", "cmd_backend_review"),
+                ("deploy", "cmd_deploy"),
+                ("frontend:review", "cmd_frontend_review"),
+            ]
+        );
+    }
+
+    #[test]
+    fn model_providers_default_name_and_accept_headers() {
+        let val: toml::Value = toml::from_str(
+            r#"
+[ollama]
+base_url = "http://localhost:11434/v1"
+wire_api = "chat"
+headers = { "X-Team" = "switchboard" }
+
+[broken]
+wire_api = 42
+"#,
+        )
+        .unwrap();
+        let providers = parse_model_providers(&val, "test.toml");
+        assert_eq!(providers.len(), 1);
+        let p = &providers["ollama"];
+        assert_eq!(p.name, "ollama");
+        assert_eq!(p.base_url.as_deref(), Some("http://localhost:11434/v1"));
+        assert_eq!(
+            p.http_headers
+                .as_ref()
+                .and_then(|h| h.get("X-Team"))
+                .map(|s| s.as_str()),
+            Some("switchboard")
+        );
+    }
+}
@@ -1,13 +1,23 @@
 mod codex_runner;
 mod config;
+mod fragments;
+mod gateway;
 mod handler;
+mod history;
+mod interpolate;
 mod loader;
 mod mcp;
 mod model;
 mod modelmap;
 mod parser;
+mod replay;
+mod runners;
+mod scan;
+mod template;
 mod toolmap;
+mod usage;
 
+use std::collections::HashMap;
 use std::time::Duration;
 
 use env_flags::env_flags;
@@ -24,6 +34,7 @@ use rust_mcp_sdk::{McpServer, StdioTransport, TransportOptions};
 
 use crate::handler::AgentsServerHandler;
 use crate::loader::{default_settings, prepare_all};
+use crate::model::AgentVariant;
 
 fn init_tracing() {
     env_flags! {
@@ -262,6 +273,49 @@ async fn main() -> SdkResult<()> {
     // Initialize tracing early
     init_tracing();
 
+    // `switchboard-mcp replay <run-id> ...` re-runs a recorded call instead of serving.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let replay_cmd = match replay::parse_replay_args(&args) {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            eprintln!("{e:#}");
+            std::process::exit(2);
+        }
+    };
+
+    // `switchboard-mcp instructions <agent>` prints an agent's resolved instructions.
+    let show_instructions = match args.as_slice() {
+        [cmd, agent] if cmd == "instructions" => Some(agent.clone()),
+        [cmd, ..] if cmd == "instructions" => {
+            eprintln!("usage: switchboard-mcp instructions <agent-tool-or-name>");
+            std::process::exit(2);
+        }
+        _ => None,
+    };
+
+    // `switchboard-mcp mcp-filter ...` proxies an MCP server with some tools
+    // denied; agents launch it in place of such servers.
+    match mcp::filter::parse_filter_args(&args) {
+        Ok(Some(cmd)) => match mcp::filter::run_filter(cmd).await {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("mcp-filter: {e:#}");
+                std::process::exit(1);
+            }
+        },
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{e:#}");
+            std::process::exit(2);
+        }
+    }
+
+    // `switchboard-mcp schema` prints the JSON Schema for YAML/JSON agent files.
+    if matches!(args.as_slice(), [cmd] if cmd == "schema") {
+        print!("{}", parser::structured::AGENT_SCHEMA);
+        return Ok(());
+    }
+
     env_flags! {
         /// Transport: "stdio" (default) or "http"
         TRANSPORT: &str = "stdio";
@@ -273,12 +327,28 @@ async fn main() -> SdkResult<()> {
         PING_SECS: u64 = 5;
         /// Enable JSON response mode for HTTP
         HTTP_JSON: bool = false;
+        /// TLS certificate chain (PEM path). With TLS_KEY, serves HTTPS instead of HTTP.
+        TLS_CERT: &str = "";
+        /// TLS private key (PEM path)
+        TLS_KEY: &str = "";
+        /// Optional client CA bundle (PEM path); when set, clients must present a certificate (mTLS)
+        TLS_CLIENT_CA: &str = "";
+        /// Reload TLS certificate/key on SIGHUP
+        TLS_RELOAD_ON_SIGHUP: bool = true;
+        /// Comma-separated browser origins allowed to call the HTTP transport ("*" allows any). Empty denies all browser origins.
+        HTTP_ALLOWED_ORIGINS: &str = "";
+        /// Comma-separated Host header values accepted by the HTTP transport ("*" disables the check). Empty uses loopback defaults.
+        HTTP_ALLOWED_HOSTS: &str = "";
         /// Workspace directory base for Switchboard MCP. If empty, defaults to the current execution directory.
         WORKSPACE_DIR: &str = "";
-        /// Enable loaders (all default to true)
+        /// Enable loaders (agent loaders default to true)
         AGENTS_ENABLE_CODEX: bool = true;
         AGENTS_ENABLE_ANTHROPIC: bool = true;
         AGENTS_ENABLE_VSCODE: bool = true;
+        /// Cursor rules and AGENTS.md/CLAUDE.md guides are opt-in
+        AGENTS_ENABLE_CURSOR: bool = false;
+        /// Prompt commands are opt-in; they run with AGENTS_COMMAND_BASE's instructions
+        AGENTS_ENABLE_COMMANDS: bool = false;
         /// Directories (comma-separated) for each variant. If empty, defaults are used.
         /// codex: <workspace>/.agents and $HOME/.agents
         AGENTS_DIRS: &str = "";
@@ -286,10 +356,20 @@ async fn main() -> SdkResult<()> {
         ANTHROPIC_AGENTS_DIRS: &str = "";
         /// vscode: <workspace>/.github/chatmodes and $HOME/.chatmodes
         VSCODE_CHATMODES_DIRS: &str = "";
+        /// cursor: <workspace>/.cursor/rules plus <workspace>/AGENTS.md and CLAUDE.md (dirs or files)
+        CURSOR_RULES_DIRS: &str = "";
+        /// commands: <workspace>/.claude/commands, $HOME/.claude/commands and $HOME/.codex/prompts
+        COMMAND_PROMPTS_DIRS: &str = "";
+        /// Agent (tool or display name) whose instructions and settings prompts/commands run with
+        AGENTS_COMMAND_BASE: &str = "";
+        /// Comma-separated environment variables templated instructions may read as {{env.NAME}}; empty allows none
+        AGENTS_TEMPLATE_ENV_ALLOW: &str = "";
         /// Tool prefixes per variant
         AGENTS_PREFIX_CODEX: &str = "agent_";
         AGENTS_PREFIX_ANTHROPIC: &str = "anth_";
         AGENTS_PREFIX_VSCODE: &str = "vsc_";
+        AGENTS_PREFIX_CURSOR: &str = "cur_";
+        AGENTS_PREFIX_COMMAND: &str = "cmd_";
         /// Optional filter for which agents to expose. Comma/whitespace separated.
         AGENTS_FILTER: &str = "";
         /// MCP discovery (stdio only) across providers (Claude/VSCode/Cursor)
@@ -312,6 +392,8 @@ async fn main() -> SdkResult<()> {
         AGENTS_TOOLMAP_ENABLE: bool = true;
         /// Allow mapping to inject custom stdio servers
         AGENTS_TOOLMAP_ALLOW_CUSTOM_SERVERS: bool = true;
+        /// Optional tool map TOML path; if empty, layers $SWITCHBOARD_HOME/tool-map.toml then <workspace>/.agents/tool-map.toml
+        AGENTS_TOOLMAP_FILE: &str = "";
         /// Enable model mapping (normalize model/provider tokens)
         AGENTS_MODEL_MAP_ENABLE: bool = true;
         /// Optional model map TOML path; if empty, defaults to <workspace>/.agents/model-map.toml
@@ -322,6 +404,24 @@ async fn main() -> SdkResult<()> {
         AGENTS_MODEL_MAP_OVERRIDE_PROVIDER: bool = false;
         /// Normalize provider aliases (e.g., Claude -> anthropic)
         AGENTS_MODEL_MAP_NORMALIZE_PROVIDER: bool = true;
+        /// Record every agent invocation under $SWITCHBOARD_HOME/history
+        HISTORY_ENABLE: bool = true;
+        /// Days of history to keep (0 keeps everything)
+        HISTORY_RETENTION_DAYS: u32 = 30;
+        /// Save replayable run records under $SWITCHBOARD_HOME/replays and expose switchboard_rerun
+        REPLAY_ENABLE: bool = true;
+        /// Default runner backend: "inproc" (Codex in-process), "subprocess" (codex CLI), "sampling" (client model) or "mock" (scripted fixtures)
+        AGENTS_RUNNER: &str = "inproc";
+        /// codex CLI used by the subprocess runner
+        CODEX_BIN: &str = "codex";
+        /// Comma-separated mock fixture files/dirs; defaults to <workspace>/.agents/mock
+        AGENTS_MOCK_FIXTURES: &str = "";
+        /// Comma-separated instruction fragment dirs; defaults to <workspace>/.agents/fragments and $SWITCHBOARD_HOME/fragments
+        AGENTS_FRAGMENT_DIRS: &str = "";
+        /// Maximum size of an agent's instructions after includes
+        AGENTS_FRAGMENT_MAX_BYTES: usize = 262144;
+        /// Subfolder levels scanned below each agent dir (0 = top level only); overrides [agents.scan.*] depth
+        AGENTS_SCAN_DEPTH: usize = scan::DEFAULT_DEPTH;
     }
 
     tracing::info!("starting switchboard-mcp (transport={})", *TRANSPORT);
@@ -428,6 +528,48 @@ async fn main() -> SdkResult<()> {
             .collect::<Vec<_>>()
             .join(", ")
     );
+    let cursor_dirs: Vec<_> = if !(*CURSOR_RULES_DIRS).is_empty() {
+        (*CURSOR_RULES_DIRS)
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| expand(s.trim()))
+            .collect()
+    } else {
+        vec![
+            workspace_dir.join(".cursor/rules"),
+            workspace_dir.join("AGENTS.md"),
+            workspace_dir.join("CLAUDE.md"),
+        ]
+    };
+    tracing::debug!(
+        "cursor rule dirs: {}",
+        cursor_dirs
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let command_dirs: Vec<_> = if !(*COMMAND_PROMPTS_DIRS).is_empty() {
+        (*COMMAND_PROMPTS_DIRS)
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| expand(s.trim()))
+            .collect()
+    } else {
+        vec![
+            workspace_dir.join(".claude/commands"),
+            expand("~/.claude/commands"),
+            expand("~/.codex/prompts"),
+        ]
+    };
+    tracing::debug!(
+        "command prompt dirs: {}",
+        command_dirs
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
 
     // Load user config for agent defaults and merge where env not set
     let user_cfg = crate::config::load_user_config(&sb_home).ok().flatten();
@@ -460,6 +602,24 @@ async fn main() -> SdkResult<()> {
             .and_then(|a| a.enable_vscode)
             .unwrap_or(*AGENTS_ENABLE_VSCODE)
     };
+    let enable_cursor = if env_set("AGENTS_ENABLE_CURSOR") {
+        *AGENTS_ENABLE_CURSOR
+    } else {
+        user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.enable_cursor)
+            .unwrap_or(*AGENTS_ENABLE_CURSOR)
+    };
+    let enable_commands = if env_set("AGENTS_ENABLE_COMMANDS") {
+        *AGENTS_ENABLE_COMMANDS
+    } else {
+        user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.enable_commands)
+            .unwrap_or(*AGENTS_ENABLE_COMMANDS)
+    };
 
     // Allow user config to add extra dirs (appended) when env isn’t explicitly set
     let mut codex_dirs = codex_dirs;
@@ -504,6 +664,42 @@ async fn main() -> SdkResult<()> {
             }
         }
     }
+    let mut cursor_dirs = cursor_dirs;
+    if !env_set("CURSOR_RULES_DIRS")
+        && let Some(extra) = user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.cursor_dirs.as_ref())
+    {
+        for p in extra {
+            let pb = crate::config::expand_home(p);
+            if !cursor_dirs.contains(&pb) {
+                cursor_dirs.push(pb);
+            }
+        }
+    }
+    let mut command_dirs = command_dirs;
+    if !env_set("COMMAND_PROMPTS_DIRS")
+        && let Some(extra) = user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.command_dirs.as_ref())
+    {
+        for p in extra {
+            let pb = crate::config::expand_home(p);
+            if !command_dirs.contains(&pb) {
+                command_dirs.push(pb);
+            }
+        }
+    }
+    let command_base: Option<String> = if env_set("AGENTS_COMMAND_BASE") {
+        Some((*AGENTS_COMMAND_BASE).to_string()).filter(|s| !s.trim().is_empty())
+    } else {
+        user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.command_base.clone())
+    };
 
     // Prefixes and other knobs (env wins, else config, else defaults)
     let prefix_codex = if env_set("AGENTS_PREFIX_CODEX") {
@@ -533,6 +729,24 @@ async fn main() -> SdkResult<()> {
             .and_then(|a| a.prefix_vscode.clone())
             .unwrap_or_else(|| (*AGENTS_PREFIX_VSCODE).to_string())
     };
+    let prefix_cursor = if env_set("AGENTS_PREFIX_CURSOR") {
+        (*AGENTS_PREFIX_CURSOR).to_string()
+    } else {
+        user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.prefix_cursor.clone())
+            .unwrap_or_else(|| (*AGENTS_PREFIX_CURSOR).to_string())
+    };
+    let prefix_command = if env_set("AGENTS_PREFIX_COMMAND") {
+        (*AGENTS_PREFIX_COMMAND).to_string()
+    } else {
+        user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.prefix_command.clone())
+            .unwrap_or_else(|| (*AGENTS_PREFIX_COMMAND).to_string())
+    };
 
     let filter_arg: Option<String> = if !(*AGENTS_FILTER).is_empty() || env_set("AGENTS_FILTER") {
         Some((*AGENTS_FILTER).to_string())
@@ -633,6 +847,28 @@ async fn main() -> SdkResult<()> {
             .and_then(|a| a.toolmap_allow_custom_servers)
             .unwrap_or(*AGENTS_TOOLMAP_ALLOW_CUSTOM_SERVERS)
     };
+    // Tool map files (env wins, else config, else user then workspace layers)
+    let toolmap_file = if env_set("AGENTS_TOOLMAP_FILE") && !(*AGENTS_TOOLMAP_FILE).is_empty() {
+        Some(std::path::PathBuf::from((*AGENTS_TOOLMAP_FILE).to_string()))
+    } else {
+        user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.toolmap_file.as_ref())
+            .map(|s| crate::config::expand_home(s))
+    };
+    let toolmap_files = match toolmap_file {
+        Some(p) => {
+            if !p.is_file() {
+                tracing::warn!("tool map {} not found; using defaults", p.display());
+            }
+            vec![p]
+        }
+        None => vec![
+            sb_home.join("tool-map.toml"),
+            workspace_dir.join(".agents").join("tool-map.toml"),
+        ],
+    };
     let model_map_enable = if env_set("AGENTS_MODEL_MAP_ENABLE") {
         *AGENTS_MODEL_MAP_ENABLE
     } else {
@@ -682,17 +918,69 @@ async fn main() -> SdkResult<()> {
             .unwrap_or(*AGENTS_MODEL_MAP_NORMALIZE_PROVIDER)
     };
 
+    // Instruction fragment dirs (env wins, else config dirs ahead of the defaults)
+    let fragment_dirs: Vec<_> = if !(*AGENTS_FRAGMENT_DIRS).is_empty() {
+        (*AGENTS_FRAGMENT_DIRS)
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| expand(s.trim()))
+            .collect()
+    } else {
+        let mut dirs: Vec<_> = user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.fragment_dirs.as_ref())
+            .map(|v| v.iter().map(|p| crate::config::expand_home(p)).collect())
+            .unwrap_or_default();
+        dirs.push(workspace_dir.join(".agents/fragments"));
+        dirs.push(sb_home.join("fragments"));
+        dirs
+    };
+    let fragment_max_bytes = if env_set("AGENTS_FRAGMENT_MAX_BYTES") {
+        *AGENTS_FRAGMENT_MAX_BYTES
+    } else {
+        user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.fragment_max_bytes)
+            .unwrap_or(*AGENTS_FRAGMENT_MAX_BYTES)
+    };
+
+    // Recursive scan options per variant ([agents.scan.<variant>]; env depth wins)
+    let scan_cfgs = user_cfg
+        .as_ref()
+        .and_then(|c| c.agents.as_ref())
+        .and_then(|a| a.scan.as_ref());
+    let scan_depth = env_set("AGENTS_SCAN_DEPTH").then_some(*AGENTS_SCAN_DEPTH);
+    let scan_codex =
+        scan::ScanOptions::from_cfg(scan_cfgs.and_then(|s| s.codex.as_ref()), scan_depth);
+    let scan_anthropic =
+        scan::ScanOptions::from_cfg(scan_cfgs.and_then(|s| s.anthropic.as_ref()), scan_depth);
+    let scan_vscode =
+        scan::ScanOptions::from_cfg(scan_cfgs.and_then(|s| s.vscode.as_ref()), scan_depth);
+    let scan_cursor =
+        scan::ScanOptions::from_cfg(scan_cfgs.and_then(|s| s.cursor.as_ref()), scan_depth);
+    let scan_commands =
+        scan::ScanOptions::from_cfg(scan_cfgs.and_then(|s| s.commands.as_ref()), scan_depth);
+
     let settings = default_settings(
         workspace_dir.clone(),
         enable_codex,
         enable_anthropic,
         enable_vscode,
+        enable_cursor,
+        enable_commands,
         codex_dirs,
         anthropic_dirs,
         vscode_dirs,
+        cursor_dirs,
+        command_dirs,
+        command_base,
         prefix_codex,
         prefix_anthropic,
         prefix_vscode,
+        prefix_cursor,
+        prefix_command,
         filter_arg,
         enable_mcp_discovery,
         vscode_user_mcp,
@@ -704,11 +992,20 @@ async fn main() -> SdkResult<()> {
         enum_fallback_all,
         toolmap_enable,
         toolmap_allow_custom_servers,
+        toolmap_files,
         model_map_enable,
         model_map_file,
         model_map_strict,
         model_map_override_provider,
         model_map_normalize_provider,
+        user_cfg.as_ref().and_then(|c| c.model_providers.clone()),
+        fragment_dirs,
+        fragment_max_bytes,
+        scan_codex,
+        scan_anthropic,
+        scan_vscode,
+        scan_cursor,
+        scan_commands,
     );
 
     let agents = match prepare_all(&settings).await {
@@ -720,6 +1017,22 @@ async fn main() -> SdkResult<()> {
     };
     tracing::info!("loaded {} agent(s) across enabled variants", agents.len());
 
+    if let Some(key) = show_instructions {
+        let found = agents
+            .iter()
+            .find(|a| a.tool_name == key || a.name.eq_ignore_ascii_case(&key));
+        match found {
+            Some(agent) => {
+                println!("{}", fragments::instructions_view(agent));
+                return Ok(());
+            }
+            None => {
+                eprintln!("no agent named '{key}'");
+                std::process::exit(1);
+            }
+        }
+    }
+
     // MCP initialize details and capabilities
     let server_details = InitializeResult {
         server_info: Implementation {
@@ -739,7 +1052,224 @@ async fn main() -> SdkResult<()> {
     };
 
     // Handler with in-memory registry
-    let handler = AgentsServerHandler::new(agents);
+    // Env vars exposed to templates (env wins, else [agents] in config.toml)
+    let template_env_allow: Vec<String> = if env_set("AGENTS_TEMPLATE_ENV_ALLOW") {
+        (*AGENTS_TEMPLATE_ENV_ALLOW)
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    } else {
+        user_cfg
+            .as_ref()
+            .and_then(|c| c.agents.as_ref())
+            .and_then(|a| a.template_env_allow.clone())
+            .unwrap_or_default()
+    };
+    let mut handler = AgentsServerHandler::new(agents)
+        .with_workspace(workspace_dir.clone())
+        .with_template_env_allow(template_env_allow);
+
+    // Runner backend (env wins, else [runner] in config.toml)
+    let runner_cfg = user_cfg.as_ref().and_then(|c| c.runner.as_ref());
+    let runner_kind = if env_set("AGENTS_RUNNER") {
+        (*AGENTS_RUNNER).to_string()
+    } else {
+        runner_cfg
+            .and_then(|r| r.kind.clone())
+            .unwrap_or_else(|| (*AGENTS_RUNNER).to_string())
+    };
+    let codex_bin = if env_set("CODEX_BIN") {
+        std::path::PathBuf::from(*CODEX_BIN)
+    } else {
+        runner_cfg
+            .and_then(|r| r.codex_bin.as_deref())
+            .map(crate::config::expand_home)
+            .unwrap_or_else(|| std::path::PathBuf::from(*CODEX_BIN))
+    };
+    // Agents can always pick "inproc", "subprocess" or "sampling" per definition.
+    let subprocess: std::sync::Arc<dyn codex_runner::CodexRunner> =
+        std::sync::Arc::new(runners::SubprocessCodexRunner::new(codex_bin.clone()));
+    handler = handler.with_named_runner("subprocess", subprocess.clone());
+    let sampling: std::sync::Arc<dyn codex_runner::CodexRunner> =
+        std::sync::Arc::new(runners::SamplingRunner::new(
+            runner_cfg.and_then(|r| r.sampling_max_turns).unwrap_or(24),
+            runner_cfg
+                .and_then(|r| r.sampling_max_tokens)
+                .unwrap_or(4096),
+        ));
+    handler = handler.with_named_runner("sampling", sampling.clone());
+    // Command runners from [runners.<name>] in config.toml
+    let mut command_runners: HashMap<String, std::sync::Arc<dyn codex_runner::CodexRunner>> =
+        HashMap::new();
+    for (name, rc) in user_cfg
+        .as_ref()
+        .and_then(|c| c.runners.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        let runner = runners::CommandRunner {
+            name: name.clone(),
+            command: rc.command.clone(),
+            args: rc.args.clone().unwrap_or_default(),
+            timeout: Duration::from_secs(rc.timeout_secs.unwrap_or(600)),
+            env_allowlist: rc
+                .env_allowlist
+                .clone()
+                .unwrap_or_else(runners::CommandRunner::default_env_allowlist),
+            env: rc.env.clone().unwrap_or_default(),
+        };
+        tracing::info!("registered command runner '{}' ({})", name, rc.command);
+        let runner: std::sync::Arc<dyn codex_runner::CodexRunner> = std::sync::Arc::new(runner);
+        handler = handler.with_named_runner(name, runner.clone());
+        command_runners.insert(name.to_ascii_lowercase(), runner);
+    }
+    for (provider, name) in runner_cfg
+        .and_then(|r| r.providers.as_ref())
+        .into_iter()
+        .flatten()
+    {
+        let variant = match provider.to_ascii_lowercase().as_str() {
+            "codex" => AgentVariant::Codex,
+            "anthropic" | "claude" => AgentVariant::Anthropic,
+            "vscode" => AgentVariant::Vscode,
+            "cursor" => AgentVariant::Cursor,
+            "command" | "commands" => AgentVariant::Command,
+            other => {
+                tracing::warn!("ignoring runner for unknown provider '{}'", other);
+                continue;
+            }
+        };
+        tracing::info!("{:?} agents default to runner '{}'", variant, name);
+        handler = handler.with_provider_runner(variant, name);
+    }
+
+    match runner_kind.to_ascii_lowercase().as_str() {
+        "inproc" => {}
+        "subprocess" => {
+            tracing::info!("using subprocess runner (bin={})", codex_bin.display());
+            handler = handler.with_runner(subprocess);
+        }
+        "sampling" => {
+            tracing::info!("using sampling runner (client's model via sampling/createMessage)");
+            handler = handler.with_runner(sampling);
+        }
+        "mock" => {
+            let fixtures: Vec<std::path::PathBuf> = if !(*AGENTS_MOCK_FIXTURES).is_empty() {
+                (*AGENTS_MOCK_FIXTURES)
+                    .split(',')
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .map(std::path::PathBuf::from)
+                    .collect()
+            } else if let Some(list) = runner_cfg.and_then(|r| r.mock_fixtures.as_ref()) {
+                list.iter().map(|s| crate::config::expand_home(s)).collect()
+            } else {
+                vec![workspace_dir.join(".agents/mock")]
+            };
+            match runners::MockRunner::from_paths(&fixtures) {
+                Ok(r) => {
+                    tracing::info!("using mock runner (fixtures={:?})", fixtures);
+                    // Every agent runs on the mock, whatever runner it names
+                    handler = handler.with_exclusive_runner(std::sync::Arc::new(r));
+                }
+                Err(e) => {
+                    tracing::error!("failed to load mock fixtures: {:#}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        other if command_runners.contains_key(other) => {
+            tracing::info!("using command runner '{}' by default", other);
+            handler = handler.with_runner(command_runners[other].clone());
+        }
+        other => {
+            tracing::error!(
+                "unknown runner '{}' (expected inproc, subprocess, sampling, mock or a [runners.<name>] entry)",
+                other
+            );
+            std::process::exit(1);
+        }
+    }
+
+    // Run history (env wins, else [history] in config.toml)
+    let history_cfg = user_cfg.as_ref().and_then(|c| c.history.as_ref());
+    let history_enable = if env_set("HISTORY_ENABLE") {
+        *HISTORY_ENABLE
+    } else {
+        history_cfg
+            .and_then(|h| h.enabled)
+            .unwrap_or(*HISTORY_ENABLE)
+    };
+    let history_retention = if env_set("HISTORY_RETENTION_DAYS") {
+        *HISTORY_RETENTION_DAYS
+    } else {
+        history_cfg
+            .and_then(|h| h.retention_days)
+            .unwrap_or(*HISTORY_RETENTION_DAYS)
+    };
+    if history_enable {
+        match history::HistoryStore::open(sb_home.join("history"), history_retention) {
+            Ok(store) => {
+                tracing::info!(
+                    "history: recording runs to {} (retention_days={})",
+                    store.dir().display(),
+                    history_retention
+                );
+                handler = handler.with_history(store);
+            }
+            Err(e) => tracing::warn!("history disabled: {:#}", e),
+        }
+    }
+
+    // Token usage ledger with optional daily caps from [budgets] in config.toml
+    let budgets = user_cfg
+        .as_ref()
+        .and_then(|c| c.budgets.as_ref())
+        .map(|b| usage::Budgets {
+            daily_per_agent: b.daily_per_agent,
+            agents: b.agents.clone().unwrap_or_default(),
+            tags: b.tags.clone().unwrap_or_default(),
+        })
+        .unwrap_or_default();
+    match usage::UsageLedger::open(sb_home.join("usage"), budgets) {
+        Ok(ledger) => {
+            tracing::info!("usage: recording token usage to {}", ledger.dir().display());
+            handler = handler.with_usage(ledger);
+        }
+        Err(e) => tracing::warn!("usage tracking disabled: {:#}", e),
+    }
+
+    // Replay records (env wins, else [replays] in config.toml); the replay
+    // command always needs the store.
+    let replay_enable = if env_set("REPLAY_ENABLE") {
+        *REPLAY_ENABLE
+    } else {
+        user_cfg
+            .as_ref()
+            .and_then(|c| c.replays.as_ref())
+            .and_then(|r| r.enabled)
+            .unwrap_or(*REPLAY_ENABLE)
+    };
+    if replay_enable || replay_cmd.is_some() {
+        match replay::ReplayStore::open(sb_home.join("replays")) {
+            Ok(store) => handler = handler.with_replays(store),
+            Err(e) => tracing::warn!("replays disabled: {:#}", e),
+        }
+    }
+
+    if let Some(cmd) = replay_cmd {
+        match handler.rerun(&cmd.run_id, &cmd.overrides, None).await {
+            Ok((_, report)) => {
+                println!("{report}");
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("replay failed: {e:#}");
+                std::process::exit(1);
+            }
+        }
+    }
 
     if *TRANSPORT == "stdio" {
         let transport = StdioTransport::new(TransportOptions::default())?;
@@ -757,30 +1287,151 @@ async fn main() -> SdkResult<()> {
         let host = (*HOST).to_string();
         let port = *PORT;
         let ping = Duration::from_secs(*PING_SECS);
+
+        // TLS (env wins, else [server.tls] in config.toml)
+        let tls_cfg = user_cfg
+            .as_ref()
+            .and_then(|c| c.server.as_ref())
+            .and_then(|s| s.tls.as_ref());
+        let tls_path = |env_key: &str, env_val: &str, cfg_val: Option<&String>| {
+            if env_set(env_key) && !env_val.is_empty() {
+                Some(std::path::PathBuf::from(env_val))
+            } else {
+                cfg_val.map(|s| crate::config::expand_home(s))
+            }
+        };
+        let tls_cert = tls_path("TLS_CERT", *TLS_CERT, tls_cfg.and_then(|t| t.cert.as_ref()));
+        let tls_key = tls_path("TLS_KEY", *TLS_KEY, tls_cfg.and_then(|t| t.key.as_ref()));
+        let tls_client_ca = tls_path(
+            "TLS_CLIENT_CA",
+            *TLS_CLIENT_CA,
+            tls_cfg.and_then(|t| t.client_ca.as_ref()),
+        );
+        let tls_reload = if env_set("TLS_RELOAD_ON_SIGHUP") {
+            *TLS_RELOAD_ON_SIGHUP
+        } else {
+            tls_cfg
+                .and_then(|t| t.reload_on_sighup)
+                .unwrap_or(*TLS_RELOAD_ON_SIGHUP)
+        };
+        let tls = match (tls_cert, tls_key) {
+            (Some(cert), Some(key)) => Some(gateway::TlsSettings {
+                cert,
+                key,
+                client_ca: tls_client_ca,
+                reload_on_sighup: tls_reload,
+            }),
+            (None, None) if tls_client_ca.is_none() => None,
+            _ => {
+                // Never fall back to plain HTTP when TLS was asked for
+                tracing::error!(
+                    "TLS requires both a certificate and a key (TLS_CERT/TLS_KEY), also when a client CA is set"
+                );
+                std::process::exit(1);
+            }
+        };
+
+        // Host/Origin allowlists (env wins, else [server] in config.toml)
+        let server_cfg = user_cfg.as_ref().and_then(|c| c.server.as_ref());
+        let split_list = |v: &str| -> Vec<String> {
+            v.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        };
+        let allowed_origins = if env_set("HTTP_ALLOWED_ORIGINS") {
+            split_list(*HTTP_ALLOWED_ORIGINS)
+        } else {
+            server_cfg
+                .and_then(|s| s.allowed_origins.clone())
+                .unwrap_or_default()
+        };
+        let mut allowed_hosts = if env_set("HTTP_ALLOWED_HOSTS") {
+            split_list(*HTTP_ALLOWED_HOSTS)
+        } else {
+            server_cfg
+                .and_then(|s| s.allowed_hosts.clone())
+                .unwrap_or_default()
+        };
+        if allowed_hosts.is_empty() {
+            if gateway::OriginPolicy::is_wildcard(&host) {
+                tracing::warn!(
+                    "bound to {} without HTTP_ALLOWED_HOSTS; only loopback Host names are accepted. List the names remote clients use (e.g. box.lan:{})",
+                    host,
+                    port
+                );
+            }
+            allowed_hosts = gateway::OriginPolicy::default_hosts(&host, port);
+        }
+        if allowed_hosts.iter().any(|h| h == "*") {
+            tracing::warn!(
+                "Host header validation disabled; set HTTP_ALLOWED_HOSTS to protect against DNS rebinding"
+            );
+        }
+        let policy = gateway::OriginPolicy {
+            allowed_origins,
+            allowed_hosts,
+        };
+
+        // The SDK server binds an ephemeral loopback port itself (no window
+        // between picking and binding it); the gateway owns the public address,
+        // validates Host/Origin and terminates TLS if set. The SDK only accepts
+        // the gateway's private Host value.
+        let upstream_host = gateway::upstream_host();
         let server = hyper_server_core::create_server(
             server_details,
             handler,
             HyperServerOptions {
-                host: host.clone(),
-                port,
+                host: "127.0.0.1".to_string(),
+                port: 0,
                 ping_interval: ping,
                 enable_json_response: Some(*HTTP_JSON),
+                allowed_hosts: Some(vec![upstream_host.clone()]),
+                dns_rebinding_protection: true,
                 ..Default::default()
             },
         );
         tracing::info!(
-            "http server configured; starting listener on {}:{} (json={}, ping_secs={})",
+            "http server configured; starting listener on {}:{} (json={}, ping_secs={}, tls={})",
             host,
             port,
             *HTTP_JSON,
-            *PING_SECS
+            *PING_SECS,
+            tls.is_some()
         );
-        if let Err(e) = server.start().await {
-            let msg = match e.rpc_error_message() {
-                Some(m) => m.to_string(),
-                None => e.to_string(),
-            };
-            tracing::error!("hyper server error: {}", msg);
+        let server_handle = server.server_handle();
+        let run_server = async {
+            if let Err(e) = server.start().await {
+                let msg = match e.rpc_error_message() {
+                    Some(m) => m.to_string(),
+                    None => e.to_string(),
+                };
+                tracing::error!("hyper server error: {}", msg);
+            }
+        };
+        // The gateway starts once the SDK server reports its bound address
+        let front = async {
+            let upstream = server_handle
+                .listening()
+                .await
+                .ok_or_else(|| anyhow::anyhow!("internal server failed to bind"))?;
+            gateway::serve(gateway::GatewaySettings {
+                host,
+                port,
+                upstream,
+                upstream_host,
+                tls,
+                policy,
+            })
+            .await
+        };
+        tokio::select! {
+            _ = run_server => {}
+            res = front => {
+                if let Err(e) = res {
+                    tracing::error!("http listener error: {:#}", e);
+                }
+            }
         }
     }
     tracing::info!("server stopped");
@@ -0,0 +1,339 @@
+//! Tool-filtering stdio proxy for MCP servers with denied tools.
+//!
+//! Codex cannot hide single tools of an attached MCP server, so a server with
+//! denied tools is launched as `switchboard-mcp mcp-filter --deny <tool> ...
+//! -- <command> [args...]`. The proxy relays newline-delimited JSON-RPC both
+//! ways, removes denied tools from `tools/list` results and answers
+//! `tools/call` for them with an error instead of forwarding the call.
+//!
+//! `--env-file <path>` reads the server's environment from a JSON object in a
+//! private temp file, so secrets stay off the command line (the subprocess
+//! runner launches servers this way).
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use codex_core::config_types::McpServerConfig;
+use serde_json::{Value as JsonValue, json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use super::McpToolRef;
+
+/// Subcommand that runs the proxy.
+pub const FILTER_SUBCOMMAND: &str = "mcp-filter";
+
+/// Parsed `mcp-filter` invocation.
+#[derive(Debug, PartialEq)]
+pub struct FilterCommand {
+    pub denied: Vec<String>,
+    /// JSON object of environment variables for the server.
+    pub env_file: Option<PathBuf>,
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+/// Parse `mcp-filter [--deny <tool>]... [--env-file <path>] -- <command>
+/// [args...]`; `None` for other subcommands.
+pub fn parse_filter_args(args: &[String]) -> anyhow::Result<Option<FilterCommand>> {
+    let Some((first, rest)) = args.split_first() else {
+        return Ok(None);
+    };
+    if first != FILTER_SUBCOMMAND {
+        return Ok(None);
+    }
+    let mut denied = Vec::new();
+    let mut env_file = None;
+    let mut it = rest.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--deny" => denied.push(it.next().context("--deny requires a tool name")?.clone()),
+            "--env-file" => {
+                env_file = Some(PathBuf::from(
+                    it.next().context("--env-file requires a path")?,
+                ))
+            }
+            "--" => break,
+            other => anyhow::bail!("unexpected argument '{other}'"),
+        }
+    }
+    let command = it
+        .next()
+        .context("usage: switchboard-mcp mcp-filter --deny <tool>... -- <command> [args...]")?
+        .clone();
+    Ok(Some(FilterCommand {
+        denied,
+        env_file,
+        command,
+        args: it.cloned().collect(),
+    }))
+}
+
+/// Tool names of server `key` that `denied` hides.
+fn denied_tool_names(key: &str, denied: &[McpToolRef]) -> Vec<String> {
+    let mut names: Vec<String> = denied
+        .iter()
+        .filter_map(|d| match d {
+            McpToolRef::Bare { tool } => Some(tool.clone()),
+            McpToolRef::Namespaced { server_key, tool } if server_key == key => Some(tool.clone()),
+            _ => None,
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Launch `cfg` through the filter proxy when any of `denied` concerns server
+/// `key`; otherwise return it unchanged.
+pub fn filtered_server(
+    key: &str,
+    cfg: McpServerConfig,
+    denied: &[McpToolRef],
+) -> anyhow::Result<McpServerConfig> {
+    let names = denied_tool_names(key, denied);
+    if names.is_empty() {
+        return Ok(cfg);
+    }
+    let exe = std::env::current_exe().context("locate the switchboard-mcp binary")?;
+    let mut args = vec![FILTER_SUBCOMMAND.to_string()];
+    for name in names {
+        args.push("--deny".to_string());
+        args.push(name);
+    }
+    args.push("--".to_string());
+    args.push(cfg.command.clone());
+    args.extend(cfg.args.iter().cloned());
+    Ok(McpServerConfig {
+        command: exe.display().to_string(),
+        args,
+        ..cfg
+    })
+}
+
+/// Launch `cfg` with its environment read from `env_file` instead of passed
+/// in the config. A server already behind the proxy gets the extra option.
+pub fn with_env_file(cfg: McpServerConfig, env_file: &Path) -> anyhow::Result<McpServerConfig> {
+    let option = ["--env-file".to_string(), env_file.display().to_string()];
+    let args = if cfg.args.first().map(String::as_str) == Some(FILTER_SUBCOMMAND) {
+        let mut args = cfg.args.clone();
+        args.splice(1..1, option);
+        args
+    } else {
+        let mut args = vec![FILTER_SUBCOMMAND.to_string()];
+        args.extend(option);
+        args.push("--".to_string());
+        args.push(cfg.command.clone());
+        args.extend(cfg.args.iter().cloned());
+        args
+    };
+    let exe = std::env::current_exe().context("locate the switchboard-mcp binary")?;
+    Ok(McpServerConfig {
+        command: exe.display().to_string(),
+        args,
+        env: None,
+        ..cfg
+    })
+}
+
+/// What to do with a message from the client.
+#[derive(Debug, PartialEq)]
+enum ClientMessage {
+    Forward,
+    /// Answer the client directly with this line.
+    Reply(String),
+}
+
+/// Inspect a client line: remember `tools/list` ids and refuse denied calls.
+fn client_message(
+    line: &str,
+    denied: &[String],
+    list_ids: &Mutex<HashSet<String>>,
+) -> ClientMessage {
+    let Ok(msg) = serde_json::from_str::<JsonValue>(line) else {
+        return ClientMessage::Forward;
+    };
+    let id = msg.get("id").cloned().unwrap_or(JsonValue::Null);
+    match msg.get("method").and_then(|m| m.as_str()) {
+        Some("tools/list") => {
+            if let Ok(mut ids) = list_ids.lock() {
+                ids.insert(id.to_string());
+            }
+            ClientMessage::Forward
+        }
+        Some("tools/call") => {
+            let name = msg
+                .pointer("/params/name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default();
+            if !denied.iter().any(|d| d == name) {
+                return ClientMessage::Forward;
+            }
+            tracing::warn!("mcp-filter: refused call to denied tool '{}'", name);
+            ClientMessage::Reply(
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32602, "message": format!("tool '{name}' is denied for this agent") },
+                })
+                .to_string(),
+            )
+        }
+        _ => ClientMessage::Forward,
+    }
+}
+
+/// Rewrite a server line: `tools/list` results lose their denied tools.
+fn server_message(line: &str, denied: &[String], list_ids: &Mutex<HashSet<String>>) -> String {
+    let Ok(mut msg) = serde_json::from_str::<JsonValue>(line) else {
+        return line.to_string();
+    };
+    let Some(id) = msg.get("id").map(|id| id.to_string()) else {
+        return line.to_string();
+    };
+    let is_list = list_ids
+        .lock()
+        .map(|mut ids| ids.remove(&id))
+        .unwrap_or(false);
+    let Some(tools) = msg
+        .pointer_mut("/result/tools")
+        .and_then(|t| t.as_array_mut())
+        .filter(|_| is_list)
+    else {
+        return line.to_string();
+    };
+    tools.retain(|t| {
+        let name = t.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+        !denied.iter().any(|d| d == name)
+    });
+    msg.to_string()
+}
+
+/// Run the proxy until the server exits; returns its exit code.
+pub async fn run_filter(cmd: FilterCommand) -> anyhow::Result<i32> {
+    let env: HashMap<String, String> = match &cmd.env_file {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read env file {}", path.display()))?;
+            serde_json::from_str(&text).context("env file must be a JSON object of strings")?
+        }
+        None => HashMap::new(),
+    };
+    let mut child = tokio::process::Command::new(&cmd.command)
+        .args(&cmd.args)
+        .envs(&env)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("failed to launch {}", cmd.command))?;
+    let mut server_in = child.stdin.take().context("capture server stdin")?;
+    let server_out = child.stdout.take().context("capture server stdout")?;
+    let denied = Arc::new(cmd.denied);
+    let list_ids = Arc::new(Mutex::new(HashSet::new()));
+    let stdout = Arc::new(tokio::sync::Mutex::new(tokio::io::stdout()));
+
+    let client_task = {
+        let (denied, list_ids, stdout) = (denied.clone(), list_ids.clone(), stdout.clone());
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(tokio::io::stdin()).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                match client_message(&line, &denied, &list_ids) {
+                    ClientMessage::Forward => {
+                        let sent = server_in.write_all(line.as_bytes()).await.is_ok()
+                            && server_in.write_all(b"\n").await.is_ok()
+                            && server_in.flush().await.is_ok();
+                        if !sent {
+                            break;
+                        }
+                    }
+                    ClientMessage::Reply(reply) => {
+                        let mut out = stdout.lock().await;
+                        out.write_all(format!("{reply}\n").as_bytes()).await.ok();
+                        out.flush().await.ok();
+                    }
+                }
+            }
+            // Dropping the pipe closes the server's stdin
+        })
+    };
+
+    let mut lines = BufReader::new(server_out).lines();
+    while let Some(line) = lines.next_line().await.context("read server output")? {
+        let line = server_message(&line, &denied, &list_ids);
+        let mut out = stdout.lock().await;
+        out.write_all(format!("{line}\n").as_bytes()).await?;
+        out.flush().await?;
+    }
+    client_task.abort();
+    let status = child.wait().await.context("wait for server")?;
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denied_tools_are_hidden_and_refused() {
+        let denied = vec!["delete_repo".to_string()];
+        let ids = Mutex::new(HashSet::new());
+        let list = r#"{"jsonrpc":"2.0","id":7,"method":"tools/list"}"#;
+        assert_eq!(client_message(list, &denied, &ids), ClientMessage::Forward);
+        let reply = r#"{"jsonrpc":"2.0","id":7,"result":{"tools":[{"name":"get_pr"},{"name":"delete_repo"}]}}"#;
+        let filtered: JsonValue =
+            serde_json::from_str(&server_message(reply, &denied, &ids)).unwrap();
+        assert_eq!(filtered["result"]["tools"], json!([{ "name": "get_pr" }]));
+        // Other responses pass through untouched
+        assert_eq!(server_message(reply, &denied, &ids), reply);
+
+        let call =
+            r#"{"jsonrpc":"2.0","id":"c1","method":"tools/call","params":{"name":"delete_repo"}}"#;
+        let ClientMessage::Reply(err) = client_message(call, &denied, &ids) else {
+            panic!("denied call was forwarded");
+        };
+        let err: JsonValue = serde_json::from_str(&err).unwrap();
+        assert_eq!(err["id"], "c1");
+        assert!(err["error"]["message"].as_str().unwrap().contains("denied"));
+        let call = r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"get_pr"}}"#;
+        assert_eq!(client_message(call, &denied, &ids), ClientMessage::Forward);
+    }
+
+    #[test]
+    fn wraps_only_servers_with_denied_tools() {
+        let args: Vec<String> = [
+            "mcp-filter",
+            "--deny",
+            "a",
+            "--deny",
+            "b",
+            "--",
+            "npx",
+            "-y",
+            "srv",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let cmd = parse_filter_args(&args).unwrap().unwrap();
+        assert_eq!(cmd.denied, ["a", "b"]);
+        assert!(cmd.env_file.is_none());
+        assert_eq!(cmd.command, "npx");
+        assert_eq!(cmd.args, ["-y", "srv"]);
+        assert!(parse_filter_args(&args[..5]).is_err());
+
+        let denied = vec![
+            McpToolRef::parse("github/delete_repo"),
+            McpToolRef::parse("drop_db"),
+        ];
+        assert_eq!(
+            denied_tool_names("github", &denied),
+            ["delete_repo", "drop_db"]
+        );
+        assert_eq!(denied_tool_names("memory", &denied), ["drop_db"]);
+        assert!(denied_tool_names("memory", &[McpToolRef::parse("github/x")]).is_empty());
+    }
+}
@@ -5,6 +5,7 @@
 
 pub mod discovery;
 pub mod enumerator;
+pub mod filter;
 pub mod types;
 
 pub use discovery::*;
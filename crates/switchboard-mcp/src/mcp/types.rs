@@ -29,11 +29,84 @@ pub struct DiscoveredServers {
     pub by_key: HashMap<String, NormalizedMcpServer>,
 }
 
-/// Reference to an MCP tool by bare name or namespaced `server::tool`.
+/// Reference to an MCP tool by bare name, namespaced `server::tool`, or a
+/// whole server (`server/*`).
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum McpToolRef {
-    Bare { tool: String },
-    Namespaced { server_key: String, tool: String },
+    Bare {
+        tool: String,
+    },
+    Namespaced {
+        server_key: String,
+        tool: String,
+    },
+    /// Every tool of a server.
+    Server {
+        server_key: String,
+    },
+}
+
+impl McpToolRef {
+    /// Parse a tool entry from agent frontmatter. Recognizes Claude's
+    /// `mcp__<server>__<tool>`, `server/tool` and `server::tool` as namespaced
+    /// references, and `#server`, `server/*` and `mcp__<server>` as whole
+    /// servers. Anything else (including Claude permission patterns such as
+    /// `Read(src/**)`) is a bare tool name.
+    pub fn parse(s: &str) -> Self {
+        let s = s.trim();
+        let split = match s.strip_prefix("mcp__") {
+            _ if s.contains('(') => None,
+            _ if s.starts_with('#') => Some((&s[1..], "*")),
+            Some(rest) => Some(rest.split_once("__").unwrap_or((rest, "*"))),
+            None => s.split_once("::").or_else(|| s.split_once('/')),
+        };
+        match split {
+            Some((server, tool)) if !server.trim().is_empty() && !tool.trim().is_empty() => {
+                let server_key = server.trim().to_string();
+                match tool.trim() {
+                    "*" => McpToolRef::Server { server_key },
+                    tool => McpToolRef::Namespaced {
+                        server_key,
+                        tool: tool.to_string(),
+                    },
+                }
+            }
+            _ => McpToolRef::Bare {
+                tool: s.to_string(),
+            },
+        }
+    }
+
+    /// Server named by a namespaced or whole-server reference.
+    pub fn server_key(&self) -> Option<&str> {
+        match self {
+            McpToolRef::Bare { .. } => None,
+            McpToolRef::Namespaced { server_key, .. } | McpToolRef::Server { server_key } => {
+                Some(server_key)
+            }
+        }
+    }
+
+    /// Whether this reference covers `tool` of `server` (a bare name matches
+    /// the tool on any server).
+    pub fn matches(&self, server: &str, tool: &str) -> bool {
+        match self {
+            McpToolRef::Bare { tool: t } => t == tool,
+            McpToolRef::Namespaced {
+                server_key,
+                tool: t,
+            } => server_key == server && t == tool,
+            McpToolRef::Server { server_key } => server_key == server,
+        }
+    }
+
+    /// Whether this reference covers everything `other` refers to.
+    pub fn covers(&self, other: &McpToolRef) -> bool {
+        match other {
+            McpToolRef::Namespaced { server_key, tool } => self.matches(server_key, tool),
+            _ => self == other,
+        }
+    }
 }
 
 /// Origin/provider where a server definition was found.
@@ -52,3 +125,62 @@ pub struct McpServerOrigin {
     pub path: Option<PathBuf>,
     pub note: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ns(server: &str, tool: &str) -> McpToolRef {
+        McpToolRef::Namespaced {
+            server_key: server.to_string(),
+            tool: tool.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_namespaced_tool_forms() {
+        assert_eq!(
+            McpToolRef::parse("mcp__github__create_issue"),
+            ns("github", "create_issue")
+        );
+        assert_eq!(
+            McpToolRef::parse("mcp__my-db__run__query"),
+            ns("my-db", "run__query")
+        );
+        assert_eq!(McpToolRef::parse("memory::memory"), ns("memory", "memory"));
+        assert_eq!(McpToolRef::parse(" github/get_me "), ns("github", "get_me"));
+        for server in ["#github", "github/*", "github::*", "mcp__github"] {
+            assert_eq!(
+                McpToolRef::parse(server),
+                McpToolRef::Server {
+                    server_key: "github".to_string()
+                },
+                "{server}"
+            );
+        }
+        for bare in ["Edit", "#", "/x", "a::", "Read(src/**)"] {
+            assert_eq!(
+                McpToolRef::parse(bare),
+                McpToolRef::Bare {
+                    tool: bare.to_string()
+                },
+                "{bare}"
+            );
+        }
+    }
+
+    #[test]
+    fn denials_match_calls_and_refs() {
+        let server = McpToolRef::parse("github/*");
+        let tool = McpToolRef::parse("github/create_issue");
+        let bare = McpToolRef::parse("create_issue");
+        for d in [&server, &tool, &bare] {
+            assert!(d.matches("github", "create_issue"));
+        }
+        assert!(!tool.matches("github", "get_me"));
+        assert!(bare.matches("gitlab", "create_issue"));
+        assert!(server.covers(&tool));
+        assert!(!tool.covers(&server));
+        assert!(bare.covers(&bare) && !bare.covers(&McpToolRef::parse("Edit")));
+    }
+}
@@ -14,11 +14,14 @@ pub fn safe_name(name: &str) -> String {
 }
 
 /// Supported agent provider variants.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum AgentVariant {
+    #[default]
     Codex,
     Anthropic,
     Vscode,
+    Cursor,
+    Command,
 }
 
 /// Tool name prefixes to apply for each variant when deriving `tool_name`.
@@ -26,6 +29,8 @@ pub struct EnvPrefixes<'a> {
     pub codex: &'a str,
     pub anthropic: &'a str,
     pub vscode: &'a str,
+    pub cursor: &'a str,
+    pub command: &'a str,
 }
 
 /// Get the configured tool prefix for a provider variant.
@@ -34,6 +39,8 @@ pub fn tool_prefix_for<'a>(variant: AgentVariant, env: &'a EnvPrefixes<'a>) -> &
         AgentVariant::Codex => env.codex,
         AgentVariant::Anthropic => env.anthropic,
         AgentVariant::Vscode => env.vscode,
+        AgentVariant::Cursor => env.cursor,
+        AgentVariant::Command => env.command,
     }
 }
 
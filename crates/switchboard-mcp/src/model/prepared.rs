@@ -1,21 +1,42 @@
 //! Prepared agent configuration ready for execution.
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 
+use codex_core::ModelProviderInfo;
 use codex_core::config_types::McpServerConfig;
 
 use super::naming::AgentVariant;
 use super::types::AgentRun;
+use crate::mcp::types::McpToolRef;
 
 /// A fully prepared agent definition bound to a concrete tool name.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct PreparedAgent {
     pub tool_name: String,
     pub name: String,
     pub description: String,
     pub tags: Option<Vec<String>>,
     pub provider: AgentVariant,
+    /// Definition file the agent was loaded from.
+    pub source_path: PathBuf,
     pub mcp_servers: HashMap<String, McpServerConfig>,
+    /// Provider definitions from `config.toml` and the agent, by provider id.
+    pub model_providers: HashMap<String, ModelProviderInfo>,
     pub instructions: Option<String>,
+    /// Fragment files pulled into `instructions` by include directives.
+    pub fragments: Vec<PathBuf>,
+    /// Custom variables when instruction templating is enabled; `None` keeps
+    /// the instructions verbatim.
+    pub template_vars: Option<HashMap<String, String>>,
+    /// Display color, exposed to clients in the tool's `_meta`.
+    pub color: Option<String>,
     pub run: Option<AgentRun>,
+    /// Runner backend requested by the agent definition.
+    pub runner: Option<String>,
+    /// MCP tools the agent must not call. Their servers run behind the
+    /// `mcp-filter` proxy; a run that still calls one is stopped.
+    pub denied_tools: Vec<McpToolRef>,
+    /// Prompt/command template the call's task is rendered into.
+    pub task_template: Option<String>,
 }
@@ -9,8 +9,19 @@ use crate::mcp::types::NormalizedMcpServer;
 #[derive(Debug, Clone)]
 pub struct AgentSource {
     pub variant: AgentVariant,
-    #[allow(dead_code)]
     pub path: PathBuf,
+    /// Entry within a file that defines several agents (`agents[2]`, `agents.triage`).
+    pub entry: Option<String>,
+}
+
+impl AgentSource {
+    /// Origin for diagnostics: the path, plus `#<entry>` for multi-agent files.
+    pub fn describe(&self) -> String {
+        match &self.entry {
+            Some(entry) => format!("{}#{}", self.path.display(), entry),
+            None => self.path.display().to_string(),
+        }
+    }
 }
 
 /// A resolved agent prior to converting MCP servers and instructions.
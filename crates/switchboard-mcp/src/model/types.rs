@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
@@ -25,9 +26,53 @@ pub struct AgentRun {
     pub include_apply_patch_tool: Option<bool>,
     pub include_view_image_tool: Option<bool>,
     pub tools_web_search_request: Option<bool>,
+    /// Switchboard-only: interrupt the run once its token usage exceeds this.
+    pub max_tokens_per_call: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AgentRun {
+    /// These settings with unset fields taken from `base`.
+    pub fn or_base(self, base: &AgentRun) -> AgentRun {
+        AgentRun {
+            model: self.model.or_else(|| base.model.clone()),
+            model_provider: self.model_provider.or_else(|| base.model_provider.clone()),
+            approval_policy: self.approval_policy.or(base.approval_policy),
+            disable_response_storage: self
+                .disable_response_storage
+                .or(base.disable_response_storage),
+            model_reasoning_effort: self.model_reasoning_effort.or(base.model_reasoning_effort),
+            model_reasoning_summary: self
+                .model_reasoning_summary
+                .or(base.model_reasoning_summary),
+            model_verbosity: self.model_verbosity.or(base.model_verbosity),
+            chatgpt_base_url: self
+                .chatgpt_base_url
+                .or_else(|| base.chatgpt_base_url.clone()),
+            sandbox_mode: self.sandbox_mode.or(base.sandbox_mode),
+            include_plan_tool: self.include_plan_tool.or(base.include_plan_tool),
+            include_apply_patch_tool: self
+                .include_apply_patch_tool
+                .or(base.include_apply_patch_tool),
+            include_view_image_tool: self
+                .include_view_image_tool
+                .or(base.include_view_image_tool),
+            tools_web_search_request: self
+                .tools_web_search_request
+                .or(base.tools_web_search_request),
+            max_tokens_per_call: self.max_tokens_per_call.or(base.max_tokens_per_call),
+        }
+    }
+
+    /// Keys a `[run]` table may set.
+    pub fn field_names() -> Vec<String> {
+        match serde_json::to_value(AgentRun::default()) {
+            Ok(serde_json::Value::Object(map)) => map.keys().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AgentConfig {
     pub name: String,
     pub description: String,
@@ -41,10 +86,27 @@ pub struct AgentConfig {
     pub instructions_file: Option<PathBuf>,
     /// Inline instructions for the agent when `instructions_file` is not used.
     pub instructions: Option<String>,
+    /// Render `{{...}}` variables in the instructions per call (opt-in).
+    pub templating: Option<bool>,
+    /// Custom template variables (`[vars]`).
+    pub vars: Option<HashMap<String, String>>,
     /// Run settings (mapped under `profiles.<safe>.*`).
     pub run: Option<AgentRun>,
     /// Optional MCP server definitions passed as top-level overrides (codex only).
     pub mcp_servers: Option<TomlValue>,
+    /// Optional `[model_providers.<id>]` definitions merged into Codex config (codex only).
+    pub model_providers: Option<TomlValue>,
+    /// Runner backend for this agent ("inproc", "subprocess", "sampling" or a command runner); server default when unset.
+    pub runner: Option<String>,
+    /// Claude semantics for an omitted `tools` list: every discovered MCP server is attached.
+    pub inherit_tools: Option<bool>,
+    /// Tools the agent may never use (`deny_tools`, Claude `disallowedTools`);
+    /// wins over `tools`, inherited tools and attached servers.
+    pub deny_tools: Option<Vec<McpToolRef>>,
+    /// Prompt/command body filled with each call's task (`$ARGUMENTS`, `$1`..).
+    pub task_template: Option<String>,
+    /// Display color (Claude `color` frontmatter).
+    pub color: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
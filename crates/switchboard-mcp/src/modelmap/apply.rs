@@ -54,17 +54,12 @@ mod tests {
         AgentConfig {
             name: "n".into(),
             description: "d".into(),
-            tags: None,
-            toggles: None,
-            mcp_tool_refs: None,
-            instructions_file: None,
-            instructions: None,
             run: Some(AgentRun {
                 model: model.map(|s| s.to_string()),
                 model_provider: provider.map(|s| s.to_string()),
                 ..Default::default()
             }),
-            mcp_servers: None,
+            ..Default::default()
         }
     }
 
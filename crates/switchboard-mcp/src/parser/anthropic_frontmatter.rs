@@ -1,4 +1,10 @@
-//! Parser for Anthropic-style `.agent.md` files with YAML frontmatter.
+//! Parser for Anthropic/Claude Code agent files (`*.agent.md` or plain
+//! `*.md` subagents) with YAML frontmatter.
+//!
+//! Claude semantics: in plain `*.md` subagents an omitted `tools` list grants
+//! every tool (all Codex-like built-ins and every discovered MCP server);
+//! `.agent.md` files keep the Codex defaults. `model: inherit` leaves the
+//! model to the runner, and `color` is kept for display.
 
 use anyhow::Context as _;
 use serde::Deserialize;
@@ -6,25 +12,42 @@ use std::path::Path;
 
 use crate::mcp::types::McpToolRef;
 use crate::model::{AgentConfig, AgentRun};
+use codex_core::protocol::AskForApproval;
+use codex_protocol::config_types::SandboxMode;
 
-use super::AgentParser;
+use super::{AgentParser, frontmatter_run};
 
-/// Parser for `.agent.md` files.
+/// Parser for `.agent.md` and plain `.md` agent files.
 pub struct AnthropicFrontmatterParser;
 
 #[derive(Debug, Deserialize)]
 struct Frontmatter {
-    name: String,
+    #[serde(default)]
+    name: Option<String>,
     #[serde(default)]
     description: String,
     #[serde(default)]
     tools: ToolsField,
+    #[serde(default, alias = "disallowedTools")]
+    disallowed_tools: ToolsField,
     #[serde(default)]
     tags: ToolsField,
     #[serde(default)]
     model: Option<String>,
     #[serde(default, alias = "provider", alias = "modelProvider")]
     model_provider: Option<String>,
+    #[serde(default)]
+    templating: Option<bool>,
+    #[serde(default)]
+    vars: Option<std::collections::HashMap<String, String>>,
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    runner: Option<String>,
+    #[serde(default, alias = "permissionMode")]
+    permission_mode: Option<String>,
+    #[serde(default)]
+    run: Option<AgentRun>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -83,15 +106,45 @@ impl ToolsField {
     }
 }
 
+impl AnthropicFrontmatterParser {
+    /// Whether `content` starts with a `---` frontmatter block; plain Markdown
+    /// files without one are not agents.
+    pub fn has_frontmatter(content: &str) -> bool {
+        content
+            .lines()
+            .find(|l| !l.trim().is_empty())
+            .is_some_and(|l| l.trim() == "---")
+    }
+}
+
+/// Map a Claude `permissionMode` onto approval policy and sandbox, leaving
+/// fields already set by a `run:` block alone. `default` keeps the runner's
+/// defaults. Runs are unattended, so no mode asks for approval: `acceptEdits`
+/// writes within the workspace without prompting. Returns false for an
+/// unknown mode.
+fn apply_permission_mode(run: &mut AgentRun, mode: &str) -> bool {
+    let (approval, sandbox) = match mode.trim() {
+        "default" => return true,
+        "acceptEdits" => (AskForApproval::Never, SandboxMode::WorkspaceWrite),
+        "plan" => (AskForApproval::Never, SandboxMode::ReadOnly),
+        "bypassPermissions" => (AskForApproval::Never, SandboxMode::DangerFullAccess),
+        _ => return false,
+    };
+    run.approval_policy.get_or_insert(approval);
+    run.sandbox_mode.get_or_insert(sandbox);
+    true
+}
+
 impl AgentParser for AnthropicFrontmatterParser {
     fn supports(path: &Path) -> bool {
         let Some(fname) = path.file_name().and_then(|s| s.to_str()) else {
             return false;
         };
-        fname.ends_with(".agent.md")
+        // `.prompt.md` files are sibling prompts of TOML agents, not agents.
+        fname.ends_with(".md") && !fname.ends_with(".prompt.md")
     }
 
-    fn parse(content: &str, path: &Path) -> anyhow::Result<AgentConfig> {
+    fn parse(content: &str, path: &Path) -> anyhow::Result<Vec<AgentConfig>> {
         // Detect and split YAML frontmatter delimited by --- ... --- at file start
         let mut first_non_empty = None;
         for (idx, l) in content.lines().enumerate() {
@@ -131,11 +184,21 @@ impl AgentParser for AnthropicFrontmatterParser {
         let fm: Frontmatter =
             serde_yaml::from_str(&yaml_buf).context("invalid YAML frontmatter")?;
         let tags = fm.tags.into_vec_commas_only();
-        let refs: Option<Vec<McpToolRef>> = fm.tools.into_vec().map(|v| {
-            v.into_iter()
-                .map(|t| McpToolRef::Bare { tool: t })
-                .collect()
-        });
+        // Only Claude Code subagents grant every tool when `tools` is omitted;
+        // `.agent.md` files keep the default they had before.
+        let is_legacy = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .is_some_and(|f| f.ends_with(".agent.md"));
+        let inherit_tools = matches!(fm.tools, ToolsField::Empty) && !is_legacy;
+        let refs: Option<Vec<McpToolRef>> = fm
+            .tools
+            .into_vec()
+            .map(|v| v.iter().map(|t| McpToolRef::parse(t)).collect());
+        let deny_tools: Option<Vec<McpToolRef>> = fm
+            .disallowed_tools
+            .into_vec()
+            .map(|v| v.iter().map(|t| McpToolRef::parse(t)).collect());
 
         // The rest of the file after fm_end_idx is the instructions body
         let body: String = content
@@ -144,28 +207,56 @@ impl AgentParser for AnthropicFrontmatterParser {
             .collect::<Vec<&str>>()
             .join("\n");
 
-        // Optional run mapping (model only; provider left unset for Codex defaults)
-        let run = if fm.model.is_some() || fm.model_provider.is_some() {
-            Some(AgentRun {
-                model: fm.model,
-                model_provider: fm.model_provider,
-                ..Default::default()
-            })
-        } else {
-            None
-        };
+        // Optional run settings: `run:` block plus the model/provider shorthands.
+        // `model: inherit` means the caller's model, i.e. the runner default.
+        let model = fm
+            .model
+            .filter(|m| !m.trim().eq_ignore_ascii_case("inherit"));
+        let mut run = frontmatter_run(fm.run, model, fm.model_provider);
+        if let Some(mode) = fm.permission_mode.as_deref() {
+            let r = run.get_or_insert_with(AgentRun::default);
+            if !apply_permission_mode(r, mode) {
+                tracing::warn!(
+                    "unknown permissionMode '{}' in {} (ignored)",
+                    mode,
+                    path.display()
+                );
+            }
+        }
+        if inherit_tools {
+            let r = run.get_or_insert_with(AgentRun::default);
+            r.include_plan_tool.get_or_insert(true);
+            r.include_apply_patch_tool.get_or_insert(true);
+            r.include_view_image_tool.get_or_insert(true);
+            r.tools_web_search_request.get_or_insert(true);
+        }
+        let name = fm.name.unwrap_or_else(|| {
+            let fname = path.file_name().and_then(|s| s.to_str()).unwrap_or("agent");
+            fname
+                .trim_end_matches(".md")
+                .trim_end_matches(".agent")
+                .to_string()
+        });
 
-        Ok(AgentConfig {
-            name: fm.name,
+        Ok(vec![AgentConfig {
+            name,
             description: fm.description,
             tags,
             toggles: None,
             mcp_tool_refs: refs,
             instructions_file: None,
             instructions: Some(body.trim().to_string()),
+            templating: fm.templating,
+            vars: fm.vars,
             run,
             mcp_servers: None,
-        })
+            model_providers: None,
+            runner: fm.runner,
+            inherit_tools: Some(inherit_tools),
+            deny_tools,
+            task_template: None,
+            color: fm.color,
+        }])
     }
 }
 
@@ -183,7 +274,9 @@ tags: deep research, demo, another tag
 body
 "#;
         let path = std::path::Path::new("/tmp/example.agent.md");
-        let cfg = AnthropicFrontmatterParser::parse(content, path).expect("parse ok");
+        let cfg = AnthropicFrontmatterParser::parse(content, path)
+            .expect("parse ok")
+            .remove(0);
         let tags = cfg.tags.expect("tags");
         assert_eq!(tags, vec!["deep research", "demo", "another tag"]);
         assert_eq!(cfg.name, "Example");
@@ -200,7 +293,9 @@ tools: plan apply_patch
 body
 "#;
         let path = std::path::Path::new("/tmp/tools_single.agent.md");
-        let cfg = AnthropicFrontmatterParser::parse(content, path).expect("parse ok");
+        let cfg = AnthropicFrontmatterParser::parse(content, path)
+            .expect("parse ok")
+            .remove(0);
         let refs = cfg.mcp_tool_refs.expect("refs");
         let tools: Vec<String> = refs
             .into_iter()
@@ -220,7 +315,9 @@ tools:
 body
 "#;
         let path2 = std::path::Path::new("/tmp/tools_list.agent.md");
-        let cfg2 = AnthropicFrontmatterParser::parse(content2, path2).expect("parse ok");
+        let cfg2 = AnthropicFrontmatterParser::parse(content2, path2)
+            .expect("parse ok")
+            .remove(0);
         let refs2 = cfg2.mcp_tool_refs.expect("refs");
         let tools2: Vec<String> = refs2
             .into_iter()
@@ -242,9 +339,111 @@ model: sonnet
 body
 "#;
         let path = std::path::Path::new("/tmp/with_model.agent.md");
-        let cfg = AnthropicFrontmatterParser::parse(content, path).expect("parse ok");
+        let cfg = AnthropicFrontmatterParser::parse(content, path)
+            .expect("parse ok")
+            .remove(0);
         assert_eq!(cfg.name, "WithModel");
         let run = cfg.run.expect("run present");
         assert_eq!(run.model.as_deref(), Some("sonnet"));
     }
+
+    #[test]
+    fn claude_subagent_semantics() {
+        let path = std::path::Path::new("/tmp/.claude/agents/code-reviewer.md");
+        assert!(AnthropicFrontmatterParser::supports(path));
+        assert!(!AnthropicFrontmatterParser::supports(std::path::Path::new(
+            "/tmp/dev.prompt.md"
+        )));
+        assert!(!AnthropicFrontmatterParser::has_frontmatter("# Notes\n"));
+
+        let content = r#"---
+description: Reviews code
+model: inherit
+color: purple
+---
+body
+"#;
+        let cfg = AnthropicFrontmatterParser::parse(content, path)
+            .expect("parse ok")
+            .remove(0);
+        assert_eq!(cfg.name, "code-reviewer");
+        assert_eq!(cfg.color.as_deref(), Some("purple"));
+        assert_eq!(cfg.inherit_tools, Some(true));
+        let run = cfg.run.expect("run present");
+        assert_eq!(run.model, None);
+        assert_eq!(run.include_apply_patch_tool, Some(true));
+        assert_eq!(run.tools_web_search_request, Some(true));
+
+        let legacy = std::path::Path::new("/tmp/.claude/agents/code-reviewer.agent.md");
+        let cfg = AnthropicFrontmatterParser::parse(content, legacy)
+            .expect("parse ok")
+            .remove(0);
+        assert_eq!(cfg.inherit_tools, Some(false));
+        assert!(cfg.run.is_none());
+
+        let content = "---\nname: NoTools\ntools: []\n---\nbody\n";
+        let cfg = AnthropicFrontmatterParser::parse(content, path)
+            .expect("parse ok")
+            .remove(0);
+        assert_eq!(cfg.inherit_tools, Some(false));
+        assert!(cfg.mcp_tool_refs.is_none());
+        assert!(cfg.run.is_none());
+    }
+
+    #[test]
+    fn permission_mode_and_run_block() {
+        let path = std::path::Path::new("/tmp/planner.md");
+        let content = r#"---
+name: planner
+tools: Read
+permissionMode: plan
+run:
+  model_reasoning_effort: high
+  approval_policy: on-request
+---
+body
+"#;
+        let cfg = AnthropicFrontmatterParser::parse(content, path)
+            .expect("parse ok")
+            .remove(0);
+        let run = cfg.run.expect("run present");
+        assert!(matches!(run.sandbox_mode, Some(SandboxMode::ReadOnly)));
+        assert!(matches!(
+            run.approval_policy,
+            Some(AskForApproval::OnRequest)
+        ));
+        assert!(run.model_reasoning_effort.is_some());
+
+        let content = "---\nname: x\ntools: []\npermissionMode: bypassPermissions\n---\nbody\n";
+        let run = AnthropicFrontmatterParser::parse(content, path)
+            .unwrap()
+            .remove(0)
+            .run
+            .expect("run present");
+        assert!(matches!(
+            run.sandbox_mode,
+            Some(SandboxMode::DangerFullAccess)
+        ));
+        assert!(matches!(run.approval_policy, Some(AskForApproval::Never)));
+
+        // Nobody answers approval prompts, so edits must not ask for one
+        let content = "---\nname: x\ntools: [Edit]\npermissionMode: acceptEdits\n---\nbody\n";
+        let run = AnthropicFrontmatterParser::parse(content, path)
+            .unwrap()
+            .remove(0)
+            .run
+            .expect("run present");
+        assert!(matches!(
+            run.sandbox_mode,
+            Some(SandboxMode::WorkspaceWrite)
+        ));
+        assert!(matches!(run.approval_policy, Some(AskForApproval::Never)));
+
+        let content = "---\nname: x\ntools: []\npermissionMode: default\n---\nbody\n";
+        let run = AnthropicFrontmatterParser::parse(content, path)
+            .unwrap()
+            .remove(0)
+            .run;
+        assert!(run.is_some_and(|r| r.sandbox_mode.is_none() && r.approval_policy.is_none()));
+    }
 }
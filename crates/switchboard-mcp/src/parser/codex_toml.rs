@@ -1,28 +1,155 @@
 //! Parser for Codex `.toml` agent definitions.
+//!
+//! A file defines one agent at its root, or several under `[[agents]]` /
+//! `[agents.<id>]` that each inherit the file's `[defaults]` table. YAML and
+//! JSON agent files (see [`super::structured`]) share this schema and parsing;
+//! unknown keys are an error there and a warning in TOML files.
 
 use std::path::{Path, PathBuf};
 
 use anyhow::Context as _;
 use toml::Value as TomlValue;
 
-use crate::model::{AgentConfig, AgentRun, AgentTogglePolicy};
+use crate::mcp::types::McpToolRef;
+use crate::model::{AgentConfig, AgentRun, AgentTogglePolicy, safe_name};
 
 use super::AgentParser;
+use super::structured::{self, StructuredAgentParser};
 
 /// Parser for codex agent TOML files.
 pub struct CodexTomlParser;
 
 impl AgentParser for CodexTomlParser {
     fn supports(path: &Path) -> bool {
-        path.extension()
-            .and_then(|s| s.to_str())
-            .map(|ext| ext.eq_ignore_ascii_case("toml"))
+        // Mapping files live next to agents in `.agents/`
+        let is_map = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n == "tool-map.toml" || n == "model-map.toml");
+        !is_map
+            && path
+                .extension()
+                .and_then(|s| s.to_str())
+                .map(|ext| ext.eq_ignore_ascii_case("toml"))
+                .unwrap_or(false)
+    }
+
+    fn parse(content: &str, path: &Path) -> anyhow::Result<Vec<AgentConfig>> {
+        Self::parse_in(content, path, &[])
+    }
+}
+
+impl CodexTomlParser {
+    /// Parse an agent file, resolving `extends` by path (relative to the
+    /// file) or by agent name across the file's directory and `dirs`.
+    pub fn parse_in(
+        content: &str,
+        path: &Path,
+        dirs: &[PathBuf],
+    ) -> anyhow::Result<Vec<AgentConfig>> {
+        Ok(Self::parse_entries(content, path, dirs)?
+            .into_iter()
+            .map(|(_, cfg)| cfg)
+            .collect())
+    }
+
+    /// Like [`Self::parse_in`], with each agent's entry label (`agents[2]`,
+    /// `agents.triage`) for multi-agent files. An invalid entry is skipped
+    /// with a warning so the rest of the file still loads.
+    pub fn parse_entries(
+        content: &str,
+        path: &Path,
+        dirs: &[PathBuf],
+    ) -> anyhow::Result<Vec<(Option<String>, AgentConfig)>> {
+        let mut tbl = read_table(content, path)?;
+        let start = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let Some(agents) = tbl.remove("agents") else {
+            resolve_extends(&mut tbl, path, dirs, &mut vec![start], true)?;
+            return Ok(vec![(None, Self::from_table(tbl, path, true)?)]);
+        };
+
+        let defaults = match tbl.remove("defaults") {
+            Some(TomlValue::Table(t)) => t,
+            Some(other) => anyhow::bail!(
+                "[defaults] must be a table in {} (found: {})",
+                path.display(),
+                other.type_str()
+            ),
+            None => toml::Table::new(),
+        };
+        if !tbl.is_empty() {
+            let keys = tbl.keys().cloned().collect::<Vec<_>>().join(", ");
+            if StructuredAgentParser::supports(path) {
+                anyhow::bail!(
+                    "unknown keys next to 'agents' in {} (move them to 'defaults'): {}",
+                    path.display(),
+                    keys
+                );
+            }
+            tracing::warn!(
+                "ignoring top-level keys next to [agents] in {} (move them to [defaults]): {}",
+                path.display(),
+                keys
+            );
+        }
+        // (label, entry, default name)
+        let entries: Vec<(String, TomlValue, Option<String>)> = match agents {
+            TomlValue::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| (format!("agents[{i}]"), v, None))
+                .collect(),
+            TomlValue::Table(t) => t
+                .into_iter()
+                .map(|(id, v)| (format!("agents.{id}"), v, Some(id)))
+                .collect(),
+            other => anyhow::bail!(
+                "'agents' must be an array or table in {} (found: {})",
+                path.display(),
+                other.type_str()
+            ),
+        };
+
+        let mut out = Vec::with_capacity(entries.len());
+        for (label, entry, id) in entries {
+            let TomlValue::Table(entry) = entry else {
+                tracing::warn!("skipping {label} in {}: not a table", path.display());
+                continue;
+            };
+            let mut agent = defaults.clone();
+            merge_tables(&mut agent, entry);
+            if agent.get("abstract").and_then(|v| v.as_bool()) == Some(true) {
+                continue;
+            }
+            if let Some(id) = id {
+                agent.entry("name").or_insert(TomlValue::String(id));
+            }
+            match resolve_extends(&mut agent, path, dirs, &mut vec![start.clone()], false)
+                .and_then(|()| Self::from_table(agent, path, false))
+            {
+                Ok(cfg) => out.push((Some(label), cfg)),
+                Err(e) => tracing::warn!("skipping {label} in {}: {:#}", path.display(), e),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Files with `abstract = true` only serve as `extends` bases.
+    pub fn is_abstract(content: &str, path: &Path) -> bool {
+        read_table(content, path)
+            .ok()
+            .and_then(|t| t.get("abstract").and_then(|v| v.as_bool()))
             .unwrap_or(false)
     }
 
-    fn parse(content: &str, path: &Path) -> anyhow::Result<AgentConfig> {
-        // Strict TOML: entire file must be valid TOML with a table at root
-        let mut tbl: toml::Table = content.parse().context("invalid TOML")?;
+    /// Build one agent from its merged table. `sibling_prompt` enables the
+    /// `<file>.prompt.md` fallback (single-agent files only).
+    fn from_table(
+        mut tbl: toml::Table,
+        path: &Path,
+        sibling_prompt: bool,
+    ) -> anyhow::Result<AgentConfig> {
+        tbl.remove("abstract");
 
         // Required name
         let name = tbl
@@ -82,7 +209,7 @@ impl AgentParser for CodexTomlParser {
             .remove("instructions")
             .and_then(|v| v.as_str().map(|s| s.to_string()));
 
-        if instructions_file.is_none() && instructions.is_none() {
+        if sibling_prompt && instructions_file.is_none() && instructions.is_none() {
             let prompt_path = path.with_extension("prompt.md");
             if prompt_path.exists() {
                 match std::fs::read_to_string(&prompt_path) {
@@ -100,19 +227,55 @@ impl AgentParser for CodexTomlParser {
 
         // Run settings under [run]
         let run: Option<AgentRun> = match tbl.remove("run") {
-            Some(TomlValue::Table(t)) => match t.try_into() {
-                Ok(r) => Some(r),
-                Err(e) => {
-                    tracing::warn!("invalid [run] in {}: {}", path.display(), e);
-                    None
+            Some(TomlValue::Table(t)) => {
+                unknown_keys(t.keys(), &AgentRun::field_names(), "[run]", path)?;
+                match t.try_into() {
+                    Ok(r) => Some(r),
+                    Err(e) => {
+                        invalid_value("[run]", &e.to_string(), path)?;
+                        None
+                    }
                 }
-            },
+            }
             Some(other) => {
-                tracing::warn!(
-                    "ignoring non-table [run] in {} (found: {})",
-                    path.display(),
-                    other.type_str()
-                );
+                invalid_value(
+                    "[run]",
+                    &format!("not a table ({})", other.type_str()),
+                    path,
+                )?;
+                None
+            }
+            None => None,
+        };
+
+        // Opt-in instruction templating and its custom [vars]
+        let templating = match tbl.remove("templating") {
+            Some(TomlValue::Boolean(b)) => Some(b),
+            Some(other) => {
+                invalid_value(
+                    "'templating'",
+                    &format!("not a boolean ({})", other.type_str()),
+                    path,
+                )?;
+                None
+            }
+            None => None,
+        };
+        let vars = match tbl.remove("vars") {
+            Some(TomlValue::Table(t)) => Some(
+                t.into_iter()
+                    .map(|(k, v)| match v {
+                        TomlValue::String(s) => (k, s),
+                        other => (k, other.to_string()),
+                    })
+                    .collect(),
+            ),
+            Some(other) => {
+                invalid_value(
+                    "[vars]",
+                    &format!("not a table ({})", other.type_str()),
+                    path,
+                )?;
                 None
             }
             None => None,
@@ -120,40 +283,32 @@ impl AgentParser for CodexTomlParser {
 
         // Optional mcp_servers table (codex only)
         let mcp_servers = tbl.remove("mcp_servers");
+        // Optional model_providers table (codex only)
+        let model_providers = tbl.remove("model_providers");
 
-        // Optional tools (array or string) → toggles policy
-        let toggles: Option<AgentTogglePolicy> = match tbl.remove("tools") {
-            Some(TomlValue::Array(items)) => Some(map_tools_to_toggles(
-                items
-                    .into_iter()
-                    .filter_map(|it| it.as_str().map(|s| s.to_string()))
-                    .collect(),
-            )),
-            Some(TomlValue::String(s)) => Some(map_tools_to_toggles(
-                s.split(|c: char| c == ',' || c.is_whitespace())
-                    .filter(|t| !t.trim().is_empty())
-                    .map(|t| t.trim().to_string())
-                    .collect(),
-            )),
+        // Optional runner backend selection
+        let runner = match tbl.remove("runner") {
+            Some(TomlValue::String(s)) => Some(s),
             Some(other) => {
-                tracing::warn!(
-                    "unexpected 'tools' type in {}: {}",
-                    path.display(),
-                    other.type_str()
-                );
+                invalid_value(
+                    "'runner'",
+                    &format!("not a string ({})", other.type_str()),
+                    path,
+                )?;
                 None
             }
             None => None,
         };
 
-        // Ignore stray keys but log
-        if !tbl.is_empty() {
-            tracing::debug!(
-                "unrecognized keys in {}: {}",
-                path.display(),
-                tbl.keys().cloned().collect::<Vec<_>>().join(", ")
-            );
-        }
+        // Optional tools (array or string) → toggles policy
+        let toggles: Option<AgentTogglePolicy> =
+            tool_list(tbl.remove("tools"), "tools", path).map(map_tools_to_toggles);
+        // Optional denylist; builtins and MCP refs alike (`apply_patch`, `github/*`)
+        let deny_tools: Option<Vec<McpToolRef>> =
+            tool_list(tbl.remove("deny_tools"), "deny_tools", path)
+                .map(|v| v.iter().map(|t| McpToolRef::parse(t)).collect());
+
+        unknown_keys(tbl.keys(), &[], "agent", path)?;
 
         Ok(AgentConfig {
             name,
@@ -163,12 +318,276 @@ impl AgentParser for CodexTomlParser {
             mcp_tool_refs: None,
             instructions_file,
             instructions,
+            templating,
+            vars,
             run,
             mcp_servers,
+            model_providers,
+            runner,
+            inherit_tools: None,
+            deny_tools,
+            task_template: None,
+            color: None,
         })
     }
 }
 
+/// Merge the `extends` chain of `tbl` into it: tables merge recursively,
+/// child values win and arrays are replaced. `name` is never inherited.
+/// With `instructions_mode = "append"` the child's instructions follow the
+/// base's instead of replacing them. `chain` holds visited files for cycle
+/// detection; `sibling_prompt` is false for entries of multi-agent files.
+fn resolve_extends(
+    tbl: &mut toml::Table,
+    path: &Path,
+    dirs: &[PathBuf],
+    chain: &mut Vec<PathBuf>,
+    sibling_prompt: bool,
+) -> anyhow::Result<()> {
+    let append = match tbl.remove("instructions_mode") {
+        None => false,
+        Some(TomlValue::String(m)) if m.eq_ignore_ascii_case("replace") => false,
+        Some(TomlValue::String(m)) if m.eq_ignore_ascii_case("append") => true,
+        Some(other) => anyhow::bail!(
+            "invalid instructions_mode in {} (expected \"replace\" or \"append\", found: {})",
+            path.display(),
+            other
+        ),
+    };
+    let Some(ext) = tbl.remove("extends") else {
+        return Ok(());
+    };
+    let ext = ext
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("'extends' must be a string in {}", path.display()))?;
+    let base_path = locate_base(ext, path, dirs)?;
+    let key = base_path
+        .canonicalize()
+        .unwrap_or_else(|_| base_path.clone());
+    if chain.contains(&key) {
+        let cycle: Vec<String> = chain
+            .iter()
+            .chain(std::iter::once(&key))
+            .map(|p| p.display().to_string())
+            .collect();
+        anyhow::bail!("extends cycle: {}", cycle.join(" -> "));
+    }
+    chain.push(key);
+    let content = std::fs::read_to_string(&base_path)
+        .with_context(|| format!("read base {}", base_path.display()))?;
+    let mut base = read_table(&content, &base_path)
+        .with_context(|| format!("invalid base {}", base_path.display()))?;
+    resolve_extends(&mut base, &base_path, dirs, chain, true)?;
+    chain.pop();
+
+    base.remove("name");
+    base.remove("abstract");
+    load_sibling_prompt(&mut base, &base_path);
+    if sibling_prompt {
+        load_sibling_prompt(tbl, path);
+    }
+    if has_instructions(tbl) {
+        if append && let Some(base_text) = instructions_text(&base) {
+            let child_text = instructions_text(tbl).unwrap_or_default();
+            tbl.remove("instructions_file");
+            tbl.insert(
+                "instructions".to_string(),
+                TomlValue::String(format!("{}\n\n{}", base_text.trim_end(), child_text)),
+            );
+        }
+        base.remove("instructions");
+        base.remove("instructions_file");
+    }
+    merge_tables(&mut base, std::mem::take(tbl));
+    *tbl = base;
+    Ok(())
+}
+
+/// Agent document as a table: strict TOML, or YAML/JSON for structured files.
+fn read_table(content: &str, path: &Path) -> anyhow::Result<toml::Table> {
+    if StructuredAgentParser::supports(path) {
+        return structured::to_table(content, path);
+    }
+    // Strict TOML: entire file must be valid TOML with a table at root
+    content.parse().context("invalid TOML")
+}
+
+/// Report keys outside `known`: an error in YAML/JSON files (whose schema
+/// allows no others), a warning in TOML files, which then ignore them.
+fn unknown_keys<'a>(
+    keys: impl Iterator<Item = &'a String>,
+    known: &[String],
+    what: &str,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let unknown: Vec<&str> = keys
+        .filter(|k| !known.contains(k))
+        .map(String::as_str)
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    if StructuredAgentParser::supports(path) {
+        anyhow::bail!(
+            "unknown {what} keys in {}: {}",
+            path.display(),
+            unknown.join(", ")
+        );
+    }
+    tracing::warn!(
+        "ignoring unknown {} keys in {}: {}",
+        what,
+        path.display(),
+        unknown.join(", ")
+    );
+    Ok(())
+}
+
+/// A setting with the wrong type: an error in structured (YAML/JSON) files,
+/// which are validated against the schema, and a warning in TOML files,
+/// where the setting is ignored.
+fn invalid_value(what: &str, detail: &str, path: &Path) -> anyhow::Result<()> {
+    if StructuredAgentParser::supports(path) {
+        anyhow::bail!("invalid {what} in {}: {detail}", path.display());
+    }
+    tracing::warn!(
+        "ignoring invalid {} in {}: {}",
+        what,
+        path.display(),
+        detail
+    );
+    Ok(())
+}
+
+/// Find the base for `extends`: a path (an agent file name or anything with a
+/// separator) relative to the child, else an agent name or file stem in the
+/// child's directory, then `dirs`.
+fn locate_base(ext: &str, path: &Path, dirs: &[PathBuf]) -> anyhow::Result<PathBuf> {
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let is_file = ext.ends_with(".toml") || StructuredAgentParser::supports(Path::new(ext));
+    if is_file || ext.contains('/') || ext.contains('\\') {
+        let p = crate::config::expand_home(ext);
+        let p = if p.is_absolute() { p } else { parent.join(p) };
+        if p.is_file() {
+            return Ok(p);
+        }
+        anyhow::bail!(
+            "extends: base '{}' of {} not found (looked for {})",
+            ext,
+            path.display(),
+            p.display()
+        );
+    }
+    let wanted = safe_name(ext);
+    let search = std::iter::once(parent.to_path_buf()).chain(dirs.iter().cloned());
+    for dir in search {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut files: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.is_file()
+                    && p != path
+                    && (CodexTomlParser::supports(p) || StructuredAgentParser::supports(p))
+            })
+            .collect();
+        files.sort();
+        for f in files {
+            let stem_matches = f
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .map(|s| s.strip_suffix(".agent").unwrap_or(s))
+                .is_some_and(|s| safe_name(s) == wanted);
+            let name_matches = || {
+                std::fs::read_to_string(&f)
+                    .ok()
+                    .and_then(|c| read_table(&c, &f).ok())
+                    .and_then(|t| t.get("name").and_then(|n| n.as_str()).map(safe_name))
+                    .is_some_and(|n| n == wanted)
+            };
+            if stem_matches || name_matches() {
+                return Ok(f);
+            }
+        }
+    }
+    anyhow::bail!(
+        "extends: no agent named '{}' found for {}",
+        ext,
+        path.display()
+    )
+}
+
+fn has_instructions(tbl: &toml::Table) -> bool {
+    tbl.contains_key("instructions") || tbl.contains_key("instructions_file")
+}
+
+/// Use the sibling `<file>.prompt.md` as inline instructions when none are set.
+fn load_sibling_prompt(tbl: &mut toml::Table, path: &Path) {
+    if has_instructions(tbl) {
+        return;
+    }
+    if let Ok(s) = std::fs::read_to_string(path.with_extension("prompt.md")) {
+        tbl.insert("instructions".to_string(), TomlValue::String(s));
+    }
+}
+
+/// Instructions text of a table: `instructions_file` contents, else inline.
+fn instructions_text(tbl: &toml::Table) -> Option<String> {
+    tbl.get("instructions_file")
+        .and_then(|v| v.as_str())
+        .and_then(|f| std::fs::read_to_string(f).ok())
+        .filter(|s| !s.trim().is_empty())
+        .or_else(|| {
+            tbl.get("instructions")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+        })
+}
+
+fn merge_tables(base: &mut toml::Table, over: toml::Table) {
+    for (k, v) in over {
+        match v {
+            TomlValue::Table(o) if matches!(base.get(&k), Some(TomlValue::Table(_))) => {
+                if let Some(TomlValue::Table(b)) = base.get_mut(&k) {
+                    merge_tables(b, o);
+                }
+            }
+            v => {
+                base.insert(k, v);
+            }
+        }
+    }
+}
+
+/// A tool list given as an array or a comma/space separated string.
+fn tool_list(value: Option<TomlValue>, key: &str, path: &Path) -> Option<Vec<String>> {
+    match value? {
+        TomlValue::Array(items) => Some(
+            items
+                .into_iter()
+                .filter_map(|it| it.as_str().map(|s| s.to_string()))
+                .collect(),
+        ),
+        TomlValue::String(s) => Some(
+            s.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|t| !t.trim().is_empty())
+                .map(|t| t.trim().to_string())
+                .collect(),
+        ),
+        other => {
+            tracing::warn!(
+                "unexpected '{}' type in {}: {}",
+                key,
+                path.display(),
+                other.type_str()
+            );
+            None
+        }
+    }
+}
+
 fn map_tools_to_toggles(list: Vec<String>) -> AgentTogglePolicy {
     let has = |needle: &str| list.iter().any(|s| s.eq_ignore_ascii_case(needle));
     AgentTogglePolicy {
@@ -178,3 +597,177 @@ fn map_tools_to_toggles(list: Vec<String>) -> AgentTogglePolicy {
         tools_web_search_request: Some(has("web_search") || has("web-search")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let p = dir.join(name);
+        std::fs::write(&p, content).unwrap();
+        p
+    }
+
+    #[test]
+    fn extends_deep_merges_with_child_winning() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(
+            tmp.path(),
+            "base.toml",
+            r#"
+abstract = true
+name = "base"
+tags = ["review"]
+instructions = "Be careful."
+
+[run]
+model = "gpt-5"
+approval_policy = "never"
+
+[mcp_servers.memory]
+command = "npx"
+args = ["-y", "@modelcontextprotocol/server-memory"]
+"#,
+        );
+        let child = write(
+            tmp.path(),
+            "child.toml",
+            r#"
+extends = "base.toml"
+name = "child"
+instructions = "Check tests."
+instructions_mode = "append"
+
+[run]
+model = "gpt-5-nano"
+"#,
+        );
+        let content = std::fs::read_to_string(&child).unwrap();
+        let cfg = CodexTomlParser::parse_in(&content, &child, &[])
+            .unwrap()
+            .remove(0);
+        assert_eq!(cfg.name, "child");
+        assert_eq!(cfg.tags, Some(vec!["review".to_string()]));
+        assert_eq!(
+            cfg.instructions.as_deref(),
+            Some("Be careful.\n\nCheck tests.")
+        );
+        let run = cfg.run.unwrap();
+        assert_eq!(run.model.as_deref(), Some("gpt-5-nano"));
+        assert!(run.approval_policy.is_some());
+        assert!(cfg.mcp_servers.unwrap().get("memory").is_some());
+        let base = tmp.path().join("base.toml");
+        assert!(CodexTomlParser::is_abstract(
+            &std::fs::read_to_string(&base).unwrap(),
+            &base
+        ));
+    }
+
+    #[test]
+    fn extends_by_name_and_cycles() {
+        let tmp = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        write(
+            shared.path(),
+            "common.toml",
+            "name = \"Shared Reviewer\"\ninstructions = \"Base.\"\n",
+        );
+        let child = write(
+            tmp.path(),
+            "child.toml",
+            "extends = \"shared-reviewer\"\nname = \"child\"\n",
+        );
+        let content = std::fs::read_to_string(&child).unwrap();
+        let cfg = CodexTomlParser::parse_in(&content, &child, &[shared.path().to_path_buf()])
+            .unwrap()
+            .remove(0);
+        assert_eq!(cfg.instructions.as_deref(), Some("Base."));
+
+        write(tmp.path(), "a.toml", "extends = \"b.toml\"\nname = \"a\"\n");
+        let b = write(tmp.path(), "b.toml", "extends = \"a.toml\"\nname = \"b\"\n");
+        let err =
+            CodexTomlParser::parse_in(&std::fs::read_to_string(&b).unwrap(), &b, &[]).unwrap_err();
+        assert!(err.to_string().contains("extends cycle"), "{err:#}");
+    }
+
+    #[test]
+    fn multiple_agents_inherit_file_defaults() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(
+            tmp.path(),
+            "base.toml",
+            "name = \"base\"\ntags = [\"shared\"]\n",
+        );
+        let file = write(
+            tmp.path(),
+            "team.toml",
+            r#"
+[defaults]
+description = "Team agent"
+tags = ["team"]
+
+[defaults.run]
+model = "gpt-5"
+
+[[agents]]
+name = "reviewer"
+instructions = "Review."
+
+[[agents]]
+name = "writer"
+extends = "base.toml"
+run = { model = "gpt-5-nano" }
+
+[[agents]]
+name = "draft"
+abstract = true
+"#,
+        );
+        write(tmp.path(), "team.prompt.md", "Not used by entries.");
+        let content = std::fs::read_to_string(&file).unwrap();
+        let cfgs = CodexTomlParser::parse_in(&content, &file, &[]).unwrap();
+        assert_eq!(cfgs.len(), 2);
+        assert_eq!(cfgs[0].name, "reviewer");
+        assert_eq!(cfgs[0].description, "Team agent");
+        assert_eq!(cfgs[0].tags, Some(vec!["team".to_string()]));
+        assert_eq!(
+            cfgs[0].run.as_ref().unwrap().model.as_deref(),
+            Some("gpt-5")
+        );
+        assert_eq!(cfgs[1].name, "writer");
+        assert_eq!(cfgs[1].instructions, None);
+        assert_eq!(cfgs[1].tags, Some(vec!["team".to_string()]));
+        assert_eq!(
+            cfgs[1].run.as_ref().unwrap().model.as_deref(),
+            Some("gpt-5-nano")
+        );
+
+        let cfgs = CodexTomlParser::parse_in(
+            "[defaults]\ndescription = \"d\"\n[agents.triage]\n[agents.fixer]\nname = \"Bug Fixer\"\n",
+            &file,
+            &[],
+        )
+        .unwrap();
+        let names: Vec<_> = cfgs.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Bug Fixer", "triage"]);
+
+        // Invalid entries are skipped; labels follow the file, not the output
+        let entries = CodexTomlParser::parse_entries(
+            "[[agents]]\nname = \"base\"\nabstract = true\n[[agents]]\ndescription = \"x\"\n[[agents]]\nname = \"ok\"\n",
+            &file,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0.as_deref(), Some("agents[2]"));
+        assert_eq!(entries[0].1.name, "ok");
+        let entries = CodexTomlParser::parse_entries(
+            "[agents.triage]\n[agents.fixer]\nname = \"Bug Fixer\"\n",
+            &file,
+            &[],
+        )
+        .unwrap();
+        let labels: Vec<_> = entries.iter().map(|(l, _)| l.as_deref()).collect();
+        assert_eq!(labels, [Some("agents.fixer"), Some("agents.triage")]);
+    }
+}
@@ -0,0 +1,164 @@
+//! Parser for Cursor rules (`.cursor/rules/*.mdc`) and project context files
+//! (`AGENTS.md`, `CLAUDE.md`).
+//!
+//! Each file becomes one agent whose instructions are the file body. Rule
+//! frontmatter (`description`, `globs`, `alwaysApply`) is read line by line
+//! rather than as YAML: Cursor writes unquoted globs such as `globs: *.ts`,
+//! which YAML rejects.
+
+use std::path::Path;
+
+use crate::model::AgentConfig;
+
+use super::{AgentParser, loose_frontmatter, unquote};
+
+/// Project guides loaded as agents next to Cursor rules.
+const CONTEXT_FILES: &[&str] = &["AGENTS.md", "CLAUDE.md"];
+
+/// Parser for Cursor rules and project context files.
+pub struct CursorRuleParser;
+
+#[derive(Debug, Default)]
+struct Frontmatter {
+    description: Option<String>,
+    globs: Vec<String>,
+    always_apply: bool,
+}
+
+impl AgentParser for CursorRuleParser {
+    fn supports(path: &Path) -> bool {
+        let is_mdc = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("mdc"));
+        is_mdc || is_context_file(path)
+    }
+
+    fn parse(content: &str, path: &Path) -> anyhow::Result<Vec<AgentConfig>> {
+        let (fm, body) = split_frontmatter(content);
+        let body = body.trim();
+        if body.is_empty() {
+            anyhow::bail!("empty rule body in {}", path.display());
+        }
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("rule")
+            .to_string();
+
+        let mut description = fm
+            .description
+            .filter(|d| !d.trim().is_empty())
+            .unwrap_or_else(|| {
+                if is_context_file(path) {
+                    format!("Follow the project guide in {name}.md")
+                } else {
+                    format!("Apply the '{name}' Cursor rule")
+                }
+            });
+        if !fm.globs.is_empty() {
+            description.push_str(&format!(" (applies to: {})", fm.globs.join(", ")));
+        }
+        if fm.always_apply {
+            description.push_str(" [always applied]");
+        }
+        let tag = if is_context_file(path) {
+            "project-guide"
+        } else {
+            "cursor-rule"
+        };
+
+        Ok(vec![AgentConfig {
+            name,
+            description,
+            tags: Some(vec![tag.to_string()]),
+            toggles: None,
+            mcp_tool_refs: None,
+            instructions_file: None,
+            instructions: Some(body.to_string()),
+            templating: None,
+            vars: None,
+            run: None,
+            mcp_servers: None,
+            model_providers: None,
+            runner: None,
+            inherit_tools: None,
+            deny_tools: None,
+            task_template: None,
+            color: None,
+        }])
+    }
+}
+
+fn is_context_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| CONTEXT_FILES.iter().any(|c| c.eq_ignore_ascii_case(n)))
+}
+
+/// Rule fields and the body; files without front-matter are all body.
+fn split_frontmatter(content: &str) -> (Frontmatter, &str) {
+    let (fields, body) = loose_frontmatter(content);
+    let mut fm = Frontmatter::default();
+    for (key, value) in fields {
+        match key.as_str() {
+            "description" => fm.description = Some(unquote(&value).to_string()),
+            "globs" => fm.globs.extend(
+                value
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .split(',')
+                    .map(|g| unquote(g.trim()).to_string())
+                    .filter(|g| !g.is_empty()),
+            ),
+            "alwaysApply" | "always_apply" => {
+                fm.always_apply = value.eq_ignore_ascii_case("true");
+            }
+            _ => {}
+        }
+    }
+    (fm, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_expose_globs_and_context_files_load_whole() {
+        let rule = "---\ndescription: TypeScript style\nglobs: *.ts, src/**/*.tsx\n\
+                    alwaysApply: false\n---\nUse strict types.\n";
+        let path = Path::new("/ws/.cursor/rules/ts-style.mdc");
+        assert!(CursorRuleParser::supports(path));
+        let cfg = CursorRuleParser::parse(rule, path).unwrap().remove(0);
+        assert_eq!(cfg.name, "ts-style");
+        assert_eq!(
+            cfg.description,
+            "TypeScript style (applies to: *.ts, src/**/*.tsx)"
+        );
+        assert_eq!(cfg.instructions.as_deref(), Some("Use strict types."));
+
+        let rule = "---\ndescription:\nglobs:\n  - \"docs/**\"\nalwaysApply: true\n---\nBe brief.";
+        let cfg = CursorRuleParser::parse(rule, Path::new("docs.mdc"))
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            cfg.description,
+            "Apply the 'docs' Cursor rule (applies to: docs/**) [always applied]"
+        );
+
+        let path = Path::new("/ws/AGENTS.md");
+        assert!(CursorRuleParser::supports(path));
+        assert!(!CursorRuleParser::supports(Path::new("/ws/README.md")));
+        let cfg = CursorRuleParser::parse("# Guide\n\nRun `make test`.\n", path)
+            .unwrap()
+            .remove(0);
+        assert_eq!(cfg.name, "AGENTS");
+        assert_eq!(cfg.tags, Some(vec!["project-guide".to_string()]));
+        assert_eq!(
+            cfg.instructions.as_deref(),
+            Some("# Guide\n\nRun `make test`.")
+        );
+        assert!(CursorRuleParser::parse("---\nglobs: x\n---\n", path).is_err());
+    }
+}
@@ -1,20 +1,88 @@
 //! Agent config parsers for different provider formats.
 //!
 //! Each parser declares a `supports` predicate over file paths and a `parse`
-//! function that returns a normalized `AgentConfig`.
+//! function that returns the normalized `AgentConfig`s defined by a file
+//! (one for Markdown formats; TOML, YAML and JSON files may hold several).
+//! Markdown formats that are not strict YAML share [`loose_frontmatter`].
 
 use std::path::Path;
 
 use anyhow::Result;
 
-use crate::model::AgentConfig;
+use crate::model::{AgentConfig, AgentRun};
 
 /// Parser trait implemented by provider-specific formats.
 pub trait AgentParser {
     fn supports(path: &Path) -> bool;
-    fn parse(content: &str, path: &Path) -> Result<AgentConfig>;
+    fn parse(content: &str, path: &Path) -> Result<Vec<AgentConfig>>;
+}
+
+/// Run settings from a front-matter `run:` block (any `[run]` field), with the
+/// top-level `model`/`provider` shorthands filling fields the block leaves unset.
+/// `None` when none of them is given.
+pub fn frontmatter_run(
+    block: Option<AgentRun>,
+    model: Option<String>,
+    provider: Option<String>,
+) -> Option<AgentRun> {
+    if block.is_none() && model.is_none() && provider.is_none() {
+        return None;
+    }
+    let mut run = block.unwrap_or_default();
+    if run.model.is_none() {
+        run.model = model;
+    }
+    if run.model_provider.is_none() {
+        run.model_provider = provider;
+    }
+    Some(run)
+}
+
+/// `key: value` front-matter read line by line, for hand-written formats that
+/// are not valid YAML (Cursor's `globs: *.ts`, Claude's `argument-hint: [pr]`).
+/// Returns raw values in order, with `- item` lines under an empty `key:`
+/// repeated as `key` entries, and the body. Content without `---` is all body.
+pub fn loose_frontmatter(content: &str) -> (Vec<(String, String)>, &str) {
+    let mut fields = Vec::new();
+    let Some(rest) = content.trim_start().strip_prefix("---") else {
+        return (fields, content);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (fields, content);
+    };
+    let body = rest[end + 4..].split_once('\n').map_or("", |(_, b)| b);
+    let mut list_key: Option<&str> = None;
+    for line in rest[..end].lines() {
+        let t = line.trim();
+        if let Some(item) = t.strip_prefix("- ")
+            && let Some(key) = list_key
+        {
+            fields.push((key.to_string(), item.trim().to_string()));
+            continue;
+        }
+        let Some((key, value)) = t.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        list_key = value.is_empty().then_some(key);
+        if !value.is_empty() {
+            fields.push((key.to_string(), value.to_string()));
+        }
+    }
+    (fields, body)
+}
+
+/// Strip one pair of matching single or double quotes.
+pub fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .or_else(|| s.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')))
+        .unwrap_or(s)
 }
 
 pub mod anthropic_frontmatter;
 pub mod codex_toml;
+pub mod cursor_rule;
+pub mod prompt_command;
+pub mod structured;
 pub mod vscode_chatmode;
@@ -0,0 +1,176 @@
+//! Parser for Codex custom prompts (`~/.codex/prompts/*.md`) and Claude slash
+//! commands (`.claude/commands/*.md`).
+//!
+//! The body is a task template: each call's `task` fills `$ARGUMENTS` and
+//! `$1`..`$9` (see [`crate::template::render_task`]). Front-matter is
+//! optional; `description`, `argument-hint`, `allowed-tools` and `model` are
+//! read. Claude permission rules such as `Bash(git add:*)` grant the whole
+//! tool, since Codex cannot scope a tool to certain commands.
+
+use std::path::Path;
+
+use crate::mcp::types::McpToolRef;
+use crate::model::AgentConfig;
+
+use super::{AgentParser, frontmatter_run, loose_frontmatter, unquote};
+
+/// Parser for prompt and command templates.
+pub struct PromptCommandParser;
+
+impl AgentParser for PromptCommandParser {
+    fn supports(path: &Path) -> bool {
+        path.extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("md"))
+    }
+
+    fn parse(content: &str, path: &Path) -> anyhow::Result<Vec<AgentConfig>> {
+        let (fields, body) = loose_frontmatter(content);
+        let body = body.trim();
+        if body.is_empty() {
+            anyhow::bail!("empty prompt in {}", path.display());
+        }
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("prompt")
+            .to_string();
+
+        let mut description = None;
+        let mut hint = None;
+        let mut model = None;
+        let mut tools: Option<Vec<String>> = None;
+        for (key, value) in fields {
+            match key.as_str() {
+                "description" => description = Some(unquote(&value).to_string()),
+                "argument-hint" | "argument_hint" => hint = Some(unquote(&value).to_string()),
+                "model" => model = Some(unquote(&value).to_string()),
+                "allowed-tools" | "allowed_tools" => {
+                    tools
+                        .get_or_insert_with(Vec::new)
+                        .extend(split_tools(&value));
+                }
+                _ => {}
+            }
+        }
+
+        // Without a description, the first line of the prompt says what it does
+        let mut description = description
+            .filter(|d| !d.trim().is_empty())
+            .or_else(|| {
+                body.lines()
+                    .map(|l| l.trim().trim_start_matches('#').trim())
+                    .find(|l| !l.is_empty())
+                    .map(|l| l.chars().take(120).collect())
+            })
+            .unwrap_or_else(|| format!("Run the '{name}' prompt"));
+        if let Some(hint) = hint.filter(|h| !h.trim().is_empty()) {
+            description.push_str(&format!(" (arguments: {hint})"));
+        }
+
+        let mcp_tool_refs = tools.map(|v| {
+            let mut refs: Vec<McpToolRef> = Vec::new();
+            for r in v.iter().map(|t| McpToolRef::parse(t)) {
+                if !refs.contains(&r) {
+                    refs.push(r);
+                }
+            }
+            refs
+        });
+        let model = model.filter(|m| !m.trim().eq_ignore_ascii_case("inherit"));
+
+        Ok(vec![AgentConfig {
+            name,
+            description,
+            tags: Some(vec!["command".to_string()]),
+            toggles: None, // allowed-tools become toggles via the Anthropic mapping
+            mcp_tool_refs,
+            instructions_file: None,
+            instructions: None, // taken from the base agent, if any
+            templating: None,
+            vars: None,
+            run: frontmatter_run(None, model, None),
+            mcp_servers: None,
+            model_providers: None,
+            runner: None,
+            inherit_tools: None,
+            deny_tools: None,
+            task_template: Some(body.to_string()),
+            color: None,
+        }])
+    }
+}
+
+/// Split an `allowed-tools` value on top-level commas (or whitespace between
+/// entries) and drop permission rules: `Bash(git add:*)` → `Bash`.
+fn split_tools(value: &str) -> Vec<String> {
+    let value = value.trim().trim_start_matches('[').trim_end_matches(']');
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut depth = 0usize;
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' | ' ' | '\t' if depth == 0 => {
+                out.push(std::mem::take(&mut cur));
+                continue;
+            }
+            _ if depth > 0 => continue,
+            _ => {}
+        }
+        if depth == 0 && c != ')' {
+            cur.push(c);
+        }
+    }
+    out.push(cur);
+    out.into_iter()
+        .map(|t| unquote(t.trim()).to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_keep_the_template_and_map_allowed_tools() {
+        let content = "---\nallowed-tools: Bash(git add:*), Bash(git status:*), Edit, mcp__github__get_pr\nargument-hint: [pr-number] [priority]\ndescription: Review a pull request\nmodel: claude-3-5-haiku-20241022\n---\nReview PR #$1 (priority $2).\n";
+        let path = Path::new("/ws/.claude/commands/review-pr.md");
+        let cfg = PromptCommandParser::parse(content, path).unwrap().remove(0);
+        assert_eq!(cfg.name, "review-pr");
+        assert_eq!(
+            cfg.description,
+            "Review a pull request (arguments: [pr-number] [priority])"
+        );
+        assert_eq!(
+            cfg.mcp_tool_refs.unwrap(),
+            vec![
+                McpToolRef::parse("Bash"),
+                McpToolRef::parse("Edit"),
+                McpToolRef::parse("github/get_pr"),
+            ]
+        );
+        assert_eq!(
+            cfg.run.unwrap().model.as_deref(),
+            Some("claude-3-5-haiku-20241022")
+        );
+        assert_eq!(
+            cfg.task_template.as_deref(),
+            Some("Review PR #$1 (priority $2).")
+        );
+        assert!(cfg.instructions.is_none());
+
+        // Codex prompts are usually plain Markdown
+        let path = Path::new("/home/me/.codex/prompts/changelog.md");
+        let cfg = PromptCommandParser::parse("# Draft a changelog\n\nFor $ARGUMENTS.", path)
+            .unwrap()
+            .remove(0);
+        assert_eq!(cfg.description, "Draft a changelog");
+        assert!(cfg.mcp_tool_refs.is_none() && cfg.run.is_none());
+
+        assert_eq!(split_tools("[Read, \"Grep\"]"), ["Read", "Grep"]);
+        assert!(PromptCommandParser::parse("---\nmodel: x\n---\n", path).is_err());
+    }
+}
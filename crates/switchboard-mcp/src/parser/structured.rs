@@ -0,0 +1,227 @@
+//! Parser for `.agent.yaml` / `.agent.yml` / `.agent.json` agent definitions.
+//!
+//! These use the Switchboard TOML schema unchanged (including `extends`,
+//! `[[agents]]` and `defaults`); the document is converted to a TOML table
+//! and handed to [`super::codex_toml::CodexTomlParser`], which rejects keys
+//! that `schema/agent.schema.json` does not define.
+
+use std::path::Path;
+
+use anyhow::Context as _;
+use serde_json::Value as JsonValue;
+use toml::Value as TomlValue;
+
+/// JSON Schema for structured agent files (`switchboard-mcp schema`).
+pub const AGENT_SCHEMA: &str = include_str!("../../schema/agent.schema.json");
+
+/// Recognizes YAML and JSON agent files.
+pub struct StructuredAgentParser;
+
+impl StructuredAgentParser {
+    pub fn supports(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.to_ascii_lowercase())
+            .is_some_and(|n| {
+                n.ends_with(".agent.yaml")
+                    || n.ends_with(".agent.yml")
+                    || n.ends_with(".agent.json")
+            })
+    }
+}
+
+/// Read a YAML or JSON agent document as a TOML table. `null` values are
+/// dropped and the editor-only `$schema` key is ignored.
+pub fn to_table(content: &str, path: &Path) -> anyhow::Result<toml::Table> {
+    let is_json = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    let value: JsonValue = if is_json {
+        serde_json::from_str(content).context("invalid JSON")?
+    } else {
+        serde_yaml::from_str(content).context("invalid YAML")?
+    };
+    match to_toml(value) {
+        Some(TomlValue::Table(mut t)) => {
+            t.remove("$schema");
+            Ok(t)
+        }
+        _ => anyhow::bail!("expected an object at the root"),
+    }
+}
+
+fn to_toml(value: JsonValue) -> Option<TomlValue> {
+    Some(match value {
+        JsonValue::Null => return None,
+        JsonValue::Bool(b) => TomlValue::Boolean(b),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(i) => TomlValue::Integer(i),
+            None => TomlValue::Float(n.as_f64()?),
+        },
+        JsonValue::String(s) => TomlValue::String(s),
+        JsonValue::Array(items) => {
+            TomlValue::Array(items.into_iter().filter_map(to_toml).collect())
+        }
+        JsonValue::Object(map) => TomlValue::Table(
+            map.into_iter()
+                .filter_map(|(k, v)| to_toml(v).map(|v| (k, v)))
+                .collect(),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::AgentRun;
+    use crate::parser::AgentParser;
+    use crate::parser::codex_toml::CodexTomlParser;
+
+    #[test]
+    fn yaml_and_json_match_the_toml_schema() {
+        let tmp = tempfile::tempdir().unwrap();
+        let base = tmp.path().join("base.toml");
+        std::fs::write(
+            &base,
+            "name = \"base\"\n[run]\napproval_policy = \"never\"\n",
+        )
+        .unwrap();
+
+        let yaml = r#"
+# yaml-language-server: $schema=../schema/agent.schema.json
+name: reviewer
+tags: review, security
+tools: [plan, web_search]
+extends: base.toml
+run:
+  model: gpt-5-nano
+  max_tokens_per_call: 20000
+mcp_servers:
+  memory:
+    command: npx
+    args: ["-y", "@modelcontextprotocol/server-memory"]
+instructions_file: ~
+"#;
+        let path = tmp.path().join("reviewer.agent.yaml");
+        assert!(StructuredAgentParser::supports(&path));
+        assert!(!CodexTomlParser::supports(&path));
+        let cfg = CodexTomlParser::parse_in(yaml, &path, &[])
+            .unwrap()
+            .remove(0);
+        assert_eq!(cfg.name, "reviewer");
+        assert_eq!(
+            cfg.tags,
+            Some(vec!["review".to_string(), "security".to_string()])
+        );
+        assert_eq!(cfg.toggles.unwrap().include_plan_tool, Some(true));
+        let run = cfg.run.unwrap();
+        assert_eq!(run.model.as_deref(), Some("gpt-5-nano"));
+        assert_eq!(run.max_tokens_per_call, Some(20000));
+        assert!(run.approval_policy.is_some());
+        assert!(cfg.mcp_servers.unwrap().get("memory").is_some());
+        assert!(cfg.instructions_file.is_none());
+
+        let json = r#"{
+  "$schema": "https://example.com/agent.schema.json",
+  "defaults": { "description": "Generated" },
+  "agents": [{ "name": "a", "instructions": "Do A." }, { "name": "b" }]
+}"#;
+        let path = tmp.path().join("gen.agent.json");
+        let cfgs = CodexTomlParser::parse_in(json, &path, &[]).unwrap();
+        assert_eq!(cfgs.len(), 2);
+        assert_eq!(cfgs[1].description, "Generated");
+
+        let err = CodexTomlParser::parse_in("[1, 2]", &path, &[]).unwrap_err();
+        assert!(err.to_string().contains("object at the root"), "{err:#}");
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let path = Path::new("/tmp/typo.agent.yaml");
+        let err = CodexTomlParser::parse_in(
+            "name: a
+color: red
+",
+            path,
+            &[],
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("color"), "{err:#}");
+        let err = CodexTomlParser::parse_in(
+            "name: a
+run:
+  modle: gpt-5
+",
+            path,
+            &[],
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("modle"), "{err:#}");
+        let err = CodexTomlParser::parse_in(
+            "agents: []
+name: a
+",
+            path,
+            &[],
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("defaults"), "{err:#}");
+        // Wrong types fail too, instead of dropping to looser defaults
+        for doc in [
+            "name: a\nrun:\n  sandbox_mode: readonly\n",
+            "name: a\nrun: strict\n",
+            "name: a\nvars: [x]\n",
+            "name: a\ntemplating: yes please\n",
+            "name: a\nrunner: [mock]\n",
+        ] {
+            let err = CodexTomlParser::parse_in(doc, path, &[]).unwrap_err();
+            assert!(format!("{err:#}").contains("invalid"), "{doc}: {err:#}");
+        }
+        // A misspelled entry is skipped; its siblings load
+        let cfgs = CodexTomlParser::parse_in(
+            "agents:\n  - name: a\n    instructons: x\n  - name: b\n",
+            path,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(cfgs.len(), 1);
+        assert_eq!(cfgs[0].name, "b");
+
+        // TOML files keep loading and only warn
+        let path = Path::new("/tmp/typo.toml");
+        let cfg = CodexTomlParser::parse(
+            "name = \"a\"\ncolor = \"red\"\n[run]\nmodle = \"x\"\n",
+            path,
+        )
+        .unwrap()
+        .remove(0);
+        assert_eq!(cfg.name, "a");
+        assert!(cfg.run.unwrap().model.is_none());
+        let cfg =
+            CodexTomlParser::parse("name = \"a\"\n[run]\nsandbox_mode = \"readonly\"\n", path)
+                .unwrap()
+                .remove(0);
+        assert!(cfg.run.is_none());
+    }
+
+    #[test]
+    fn published_schema_matches_the_parser() {
+        let schema: JsonValue = serde_json::from_str(AGENT_SCHEMA).unwrap();
+        let keys = |v: &JsonValue| -> Vec<String> {
+            let mut k: Vec<String> = v.as_object().unwrap().keys().cloned().collect();
+            k.sort();
+            k
+        };
+        let mut run_fields = AgentRun::field_names();
+        run_fields.sort();
+        assert_eq!(keys(&schema["$defs"]["run"]["properties"]), run_fields);
+
+        // Entries take every top-level key except the file-level ones
+        let mut top = keys(&schema["properties"]);
+        top.retain(|k| !["$schema", "defaults", "agents"].contains(&k.as_str()));
+        assert_eq!(keys(&schema["$defs"]["entry"]["properties"]), top);
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(schema["$defs"]["entry"]["additionalProperties"], false);
+    }
+}
@@ -7,7 +7,7 @@ use std::path::Path;
 use crate::mcp::types::McpToolRef;
 use crate::model::{AgentConfig, AgentRun};
 
-use super::AgentParser;
+use super::{AgentParser, frontmatter_run};
 
 /// Parser for VSCode chatmode files.
 pub struct VscodeChatmodeParser;
@@ -18,13 +18,23 @@ struct Frontmatter {
     name: Option<String>,
     description: String,
     #[serde(default)]
-    tools: ToolsField, // VSCode tools are metadata; do not map to Codex toggles
+    tools: ToolsField, // mapped through the VS Code tool table
+    #[serde(default, alias = "disallowedTools")]
+    disallowed_tools: ToolsField, // same forms as `tools`; always wins
     #[serde(default)]
-    model: Option<String>, // metadata only
+    model: Option<String>,
     #[serde(default, alias = "provider", alias = "modelProvider")]
     model_provider: Option<String>, // optional provider
     #[serde(default)]
     tags: ToolsField, // optional metadata (string or list)
+    #[serde(default)]
+    templating: Option<bool>,
+    #[serde(default)]
+    vars: Option<std::collections::HashMap<String, String>>,
+    #[serde(default)]
+    runner: Option<String>, // optional runner backend
+    #[serde(default)]
+    run: Option<AgentRun>, // any [run] field; wins over model/provider
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -67,7 +77,7 @@ impl AgentParser for VscodeChatmodeParser {
         fname.ends_with(".chatmode.md")
     }
 
-    fn parse(content: &str, path: &Path) -> anyhow::Result<AgentConfig> {
+    fn parse(content: &str, path: &Path) -> anyhow::Result<Vec<AgentConfig>> {
         // Detect and split YAML frontmatter delimited by --- ... --- at file start
         let mut first_non_empty = None;
         for (idx, l) in content.lines().enumerate() {
@@ -106,9 +116,6 @@ impl AgentParser for VscodeChatmodeParser {
 
         let fm: Frontmatter =
             serde_yaml::from_str(&yaml_buf).context("invalid YAML frontmatter")?;
-        // Touch fields to satisfy dead_code lint (metadata only).
-        let _unused_tools = &fm.tools;
-        let _unused_model = &fm.model;
         let name = fm.name.unwrap_or_else(|| derive_name_from_filename(path));
         let description = fm.description;
 
@@ -119,36 +126,39 @@ impl AgentParser for VscodeChatmodeParser {
             .collect::<Vec<&str>>()
             .join("\n");
 
-        // Build tool refs: either Bare or Namespaced
-        let mcp_tool_refs: Option<Vec<McpToolRef>> = fm.tools.into_vec().map(|tools| {
-            tools
-                .into_iter()
-                .map(|t| parse_vscode_tool_ref(&t))
-                .collect()
-        });
-
-        // Optional run mapping (model only; provider left unset for Codex defaults)
-        let run = if fm.model.is_some() || fm.model_provider.is_some() {
-            Some(AgentRun {
-                model: fm.model.clone(),
-                model_provider: fm.model_provider.clone(),
-                ..Default::default()
-            })
-        } else {
-            None
-        };
-
-        Ok(AgentConfig {
+        // Build tool refs: bare, namespaced (`server/tool`) or whole servers
+        // (`#server`, `server/*`); tool sets are expanded by the loader.
+        let mcp_tool_refs: Option<Vec<McpToolRef>> = fm
+            .tools
+            .into_vec()
+            .map(|tools| tools.into_iter().map(|t| McpToolRef::parse(&t)).collect());
+        let deny_tools: Option<Vec<McpToolRef>> = fm
+            .disallowed_tools
+            .into_vec()
+            .map(|tools| tools.into_iter().map(|t| McpToolRef::parse(&t)).collect());
+
+        // Optional run settings: `run:` block plus the model/provider shorthands
+        let run = frontmatter_run(fm.run, fm.model, fm.model_provider);
+
+        Ok(vec![AgentConfig {
             name,
             description,
             tags: fm.tags.into_vec(),
-            toggles: None, // vscode tools are metadata, not codex toggles
+            toggles: None, // tools become toggles via the provider mapping
             mcp_tool_refs,
             instructions_file: None,
             instructions: Some(body.trim().to_string()),
+            templating: fm.templating,
+            vars: fm.vars,
             run,
             mcp_servers: None,
-        })
+            model_providers: None,
+            runner: fm.runner,
+            inherit_tools: None,
+            deny_tools,
+            task_template: None,
+            color: None,
+        }])
     }
 }
 
@@ -157,16 +167,3 @@ fn derive_name_from_filename(path: &Path) -> String {
     // file_stem() on .chatmode.md returns "<name>.chatmode"; trim suffix
     fname.strip_suffix(".chatmode").unwrap_or(fname).to_string()
 }
-
-fn parse_vscode_tool_ref(s: &str) -> McpToolRef {
-    if let Some((server, tool)) = s.split_once("::") {
-        McpToolRef::Namespaced {
-            server_key: server.trim().to_string(),
-            tool: tool.trim().to_string(),
-        }
-    } else {
-        McpToolRef::Bare {
-            tool: s.trim().to_string(),
-        }
-    }
-}
@@ -0,0 +1,261 @@
+//! Replayable run records and re-run comparison.
+//!
+//! Each agent call is saved to `SWITCHBOARD_HOME/replays/<run-id>.json` with
+//! enough context (tool, task, cwd) to execute it again, plus the original
+//! output and final diff. A re-run produces a side-by-side comparison against
+//! the recorded result so prompt or model changes can be checked for regressions.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A recorded agent call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayRecord {
+    pub run_id: String,
+    pub recorded_at: DateTime<Utc>,
+    pub tool: String,
+    pub agent: String,
+    pub source: String,
+    pub task: String,
+    pub cwd: String,
+    pub model: Option<String>,
+    pub provider: Option<String>,
+    pub ok: bool,
+    pub output: String,
+    /// Final unified diff of the run (last `TurnDiff`), if any.
+    pub diff: Option<String>,
+    /// Run this record re-executes, when it came from a re-run.
+    pub rerun_of: Option<String>,
+}
+
+/// Optional changes applied when re-running a record.
+#[derive(Debug, Clone, Default)]
+pub struct RerunOverrides {
+    /// Model to use instead of the agent's configured model.
+    pub model: Option<String>,
+    /// Tool name of a different agent (e.g. a new version) to run instead.
+    pub tool: Option<String>,
+}
+
+/// Parsed `switchboard-mcp replay <run-id> [--model M] [--agent TOOL]`.
+#[derive(Debug, Clone)]
+pub struct ReplayCommand {
+    pub run_id: String,
+    pub overrides: RerunOverrides,
+}
+
+/// Parse process arguments (without the program name). Returns `None` when the
+/// first argument is not `replay`, i.e. the server should start normally.
+pub fn parse_replay_args(args: &[String]) -> anyhow::Result<Option<ReplayCommand>> {
+    let Some((first, rest)) = args.split_first() else {
+        return Ok(None);
+    };
+    if first != "replay" {
+        return Ok(None);
+    }
+    let mut run_id = None;
+    let mut overrides = RerunOverrides::default();
+    let mut it = rest.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--model" => {
+                overrides.model = Some(it.next().context("--model requires a value")?.clone())
+            }
+            "--agent" => {
+                overrides.tool = Some(it.next().context("--agent requires a tool name")?.clone())
+            }
+            other if other.starts_with("--") => anyhow::bail!("unknown option '{other}'"),
+            other if run_id.is_none() => run_id = Some(other.to_string()),
+            other => anyhow::bail!("unexpected argument '{other}'"),
+        }
+    }
+    let run_id =
+        run_id.context("usage: switchboard-mcp replay <run-id> [--model MODEL] [--agent TOOL]")?;
+    Ok(Some(ReplayCommand { run_id, overrides }))
+}
+
+/// Directory of `<run-id>.json` records.
+pub struct ReplayStore {
+    dir: PathBuf,
+}
+
+impl ReplayStore {
+    pub fn open(dir: PathBuf) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("create replay dir {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    pub fn save(&self, record: &ReplayRecord) -> anyhow::Result<()> {
+        let path = self.path_for(&record.run_id)?;
+        let json = serde_json::to_string_pretty(record).context("serialize replay record")?;
+        write_atomic(&path, json.as_bytes())
+    }
+
+    pub fn load(&self, run_id: &str) -> anyhow::Result<ReplayRecord> {
+        let path = self.path_for(run_id)?;
+        let s = std::fs::read_to_string(&path)
+            .with_context(|| format!("no replay record for run '{run_id}'"))?;
+        serde_json::from_str(&s).with_context(|| format!("parse {}", path.display()))
+    }
+
+    /// Reject ids that could escape the replay directory.
+    fn path_for(&self, run_id: &str) -> anyhow::Result<PathBuf> {
+        if run_id.is_empty()
+            || !run_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            anyhow::bail!("invalid run id '{run_id}'");
+        }
+        Ok(self.dir.join(format!("{run_id}.json")))
+    }
+}
+
+/// Write `path` through a temp file in the same directory and a rename, so
+/// readers never see a torn file.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("create temp file in {}", dir.display()))?;
+    std::io::Write::write_all(&mut tmp, contents)
+        .with_context(|| format!("write {}", path.display()))?;
+    tmp.as_file()
+        .sync_all()
+        .with_context(|| format!("sync {}", path.display()))?;
+    tmp.persist(path)
+        .with_context(|| format!("replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Render a comparison of an original record and its re-run.
+pub fn render_comparison(original: &ReplayRecord, rerun: &ReplayRecord) -> String {
+    let mut out = String::new();
+    out.push_str(&format!(
+        "run {} ({}, model={}) vs re-run {} ({}, model={})\n",
+        original.run_id,
+        original.tool,
+        original.model.as_deref().unwrap_or("<default>"),
+        rerun.run_id,
+        rerun.tool,
+        rerun.model.as_deref().unwrap_or("<default>"),
+    ));
+    out.push_str(&format!("ok: {} -> {}\n", original.ok, rerun.ok));
+    let sections = [
+        ("output", original.output.as_str(), rerun.output.as_str()),
+        (
+            "diff",
+            original.diff.as_deref().unwrap_or(""),
+            rerun.diff.as_deref().unwrap_or(""),
+        ),
+    ];
+    for (label, left, right) in sections {
+        let verdict = if left.trim_end() == right.trim_end() {
+            "identical"
+        } else {
+            "differs"
+        };
+        out.push_str(&format!("\n== {label}: {verdict} ==\n"));
+        if verdict == "differs" {
+            out.push_str(&side_by_side(left, right, COLUMN_WIDTH));
+        }
+    }
+    out
+}
+
+const COLUMN_WIDTH: usize = 60;
+
+/// Two-column view; the gutter is `|` where lines differ and blank otherwise.
+fn side_by_side(left: &str, right: &str, width: usize) -> String {
+    let l: Vec<&str> = left.lines().collect();
+    let r: Vec<&str> = right.lines().collect();
+    let mut out = format!("{:<width$}   {}\n", "original", "re-run");
+    for i in 0..l.len().max(r.len()) {
+        let a = l.get(i).copied().unwrap_or("");
+        let b = r.get(i).copied().unwrap_or("");
+        let gutter = if a == b { ' ' } else { '|' };
+        out.push_str(&format!(
+            "{:<width$} {} {}\n",
+            clip(a, width),
+            gutter,
+            clip(b, width)
+        ));
+    }
+    out
+}
+
+fn clip(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        s.to_string()
+    } else {
+        let mut c: String = s.chars().take(width.saturating_sub(1)).collect();
+        c.push('…');
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(run_id: &str, output: &str) -> ReplayRecord {
+        ReplayRecord {
+            run_id: run_id.to_string(),
+            recorded_at: Utc::now(),
+            tool: "agent_sample".to_string(),
+            agent: "Sample".to_string(),
+            source: "/tmp/sample.toml".to_string(),
+            task: "do it".to_string(),
+            cwd: "/tmp".to_string(),
+            model: None,
+            provider: None,
+            ok: true,
+            output: output.to_string(),
+            diff: None,
+            rerun_of: None,
+        }
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ReplayStore::open(tmp.path().join("replays")).unwrap();
+        store.save(&record("abc-123", "hello")).unwrap();
+        let loaded = store.load("abc-123").unwrap();
+        assert_eq!(loaded.output, "hello");
+        assert!(store.load("missing").is_err());
+        assert!(store.load("../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn replay_args_are_parsed() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(parse_replay_args(&args(&[])).unwrap().is_none());
+        let cmd = parse_replay_args(&args(&[
+            "replay", "r1", "--model", "o3", "--agent", "agent_v2",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(cmd.run_id, "r1");
+        assert_eq!(cmd.overrides.model.as_deref(), Some("o3"));
+        assert_eq!(cmd.overrides.tool.as_deref(), Some("agent_v2"));
+        assert!(parse_replay_args(&args(&["replay"])).is_err());
+        assert!(parse_replay_args(&args(&["replay", "r1", "--bogus"])).is_err());
+    }
+
+    #[test]
+    fn comparison_marks_differing_lines() {
+        let a = record("a", "same\nold line");
+        let b = record("b", "same\nnew line");
+        let text = render_comparison(&a, &b);
+        assert!(text.contains("== output: differs =="));
+        assert!(text.contains("== diff: identical =="));
+        let changed = text.lines().find(|l| l.starts_with("old line")).unwrap();
+        assert!(changed.contains(" | new line"));
+        let same = text.lines().find(|l| l.starts_with("same")).unwrap();
+        assert!(!same.contains('|'));
+    }
+}
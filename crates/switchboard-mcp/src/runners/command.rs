@@ -0,0 +1,407 @@
+//! Generic command runner for non-Codex agent CLIs.
+//!
+//! Runs an arbitrary program (e.g. `claude -p`, `aider --message`, an in-house
+//! script) with templated arguments, a timeout and an environment allowlist,
+//! and maps its stdout/stderr/exit status to `CodexRunOutput`.
+//!
+//! Placeholders: `{task}`, `{instructions}`, `{instructions_file}`, `{model}`,
+//! `{cwd}`, `{agent}`. An argument referencing a placeholder with no value
+//! (e.g. `{model}` when the agent sets none) is dropped, so optional flags
+//! should use the `--flag={value}` form.
+//!
+//! The command receives no tool lists, so agents that deny MCP tools
+//! (`deny_tools`, `disallowedTools`) or may not edit files (patch tool off,
+//! read-only sandbox) are refused rather than run unrestricted.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+
+use super::sampling::can_edit;
+use crate::codex_runner::{CodexRunOutput, CodexRunner, RunTrace};
+use crate::model::PreparedAgent;
+
+/// Env vars passed through when no allowlist is configured.
+const DEFAULT_ENV_ALLOWLIST: &[&str] = &["PATH", "HOME", "USER", "LANG", "TERM", "TMPDIR"];
+
+/// A configured command backend.
+#[derive(Debug, Clone)]
+pub struct CommandRunner {
+    /// Name agents and providers refer to (also reported as the provider).
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub timeout: Duration,
+    /// Variables inherited from the server environment.
+    pub env_allowlist: Vec<String>,
+    /// Fixed variables set for every run.
+    pub env: HashMap<String, String>,
+}
+
+impl CommandRunner {
+    pub fn default_env_allowlist() -> Vec<String> {
+        DEFAULT_ENV_ALLOWLIST
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    /// Expand placeholders in `args`, dropping arguments whose placeholders
+    /// have no value.
+    fn render_args(&self, vars: &HashMap<&str, Option<String>>) -> Vec<String> {
+        self.args
+            .iter()
+            .filter_map(|arg| render_arg(arg, vars))
+            .collect()
+    }
+}
+
+/// Expand one argument in a single left-to-right pass, so placeholder text
+/// inside a substituted value (e.g. a task mentioning `{cwd}`) stays as is.
+/// `None` when a placeholder has no value; unknown `{...}` are kept.
+fn render_arg(arg: &str, vars: &HashMap<&str, Option<String>>) -> Option<String> {
+    let mut out = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').map(|end| (&after[..end], end)) {
+            Some((key, end)) if vars.contains_key(key) => {
+                out.push_str(vars[key].as_deref()?);
+                rest = &after[end + 1..];
+            }
+            _ => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+/// Kill the whole process group led by `pid` (the command and its children).
+#[cfg(unix)]
+fn kill_process_group(pid: u32) {
+    let status = std::process::Command::new("kill")
+        .args(["-KILL", "--", &format!("-{pid}")])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if let Err(e) = status {
+        tracing::warn!("failed to kill process group {}: {}", pid, e);
+    }
+}
+
+#[async_trait]
+impl CodexRunner for CommandRunner {
+    async fn exec_task(
+        &self,
+        prepared: &PreparedAgent,
+        tool: &str,
+        task: &str,
+        cwd: &str,
+    ) -> anyhow::Result<CodexRunOutput> {
+        let cwd_path = std::path::Path::new(cwd);
+        if !cwd_path.is_absolute() {
+            anyhow::bail!("cwd must be an absolute path");
+        }
+        if !prepared.denied_tools.is_empty() {
+            anyhow::bail!(
+                "agent '{}' denies MCP tools, which command runner '{}' cannot enforce",
+                prepared.name,
+                self.name
+            );
+        }
+        if !can_edit(prepared) {
+            anyhow::bail!(
+                "agent '{}' may not edit files, which command runner '{}' cannot enforce",
+                prepared.name,
+                self.name
+            );
+        }
+        let instructions = prepared
+            .instructions
+            .clone()
+            .filter(|s| !s.trim().is_empty());
+        let model = prepared.run.as_ref().and_then(|r| r.model.clone());
+
+        // Only materialize the instructions file when a template asks for it.
+        let instructions_file = if instructions.is_some()
+            && self.args.iter().any(|a| a.contains("{instructions_file}"))
+        {
+            let mut f = tempfile::Builder::new()
+                .prefix("switchboard-instructions-")
+                .suffix(".md")
+                .tempfile()
+                .context("create instructions file")?;
+            std::io::Write::write_all(&mut f, instructions.as_deref().unwrap_or("").as_bytes())
+                .context("write instructions file")?;
+            Some(f)
+        } else {
+            None
+        };
+
+        let vars: HashMap<&str, Option<String>> = HashMap::from([
+            ("task", Some(task.to_string())),
+            ("instructions", instructions.clone()),
+            (
+                "instructions_file",
+                instructions_file
+                    .as_ref()
+                    .map(|f| f.path().display().to_string()),
+            ),
+            ("model", model.clone()),
+            ("cwd", Some(cwd.to_string())),
+            ("agent", Some(prepared.name.clone())),
+        ]);
+        let args = self.render_args(&vars);
+
+        let mut cmd = tokio::process::Command::new(&self.command);
+        cmd.args(&args)
+            .current_dir(cwd_path)
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        // Own process group, so a timeout also stops what the command spawned
+        #[cfg(unix)]
+        cmd.process_group(0);
+        for key in &self.env_allowlist {
+            if let Some(v) = std::env::var_os(key) {
+                cmd.env(key, v);
+            }
+        }
+        cmd.envs(&self.env);
+        tracing::info!(
+            "command runner '{}': tool={}, agent={}, command={}, args={}",
+            self.name,
+            tool,
+            prepared.name,
+            self.command,
+            args.len()
+        );
+
+        let child = cmd
+            .spawn()
+            .with_context(|| format!("failed to launch '{}'", self.command))?;
+        #[cfg(unix)]
+        let pid = child.id();
+        let trace = RunTrace {
+            model,
+            provider: Some(self.name.clone()),
+            ..Default::default()
+        };
+        let output = match tokio::time::timeout(self.timeout, child.wait_with_output()).await {
+            Ok(res) => res.with_context(|| format!("wait for '{}'", self.command))?,
+            Err(_) => {
+                // Dropping the child future kills the process (kill_on_drop);
+                // its children share the process group.
+                #[cfg(unix)]
+                if let Some(pid) = pid {
+                    kill_process_group(pid);
+                }
+                tracing::warn!(
+                    "command runner '{}' timed out after {}s",
+                    self.name,
+                    self.timeout.as_secs()
+                );
+                return Ok(CodexRunOutput {
+                    ok: false,
+                    status: 124,
+                    stdout: String::new(),
+                    stderr: format!(
+                        "'{}' timed out after {}s\n",
+                        self.command,
+                        self.timeout.as_secs()
+                    ),
+                    trace,
+                });
+            }
+        };
+        let ok = output.status.success();
+        let status = if ok {
+            0
+        } else {
+            output.status.code().unwrap_or(1)
+        };
+        tracing::info!(
+            "command runner '{}' finished: ok={}, status={}",
+            self.name,
+            ok,
+            status
+        );
+        Ok(CodexRunOutput {
+            ok,
+            status,
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            trace,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{AgentRun, naming::AgentVariant};
+
+    fn runner(command: &str, args: &[&str], timeout: Duration) -> CommandRunner {
+        CommandRunner {
+            name: "script".to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+            timeout,
+            env_allowlist: CommandRunner::default_env_allowlist(),
+            env: HashMap::from([("SB_TEST".to_string(), "1".to_string())]),
+        }
+    }
+
+    fn agent(model: Option<&str>) -> PreparedAgent {
+        PreparedAgent {
+            tool_name: "anth_writer".to_string(),
+            name: "Writer".to_string(),
+            description: "d".to_string(),
+            provider: AgentVariant::Anthropic,
+            source_path: "/tmp/writer.agent.md".into(),
+            instructions: Some("Be brief.".to_string()),
+            run: model.map(|m| AgentRun {
+                model: Some(m.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unset_placeholders_drop_their_argument() {
+        let r = runner(
+            "claude",
+            &["-p", "{task}", "--model={model}"],
+            Duration::from_secs(1),
+        );
+        let vars = HashMap::from([("task", Some("hi".to_string())), ("model", None)]);
+        assert_eq!(
+            r.render_args(&vars),
+            vec!["-p".to_string(), "hi".to_string()]
+        );
+    }
+
+    #[test]
+    fn substituted_values_are_not_rescanned() {
+        let r = runner(
+            "claude",
+            &["-p", "{task}", "--model={model}", "{cwd}/{unknown}"],
+            Duration::from_secs(1),
+        );
+        let vars = HashMap::from([
+            (
+                "task",
+                Some("print {cwd} and {instructions} and {model}".to_string()),
+            ),
+            ("cwd", Some("/w".to_string())),
+            ("instructions", Some("secret".to_string())),
+            ("model", None),
+        ]);
+        assert_eq!(
+            r.render_args(&vars),
+            vec![
+                "-p".to_string(),
+                "print {cwd} and {instructions} and {model}".to_string(),
+                "/w/{unknown}".to_string(),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn captures_output_and_status() {
+        let r = runner(
+            "sh",
+            &[
+                "-c",
+                "echo \"$0|$1|$SB_TEST\"; exit 3",
+                "{task}",
+                "--model={model}",
+            ],
+            Duration::from_secs(10),
+        );
+        let out = r
+            .exec_task(&agent(Some("sonnet")), "anth_writer", "write", "/tmp")
+            .await
+            .unwrap();
+        assert!(!out.ok);
+        assert_eq!(out.status, 3);
+        assert_eq!(out.stdout.trim(), "write|--model=sonnet|1");
+        assert_eq!(out.trace.provider.as_deref(), Some("script"));
+    }
+
+    #[tokio::test]
+    async fn denylists_are_refused() {
+        let r = runner("true", &[], Duration::from_secs(1));
+        let mut a = agent(None);
+        a.denied_tools = vec![crate::mcp::types::McpToolRef::parse("github/delete_repo")];
+        let err = r
+            .exec_task(&a, "anth_writer", "t", "/tmp")
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("cannot enforce"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn read_only_agents_are_refused() {
+        let r = runner("true", &[], Duration::from_secs(1));
+        for run in [
+            AgentRun {
+                include_apply_patch_tool: Some(false),
+                ..Default::default()
+            },
+            AgentRun {
+                sandbox_mode: Some(codex_protocol::config_types::SandboxMode::ReadOnly),
+                ..Default::default()
+            },
+        ] {
+            let mut a = agent(None);
+            a.run = Some(run);
+            let err = r
+                .exec_task(&a, "anth_writer", "t", "/tmp")
+                .await
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains("may not edit"), "{err:#}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn timeout_is_reported() {
+        let r = runner("sh", &["-c", "sleep 5"], Duration::from_millis(100));
+        let out = r
+            .exec_task(&agent(None), "anth_writer", "t", "/tmp")
+            .await
+            .unwrap();
+        assert!(!out.ok);
+        assert!(out.stderr.contains("timed out"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn timeout_kills_grandchildren() {
+        let tmp = tempfile::tempdir().unwrap();
+        let marker = tmp.path().join("marker");
+        let script = format!("(sleep 1; touch {}) & wait", marker.display());
+        let r = runner("sh", &["-c", &script], Duration::from_millis(100));
+        let out = r
+            .exec_task(&agent(None), "anth_writer", "t", "/tmp")
+            .await
+            .unwrap();
+        assert_eq!(out.status, 124);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists(), "background child outlived the timeout");
+    }
+}
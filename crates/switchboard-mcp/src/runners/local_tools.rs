@@ -0,0 +1,380 @@
+//! Built-in tools for runners that execute the tool loop themselves.
+//!
+//! Every path is resolved against the call's `cwd` and rejected when it would
+//! leave it (`..`, absolute paths elsewhere, symlinks pointing outside).
+//! Patches use the Codex envelope:
+//!
+//! ```text
+//! *** Begin Patch
+//! *** Add File: notes.md
+//! +hello
+//! *** Update File: src/lib.rs
+//! @@ fn main
+//! -    old();
+//! +    new();
+//! *** Delete File: stale.txt
+//! *** End Patch
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Context;
+
+/// Largest file `read_file` returns, in bytes.
+const MAX_READ_BYTES: u64 = 256 * 1024;
+
+/// Drop `.` and resolve `..` lexically; `None` when `..` climbs past the root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::ParentDir => {
+                if !out.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            other => out.push(other),
+        }
+    }
+    Some(out)
+}
+
+/// Resolve `rel` inside `cwd`, refusing anything that escapes it.
+pub fn resolve_within(cwd: &Path, rel: &str) -> anyhow::Result<PathBuf> {
+    let cwd =
+        normalize(cwd).with_context(|| format!("invalid working directory {}", cwd.display()))?;
+    let p = Path::new(rel);
+    let joined = if p.is_absolute() {
+        p.to_path_buf()
+    } else {
+        cwd.join(p)
+    };
+    let Some(out) = normalize(&joined) else {
+        anyhow::bail!("path '{rel}' is outside the working directory");
+    };
+    if !out.starts_with(&cwd) {
+        anyhow::bail!("path '{rel}' is outside the working directory");
+    }
+    // Follow symlinks for the deepest existing ancestor.
+    if let Some(existing) = out.ancestors().find(|a| a.exists())
+        && let (Ok(real), Ok(root)) = (existing.canonicalize(), cwd.canonicalize())
+        && !real.starts_with(&root)
+    {
+        anyhow::bail!("path '{rel}' resolves outside the working directory");
+    }
+    Ok(out)
+}
+
+pub fn read_file(cwd: &Path, rel: &str) -> anyhow::Result<String> {
+    let path = resolve_within(cwd, rel)?;
+    let meta = std::fs::metadata(&path).with_context(|| format!("stat {rel}"))?;
+    if meta.len() > MAX_READ_BYTES {
+        anyhow::bail!("{rel} is {} bytes; limit is {MAX_READ_BYTES}", meta.len());
+    }
+    std::fs::read_to_string(&path).with_context(|| format!("read {rel}"))
+}
+
+/// Sorted directory listing; directories get a trailing `/`.
+pub fn list_dir(cwd: &Path, rel: &str) -> anyhow::Result<String> {
+    let path = resolve_within(cwd, rel)?;
+    let mut names: Vec<String> = std::fs::read_dir(&path)
+        .with_context(|| format!("list {rel}"))?
+        .flatten()
+        .map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            if e.path().is_dir() {
+                format!("{name}/")
+            } else {
+                name
+            }
+        })
+        .collect();
+    names.sort();
+    Ok(names.join("\n"))
+}
+
+enum FileOp {
+    Add {
+        path: String,
+        lines: Vec<String>,
+    },
+    Delete {
+        path: String,
+    },
+    Update {
+        path: String,
+        hunks: Vec<Vec<String>>,
+    },
+}
+
+fn parse_patch(patch: &str) -> anyhow::Result<Vec<FileOp>> {
+    let mut ops: Vec<FileOp> = Vec::new();
+    for line in patch.lines() {
+        let line = line.trim_end_matches('\r');
+        if line == "*** Begin Patch" || line == "*** End Patch" || line == "*** End of File" {
+            continue;
+        }
+        if let Some(p) = line.strip_prefix("*** Add File: ") {
+            ops.push(FileOp::Add {
+                path: p.trim().to_string(),
+                lines: Vec::new(),
+            });
+        } else if let Some(p) = line.strip_prefix("*** Delete File: ") {
+            ops.push(FileOp::Delete {
+                path: p.trim().to_string(),
+            });
+        } else if let Some(p) = line.strip_prefix("*** Update File: ") {
+            ops.push(FileOp::Update {
+                path: p.trim().to_string(),
+                hunks: Vec::new(),
+            });
+        } else {
+            match ops.last_mut() {
+                Some(FileOp::Add { lines, .. }) => {
+                    let Some(l) = line.strip_prefix('+') else {
+                        anyhow::bail!("added file lines must start with '+': {line:?}");
+                    };
+                    lines.push(l.to_string());
+                }
+                Some(FileOp::Update { hunks, .. }) => {
+                    if line.starts_with("@@") {
+                        hunks.push(Vec::new());
+                    } else if line.starts_with([' ', '+', '-']) || line.is_empty() {
+                        if hunks.is_empty() {
+                            hunks.push(Vec::new());
+                        }
+                        // An empty line is an empty context line.
+                        let l = if line.is_empty() { " " } else { line };
+                        if let Some(h) = hunks.last_mut() {
+                            h.push(l.to_string());
+                        }
+                    } else {
+                        anyhow::bail!("unexpected line in update hunk: {line:?}");
+                    }
+                }
+                _ if line.trim().is_empty() => {}
+                _ => anyhow::bail!("unexpected patch line: {line:?}"),
+            }
+        }
+    }
+    if ops.is_empty() {
+        anyhow::bail!("patch contains no file operations");
+    }
+    Ok(ops)
+}
+
+/// Apply one hunk to `lines`, searching from `cursor`; returns the new cursor.
+fn apply_hunk(lines: &mut Vec<String>, hunk: &[String], cursor: usize) -> anyhow::Result<usize> {
+    let old: Vec<&str> = hunk
+        .iter()
+        .filter(|l| !l.starts_with('+'))
+        .map(|l| &l[1..])
+        .collect();
+    let new: Vec<String> = hunk
+        .iter()
+        .filter(|l| !l.starts_with('-'))
+        .map(|l| l[1..].to_string())
+        .collect();
+    if old.is_empty() {
+        lines.extend(new);
+        return Ok(lines.len());
+    }
+    let fits = |start: usize, exact: bool| {
+        old.iter().enumerate().all(|(i, o)| {
+            let l = &lines[start + i];
+            if exact {
+                l == o
+            } else {
+                l.trim_end() == o.trim_end()
+            }
+        })
+    };
+    let last = lines.len().checked_sub(old.len());
+    let found = last.and_then(|last| {
+        (cursor..=last)
+            .find(|&s| fits(s, true))
+            .or_else(|| (cursor..=last).find(|&s| fits(s, false)))
+    });
+    let Some(start) = found else {
+        anyhow::bail!("hunk context not found:\n{}", old.join("\n"));
+    };
+    let end = start + new.len();
+    lines.splice(start..start + old.len(), new);
+    Ok(end)
+}
+
+/// Apply a patch inside `cwd`. All operations are checked before any file is
+/// written, and later sections see the result of earlier ones for the same
+/// path. Returns the touched paths as given in the patch.
+pub fn apply_patch(cwd: &Path, patch: &str) -> anyhow::Result<Vec<String>> {
+    let ops = parse_patch(patch)?;
+    // Final content per path (`None` = deleted), in first-touched order.
+    let mut pending: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut order: Vec<PathBuf> = Vec::new();
+    let mut files = Vec::new();
+    for op in &ops {
+        let (path, content) = match op {
+            FileOp::Add { path, lines } => {
+                let mut content = lines.join("\n");
+                content.push('\n');
+                (path, Some(content))
+            }
+            FileOp::Delete { path } => {
+                let abs = resolve_within(cwd, path)?;
+                let exists = match pending.get(&abs) {
+                    Some(state) => state.is_some(),
+                    None => abs.is_file(),
+                };
+                if !exists {
+                    anyhow::bail!("cannot delete {path}: no such file");
+                }
+                (path, None)
+            }
+            FileOp::Update { path, hunks } => {
+                let abs = resolve_within(cwd, path)?;
+                let original = match pending.get(&abs) {
+                    Some(Some(c)) => c.clone(),
+                    Some(None) => {
+                        anyhow::bail!("cannot update {path}: deleted earlier in the patch")
+                    }
+                    None => {
+                        std::fs::read_to_string(&abs).with_context(|| format!("read {path}"))?
+                    }
+                };
+                let mut lines: Vec<String> = original.lines().map(|l| l.to_string()).collect();
+                let mut cursor = 0;
+                for hunk in hunks {
+                    cursor = apply_hunk(&mut lines, hunk, cursor)
+                        .with_context(|| format!("update {path}"))?;
+                }
+                let mut content = lines.join("\n");
+                content.push('\n');
+                (path, Some(content))
+            }
+        };
+        let abs = resolve_within(cwd, path)?;
+        if !pending.contains_key(&abs) {
+            order.push(abs.clone());
+            files.push(path.clone());
+        }
+        pending.insert(abs, content);
+    }
+    for abs in order {
+        match pending.remove(&abs).flatten() {
+            Some(c) => {
+                if let Some(parent) = abs.parent() {
+                    std::fs::create_dir_all(parent)
+                        .with_context(|| format!("create {}", parent.display()))?;
+                }
+                std::fs::write(&abs, c).with_context(|| format!("write {}", abs.display()))?;
+            }
+            // A file added and deleted within the patch never reaches the disk
+            None if !abs.exists() => {}
+            None => {
+                std::fs::remove_file(&abs).with_context(|| format!("delete {}", abs.display()))?
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_outside_cwd_are_rejected() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cwd = tmp.path();
+        assert!(resolve_within(cwd, "src/lib.rs").is_ok());
+        assert!(resolve_within(cwd, "a/../b.txt").is_ok());
+        assert!(resolve_within(cwd, "../escape.txt").is_err());
+        assert!(resolve_within(cwd, "/etc/passwd").is_err());
+
+        // `cwd` itself is normalized before the containment check
+        std::fs::create_dir(cwd.join("sub")).unwrap();
+        let dotted = cwd.join(".").join("sub").join("..");
+        assert_eq!(resolve_within(&dotted, "x.txt").unwrap(), cwd.join("x.txt"));
+        assert!(resolve_within(&dotted, "../x.txt").is_err());
+        assert!(resolve_within(&cwd.join("sub"), "../../x.txt").is_err());
+    }
+
+    #[test]
+    fn patch_adds_updates_and_deletes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cwd = tmp.path();
+        std::fs::write(cwd.join("lib.rs"), "fn a() {}\nfn b() {\n    old();\n}\n").unwrap();
+        std::fs::write(cwd.join("stale.txt"), "x\n").unwrap();
+        let patch = "\
+*** Begin Patch
+*** Add File: docs/notes.md
++hello
+*** Update File: lib.rs
+@@ fn b
+ fn b() {
+-    old();
++    new();
+*** Delete File: stale.txt
+*** End Patch
+";
+        let files = apply_patch(cwd, patch).unwrap();
+        assert_eq!(files, vec!["docs/notes.md", "lib.rs", "stale.txt"]);
+        assert_eq!(
+            std::fs::read_to_string(cwd.join("docs/notes.md")).unwrap(),
+            "hello\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(cwd.join("lib.rs")).unwrap(),
+            "fn a() {}\nfn b() {\n    new();\n}\n"
+        );
+        assert!(!cwd.join("stale.txt").exists());
+    }
+
+    #[test]
+    fn sections_for_the_same_path_build_on_each_other() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cwd = tmp.path();
+        std::fs::write(cwd.join("a.txt"), "one\ntwo\n").unwrap();
+        let patch = "\
+*** Begin Patch
+*** Update File: a.txt
+-one
++ONE
+*** Update File: a.txt
+-two
++TWO
+*** Add File: b.txt
++first
+*** Update File: b.txt
+-first
++second
+*** Add File: tmp.txt
++scratch
+*** Delete File: tmp.txt
+*** End Patch
+";
+        let files = apply_patch(cwd, patch).unwrap();
+        assert_eq!(files, vec!["a.txt", "b.txt", "tmp.txt"]);
+        assert_eq!(
+            std::fs::read_to_string(cwd.join("a.txt")).unwrap(),
+            "ONE\nTWO\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(cwd.join("b.txt")).unwrap(),
+            "second\n"
+        );
+        assert!(!cwd.join("tmp.txt").exists());
+    }
+
+    #[test]
+    fn failed_hunk_writes_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cwd = tmp.path();
+        std::fs::write(cwd.join("a.txt"), "one\n").unwrap();
+        let patch = "*** Add File: b.txt\n+new\n*** Update File: a.txt\n-missing\n+x\n";
+        assert!(apply_patch(cwd, patch).is_err());
+        assert!(!cwd.join("b.txt").exists());
+    }
+}
@@ -0,0 +1,328 @@
+//! Scripted mock runner for offline tests.
+//!
+//! Responses come from TOML fixture files; each `[[responses]]` entry matches
+//! an agent (tool name or agent name) and a task regex and supplies the output,
+//! diff and trace events to return. The first matching entry wins, in file
+//! order (files sorted by name when a directory is given).
+//!
+//! ```toml
+//! [[responses]]
+//! agent = "agent_reviewer"   # optional; matches any agent when omitted
+//! task = "(?i)review"        # optional regex; matches any task when omitted
+//! output = "LGTM"
+//! diff = "--- a/x\n+++ b/x\n"
+//!
+//! [[responses.events]]
+//! type = "exec"
+//! command = ["cargo", "test"]
+//! exit_code = 0
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::codex_runner::{
+    CodexRunOutput, CodexRunner, CommandRecord, McpCallRecord, PatchRecord, RunTrace,
+    TokenUsageRecord,
+};
+use crate::model::PreparedAgent;
+
+#[derive(Debug, Deserialize)]
+struct FixtureFile {
+    #[serde(default)]
+    responses: Vec<ResponseDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseDef {
+    agent: Option<String>,
+    task: Option<String>,
+    #[serde(default = "default_true")]
+    ok: bool,
+    #[serde(default)]
+    output: String,
+    #[serde(default)]
+    stderr: String,
+    diff: Option<String>,
+    model: Option<String>,
+    #[serde(default)]
+    events: Vec<EventDef>,
+    usage: Option<UsageDef>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Scripted trace events, mirroring what the in-process runner records.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum EventDef {
+    Exec {
+        command: Vec<String>,
+        #[serde(default)]
+        exit_code: i32,
+    },
+    McpCall {
+        server: String,
+        tool: String,
+        #[serde(default = "default_true")]
+        ok: bool,
+    },
+    Patch {
+        files: Vec<String>,
+        #[serde(default = "default_true")]
+        success: bool,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct UsageDef {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+/// One compiled fixture entry.
+#[derive(Debug)]
+struct MockResponse {
+    agent: Option<String>,
+    task: Option<Regex>,
+    def: ResponseDef,
+    origin: PathBuf,
+}
+
+impl MockResponse {
+    fn matches(&self, prepared: &PreparedAgent, task: &str) -> bool {
+        let agent_ok = self
+            .agent
+            .as_ref()
+            .map(|a| a == &prepared.tool_name || a.eq_ignore_ascii_case(&prepared.name))
+            .unwrap_or(true);
+        let task_ok = self.task.as_ref().map(|r| r.is_match(task)).unwrap_or(true);
+        agent_ok && task_ok
+    }
+}
+
+/// Runner that answers from fixtures instead of calling a model.
+pub struct MockRunner {
+    responses: Vec<MockResponse>,
+}
+
+impl MockRunner {
+    /// Load fixtures from files or directories (`*.toml`, non-recursive).
+    pub fn from_paths(paths: &[PathBuf]) -> anyhow::Result<Self> {
+        let mut files = Vec::new();
+        for p in paths {
+            if p.is_dir() {
+                let mut found: Vec<PathBuf> = std::fs::read_dir(p)
+                    .with_context(|| format!("read fixture dir {}", p.display()))?
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|f| f.extension().and_then(|e| e.to_str()) == Some("toml"))
+                    .collect();
+                found.sort();
+                files.extend(found);
+            } else {
+                files.push(p.clone());
+            }
+        }
+        let mut responses = Vec::new();
+        for f in &files {
+            let content = std::fs::read_to_string(f)
+                .with_context(|| format!("read fixture {}", f.display()))?;
+            responses.extend(Self::parse(&content, f)?);
+        }
+        tracing::info!(
+            "mock runner: loaded {} response(s) from {} file(s)",
+            responses.len(),
+            files.len()
+        );
+        Ok(Self { responses })
+    }
+
+    fn parse(content: &str, origin: &Path) -> anyhow::Result<Vec<MockResponse>> {
+        let file: FixtureFile =
+            toml::from_str(content).with_context(|| format!("parse {}", origin.display()))?;
+        file.responses
+            .into_iter()
+            .map(|def| {
+                let task = def
+                    .task
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .with_context(|| format!("invalid task pattern in {}", origin.display()))?;
+                Ok(MockResponse {
+                    agent: def.agent.clone(),
+                    task,
+                    def,
+                    origin: origin.to_path_buf(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl CodexRunner for MockRunner {
+    async fn exec_task(
+        &self,
+        prepared: &PreparedAgent,
+        tool: &str,
+        task: &str,
+        cwd: &str,
+    ) -> anyhow::Result<CodexRunOutput> {
+        let Some(resp) = self.responses.iter().find(|r| r.matches(prepared, task)) else {
+            anyhow::bail!("mock runner: no scripted response for agent '{tool}' and task '{task}'");
+        };
+        tracing::info!(
+            "mock runner: tool={}, fixture={}",
+            tool,
+            resp.origin.display()
+        );
+        let def = &resp.def;
+        let mut trace = RunTrace {
+            model: def
+                .model
+                .clone()
+                .or_else(|| prepared.run.as_ref().and_then(|r| r.model.clone())),
+            provider: Some("mock".to_string()),
+            diff: def.diff.clone(),
+            usage: def.usage.as_ref().map(|u| TokenUsageRecord {
+                input_tokens: u.input_tokens,
+                output_tokens: u.output_tokens,
+                total_tokens: u.input_tokens + u.output_tokens,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        for (i, ev) in def.events.iter().enumerate() {
+            let call_id = format!("mock-{i}");
+            match ev.clone() {
+                EventDef::Exec { command, exit_code } => trace.commands.push(CommandRecord {
+                    call_id,
+                    command,
+                    cwd: cwd.to_string(),
+                    exit_code: Some(exit_code),
+                    duration_ms: Some(0),
+                }),
+                EventDef::McpCall { server, tool, ok } => trace.mcp_calls.push(McpCallRecord {
+                    server,
+                    tool,
+                    ok,
+                    duration_ms: 0,
+                }),
+                EventDef::Patch { files, success } => trace.patches.push(PatchRecord {
+                    call_id,
+                    files,
+                    auto_approved: true,
+                    success: Some(success),
+                }),
+            }
+        }
+        Ok(CodexRunOutput {
+            ok: def.ok,
+            status: if def.ok { 0 } else { 1 },
+            stdout: def.output.clone(),
+            stderr: def.stderr.clone(),
+            trace,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"
+[[responses]]
+agent = "agent_reviewer"
+task = "(?i)review"
+output = "LGTM"
+diff = "--- a/x\n+++ b/x\n"
+usage = { input_tokens = 10, output_tokens = 5 }
+
+[[responses.events]]
+type = "exec"
+command = ["cargo", "test"]
+
+[[responses.events]]
+type = "patch"
+files = ["src/lib.rs"]
+
+[[responses]]
+ok = false
+output = "fallback"
+"#;
+
+    fn agent(tool: &str) -> PreparedAgent {
+        PreparedAgent {
+            tool_name: tool.to_string(),
+            name: "Reviewer".to_string(),
+            description: "d".to_string(),
+            source_path: "/tmp/reviewer.toml".into(),
+            ..Default::default()
+        }
+    }
+
+    fn runner() -> MockRunner {
+        let responses = MockRunner::parse(FIXTURE, Path::new("fixture.toml")).unwrap();
+        MockRunner { responses }
+    }
+
+    #[tokio::test]
+    async fn first_matching_response_wins() {
+        let out = runner()
+            .exec_task(
+                &agent("agent_reviewer"),
+                "agent_reviewer",
+                "Please REVIEW",
+                "/tmp",
+            )
+            .await
+            .unwrap();
+        assert!(out.ok);
+        assert_eq!(out.stdout, "LGTM");
+        assert_eq!(out.trace.commands.len(), 1);
+        assert_eq!(out.trace.commands[0].cwd, "/tmp");
+        assert_eq!(out.trace.patches[0].files, vec!["src/lib.rs".to_string()]);
+        assert_eq!(out.trace.usage.as_ref().unwrap().total_tokens, 15);
+        assert!(out.trace.diff.is_some());
+    }
+
+    #[tokio::test]
+    async fn unmatched_task_falls_through() {
+        let out = runner()
+            .exec_task(
+                &agent("agent_reviewer"),
+                "agent_reviewer",
+                "write docs",
+                "/tmp",
+            )
+            .await
+            .unwrap();
+        assert!(!out.ok);
+        assert_eq!(out.stdout, "fallback");
+    }
+
+    #[tokio::test]
+    async fn no_match_is_an_error() {
+        let responses = MockRunner::parse(
+            "[[responses]]\nagent = \"other\"\noutput = \"x\"\n",
+            Path::new("f.toml"),
+        )
+        .unwrap();
+        let err = MockRunner { responses }
+            .exec_task(&agent("agent_reviewer"), "agent_reviewer", "t", "/tmp")
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("no scripted response"));
+    }
+}
@@ -0,0 +1,17 @@
+//! Alternative `CodexRunner` backends selected by configuration.
+//!
+//! The default in-process runner lives in `codex_runner`; this module holds
+//! the others: scripted mock for offline tests, the `codex` CLI subprocess,
+//! generic commands for non-Codex agent CLIs and MCP sampling through the
+//! connected client.
+
+pub mod command;
+mod local_tools;
+pub mod mock;
+pub mod sampling;
+pub mod subprocess;
+
+pub use command::*;
+pub use mock::*;
+pub use sampling::*;
+pub use subprocess::*;
@@ -0,0 +1,489 @@
+//! MCP sampling runner: run agents on the connected client's own model.
+//!
+//! Instead of calling a model provider, each turn is sent to the client as a
+//! `sampling/createMessage` request: the agent instructions (plus a short tool
+//! protocol) as the system prompt and the task as the first user message. The
+//! model can ask for a built-in tool by replying with a fenced `tool` block:
+//!
+//! ````text
+//! ```tool
+//! {"tool": "read_file", "path": "src/lib.rs"}
+//! ```
+//! ````
+//!
+//! Tools (`read_file`, `list_dir`, `apply_patch`) run locally within `cwd` and
+//! their results are sent back as the next user message. A reply without a
+//! tool block is the final answer. `apply_patch` is neither offered nor run
+//! when the agent turns the patch tool off (e.g. `disallowedTools: Edit`) or
+//! runs in a read-only sandbox.
+
+use std::path::Path;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use codex_protocol::config_types::SandboxMode;
+use rust_mcp_sdk::McpServer;
+use rust_mcp_sdk::schema::{CreateMessageRequestParams, CreateMessageResult};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::local_tools;
+use crate::codex_runner::{CodexRunOutput, CodexRunner, McpCallRecord, PatchRecord, RunTrace};
+use crate::model::PreparedAgent;
+
+/// Appended to the agent instructions so the model knows how to use tools.
+const TOOL_PROTOCOL: &str = "\
+You can use these tools inside the working directory. To call one, reply with \
+only a fenced block tagged `tool` holding a JSON object, then wait for the result:
+
+```tool
+{\"tool\": \"read_file\", \"path\": \"relative/path\"}
+```
+
+- read_file {\"path\"}: return a file's contents.
+- list_dir {\"path\"} (default \".\"): list a directory.";
+
+/// Protocol line for the patch tool, offered only to agents that may edit.
+const PATCH_TOOL: &str = "
+- apply_patch {\"patch\"}: apply a patch in the `*** Begin Patch` / `*** End Patch` \
+format (`*** Add File:`, `*** Update File:` with `@@` hunks, `*** Delete File:`).";
+
+const PROTOCOL_END: &str = "\n\nWhen you are done, reply with your final answer and no tool block.";
+
+/// Whether the agent may edit files: the patch tool is not switched off and
+/// the sandbox is not read-only.
+pub(crate) fn can_edit(prepared: &PreparedAgent) -> bool {
+    prepared.run.as_ref().is_none_or(|r| {
+        r.include_apply_patch_tool != Some(false)
+            && !matches!(r.sandbox_mode, Some(SandboxMode::ReadOnly))
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingRole {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone)]
+pub struct SamplingMessage {
+    pub role: SamplingRole,
+    pub text: String,
+}
+
+/// One `createMessage` request, independent of the SDK schema types.
+#[derive(Debug, Clone)]
+pub struct SamplingRequest {
+    pub system_prompt: String,
+    pub messages: Vec<SamplingMessage>,
+    pub max_tokens: u64,
+    /// Agent's configured model, passed as a preference hint.
+    pub model_hint: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SamplingReply {
+    pub text: String,
+    pub model: Option<String>,
+}
+
+/// Something that can sample a model on the runner's behalf.
+#[async_trait]
+pub trait SamplingClient: Send + Sync {
+    async fn create_message(&self, request: SamplingRequest) -> anyhow::Result<SamplingReply>;
+}
+
+/// Samples through the MCP client connected to this server.
+pub struct McpSamplingClient<'a> {
+    runtime: &'a dyn McpServer,
+}
+
+impl<'a> McpSamplingClient<'a> {
+    pub fn new(runtime: &'a dyn McpServer) -> Self {
+        Self { runtime }
+    }
+}
+
+#[async_trait]
+impl SamplingClient for McpSamplingClient<'_> {
+    async fn create_message(&self, request: SamplingRequest) -> anyhow::Result<SamplingReply> {
+        if self.runtime.client_supports_sampling() == Some(false) {
+            anyhow::bail!("the connected MCP client does not support sampling");
+        }
+        let messages: Vec<serde_json::Value> = request
+            .messages
+            .iter()
+            .map(|m| {
+                let role = match m.role {
+                    SamplingRole::User => "user",
+                    SamplingRole::Assistant => "assistant",
+                };
+                json!({ "role": role, "content": { "type": "text", "text": m.text } })
+            })
+            .collect();
+        let mut params = json!({
+            "messages": messages,
+            "systemPrompt": request.system_prompt,
+            "maxTokens": request.max_tokens,
+            "includeContext": "none",
+        });
+        if let Some(model) = request.model_hint {
+            params["modelPreferences"] = json!({ "hints": [{ "name": model }] });
+        }
+        let params: CreateMessageRequestParams = serde_json::from_value(params)?;
+        let result: CreateMessageResult = self
+            .runtime
+            .create_message(params)
+            .await
+            .map_err(|e| anyhow::anyhow!("sampling request failed: {e}"))?;
+        let value = serde_json::to_value(&result)?;
+        let text = match &value["content"] {
+            serde_json::Value::Array(parts) => parts
+                .iter()
+                .filter_map(|p| p["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            part => part["text"].as_str().unwrap_or_default().to_string(),
+        };
+        Ok(SamplingReply {
+            text,
+            model: value["model"].as_str().map(|s| s.to_string()),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "tool", rename_all = "snake_case")]
+enum ToolCall {
+    ReadFile { path: String },
+    ListDir { path: Option<String> },
+    ApplyPatch { patch: String },
+}
+
+impl ToolCall {
+    fn name(&self) -> &'static str {
+        match self {
+            ToolCall::ReadFile { .. } => "read_file",
+            ToolCall::ListDir { .. } => "list_dir",
+            ToolCall::ApplyPatch { .. } => "apply_patch",
+        }
+    }
+}
+
+/// Extract the first ```` ```tool ```` block; `None` means a final answer.
+fn parse_tool_call(reply: &str) -> Option<Result<ToolCall, String>> {
+    let start = reply.find("```tool")?;
+    let body = &reply[start + "```tool".len()..];
+    let body = body.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+    let body = body.split("```").next().unwrap_or("");
+    Some(serde_json::from_str(body.trim()).map_err(|e| format!("invalid tool call: {e}")))
+}
+
+/// Runner that drives the agent loop through MCP sampling.
+pub struct SamplingRunner {
+    max_turns: usize,
+    max_tokens: u64,
+}
+
+impl SamplingRunner {
+    pub fn new(max_turns: usize, max_tokens: u64) -> Self {
+        Self {
+            max_turns,
+            max_tokens,
+        }
+    }
+
+    /// Execute one tool call and record it in the trace.
+    fn run_tool(
+        &self,
+        call: &ToolCall,
+        cwd: &Path,
+        can_edit: bool,
+        turn: usize,
+        trace: &mut RunTrace,
+    ) -> String {
+        let started = Instant::now();
+        let result = match call {
+            ToolCall::ApplyPatch { .. } if !can_edit => {
+                tracing::warn!(
+                    "sampling runner: refused apply_patch for an agent that may not edit"
+                );
+                Err(anyhow::anyhow!(
+                    "apply_patch is not available to this agent"
+                ))
+            }
+            ToolCall::ReadFile { path } => local_tools::read_file(cwd, path),
+            ToolCall::ListDir { path } => {
+                local_tools::list_dir(cwd, path.as_deref().unwrap_or("."))
+            }
+            ToolCall::ApplyPatch { patch } => {
+                let applied = local_tools::apply_patch(cwd, patch);
+                trace.patches.push(PatchRecord {
+                    call_id: format!("sampling-{turn}"),
+                    files: applied.as_ref().cloned().unwrap_or_default(),
+                    auto_approved: true,
+                    success: Some(applied.is_ok()),
+                });
+                applied.map(|files| format!("applied patch to: {}", files.join(", ")))
+            }
+        };
+        if !matches!(call, ToolCall::ApplyPatch { .. }) || !can_edit {
+            trace.mcp_calls.push(McpCallRecord {
+                server: "builtin".to_string(),
+                tool: call.name().to_string(),
+                ok: result.is_ok(),
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+        }
+        match result {
+            Ok(out) => out,
+            Err(e) => format!("error: {e:#}"),
+        }
+    }
+}
+
+#[async_trait]
+impl CodexRunner for SamplingRunner {
+    async fn exec_task(
+        &self,
+        _prepared: &PreparedAgent,
+        tool: &str,
+        _task: &str,
+        _cwd: &str,
+    ) -> anyhow::Result<CodexRunOutput> {
+        anyhow::bail!("agent '{tool}' uses the sampling runner, which needs a connected MCP client")
+    }
+
+    async fn exec_task_with_client(
+        &self,
+        prepared: &PreparedAgent,
+        tool: &str,
+        task: &str,
+        cwd: &str,
+        client: Option<&dyn SamplingClient>,
+    ) -> anyhow::Result<CodexRunOutput> {
+        let Some(client) = client else {
+            return self.exec_task(prepared, tool, task, cwd).await;
+        };
+        let cwd_path = Path::new(cwd);
+        if !cwd_path.is_absolute() {
+            anyhow::bail!("cwd must be an absolute path");
+        }
+        let can_edit = can_edit(prepared);
+        let protocol = if can_edit {
+            format!("{TOOL_PROTOCOL}{PATCH_TOOL}{PROTOCOL_END}")
+        } else {
+            format!("{TOOL_PROTOCOL}{PROTOCOL_END}")
+        };
+        let system_prompt = match prepared.instructions.as_deref().map(str::trim) {
+            Some(i) if !i.is_empty() => format!("{i}\n\n{protocol}"),
+            _ => protocol,
+        };
+        let model_hint = prepared.run.as_ref().and_then(|r| r.model.clone());
+        let mut trace = RunTrace {
+            provider: Some("sampling".to_string()),
+            model: model_hint.clone(),
+            ..Default::default()
+        };
+        let mut messages = vec![SamplingMessage {
+            role: SamplingRole::User,
+            text: format!("Working directory: {cwd}\n\n{task}"),
+        }];
+        tracing::info!("sampling runner: tool={}, agent={}", tool, prepared.name);
+
+        for turn in 0..self.max_turns {
+            let reply = client
+                .create_message(SamplingRequest {
+                    system_prompt: system_prompt.clone(),
+                    messages: messages.clone(),
+                    max_tokens: self.max_tokens,
+                    model_hint: model_hint.clone(),
+                })
+                .await?;
+            if reply.model.is_some() {
+                trace.model = reply.model.clone();
+            }
+            let result = match parse_tool_call(&reply.text) {
+                None => {
+                    return Ok(CodexRunOutput {
+                        ok: true,
+                        status: 0,
+                        stdout: reply.text,
+                        stderr: String::new(),
+                        trace,
+                    });
+                }
+                Some(Err(e)) => e,
+                Some(Ok(call)) => {
+                    tracing::debug!("sampling runner: turn {} calls {}", turn, call.name());
+                    format!(
+                        "[{} result]\n{}",
+                        call.name(),
+                        self.run_tool(&call, cwd_path, can_edit, turn, &mut trace)
+                    )
+                }
+            };
+            messages.push(SamplingMessage {
+                role: SamplingRole::Assistant,
+                text: reply.text,
+            });
+            messages.push(SamplingMessage {
+                role: SamplingRole::User,
+                text: result,
+            });
+        }
+        tracing::warn!(
+            "sampling runner: {} stopped after {} turns",
+            tool,
+            self.max_turns
+        );
+        Ok(CodexRunOutput {
+            ok: false,
+            status: 1,
+            stdout: format!(
+                "[run stopped: no final answer after {} turns]",
+                self.max_turns
+            ),
+            stderr: String::new(),
+            trace,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::AgentRun;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Mock client answering from a script and recording every request.
+    struct ScriptedClient {
+        replies: Mutex<VecDeque<String>>,
+        requests: Mutex<Vec<SamplingRequest>>,
+    }
+
+    impl ScriptedClient {
+        fn new(replies: &[&str]) -> Self {
+            Self {
+                replies: Mutex::new(replies.iter().map(|s| s.to_string()).collect()),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl SamplingClient for ScriptedClient {
+        async fn create_message(&self, request: SamplingRequest) -> anyhow::Result<SamplingReply> {
+            self.requests.lock().unwrap().push(request);
+            let text = self
+                .replies
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_else(|| "```tool\n{\"tool\": \"list_dir\"}\n```".to_string());
+            Ok(SamplingReply {
+                text,
+                model: Some("client-model".to_string()),
+            })
+        }
+    }
+
+    fn agent() -> PreparedAgent {
+        PreparedAgent {
+            tool_name: "agent_fixer".to_string(),
+            name: "Fixer".to_string(),
+            description: "d".to_string(),
+            source_path: "/tmp/fixer.toml".into(),
+            instructions: Some("Fix typos.".to_string()),
+            runner: Some("sampling".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_loop_reads_and_patches_within_cwd() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("README.md"), "helo\n").unwrap();
+        let client = ScriptedClient::new(&[
+            "Let me look.\n```tool\n{\"tool\": \"read_file\", \"path\": \"README.md\"}\n```",
+            "```tool\n{\"tool\": \"read_file\", \"path\": \"../secret\"}\n```",
+            "```tool\n{\"tool\": \"apply_patch\", \"patch\": \"*** Begin Patch\\n*** Update File: README.md\\n-helo\\n+hello\\n*** End Patch\"}\n```",
+            "Fixed the typo.",
+        ]);
+        let cwd = tmp.path().to_str().unwrap();
+        let out = SamplingRunner::new(8, 1024)
+            .exec_task_with_client(&agent(), "agent_fixer", "fix README", cwd, Some(&client))
+            .await
+            .unwrap();
+        assert!(out.ok);
+        assert_eq!(out.stdout, "Fixed the typo.");
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join("README.md")).unwrap(),
+            "hello\n"
+        );
+        assert_eq!(out.trace.model.as_deref(), Some("client-model"));
+        assert_eq!(out.trace.patches[0].files, vec!["README.md".to_string()]);
+        assert!(out.trace.mcp_calls[0].ok);
+        assert!(!out.trace.mcp_calls[1].ok);
+
+        let requests = client.requests.lock().unwrap();
+        assert!(requests[0].system_prompt.starts_with("Fix typos."));
+        assert!(requests[1].messages[2].text.contains("helo"));
+        assert!(
+            requests[2].messages[4]
+                .text
+                .contains("outside the working directory")
+        );
+    }
+
+    #[tokio::test]
+    async fn denied_edit_leaves_files_unchanged() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("README.md"), "helo\n").unwrap();
+        let client = ScriptedClient::new(&[
+            "```tool\n{\"tool\": \"apply_patch\", \"patch\": \"*** Begin Patch\\n*** Update File: README.md\\n-helo\\n+hello\\n*** End Patch\"}\n```",
+            "I cannot edit files.",
+        ]);
+        let mut a = agent();
+        // What `disallowedTools: Edit` maps to
+        a.run = Some(AgentRun {
+            include_apply_patch_tool: Some(false),
+            ..Default::default()
+        });
+        let cwd = tmp.path().to_str().unwrap();
+        let out = SamplingRunner::new(8, 1024)
+            .exec_task_with_client(&a, "agent_fixer", "fix README", cwd, Some(&client))
+            .await
+            .unwrap();
+        assert!(out.ok);
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join("README.md")).unwrap(),
+            "helo\n"
+        );
+        assert!(out.trace.patches.is_empty());
+        assert!(!out.trace.mcp_calls[0].ok);
+
+        let requests = client.requests.lock().unwrap();
+        assert!(!requests[0].system_prompt.contains("apply_patch"));
+        assert!(requests[1].messages[2].text.contains("not available"));
+    }
+
+    #[tokio::test]
+    async fn turn_limit_and_missing_client_fail() {
+        let client = ScriptedClient::new(&[]);
+        let out = SamplingRunner::new(2, 256)
+            .exec_task_with_client(&agent(), "agent_fixer", "t", "/tmp", Some(&client))
+            .await
+            .unwrap();
+        assert!(!out.ok);
+        assert_eq!(client.requests.lock().unwrap().len(), 2);
+
+        let err = SamplingRunner::new(2, 256)
+            .exec_task_with_client(&agent(), "agent_fixer", "t", "/tmp", None)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("connected MCP client"));
+    }
+}
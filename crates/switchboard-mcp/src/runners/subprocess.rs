@@ -0,0 +1,627 @@
+//! Runner that shells out to the `codex` CLI.
+//!
+//! Launches `codex exec --json` in the requested cwd, passing the agent's run
+//! settings, instructions and MCP servers as `-c key=value` overrides (server
+//! environments go through private temp files, not argv), and
+//! folds the JSONL event stream into the same `CodexRunOutput` the in-process
+//! runner produces. This lets agents use a newer Codex than the embedded one.
+
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use codex_protocol::config_types::SandboxMode;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+use crate::codex_runner::{
+    CodexRunOutput, CodexRunner, CommandRecord, McpCallRecord, PatchRecord, RunTrace,
+    TokenUsageRecord,
+};
+use crate::mcp::types::McpToolRef;
+use crate::model::PreparedAgent;
+
+/// `codex exec --json` backend.
+pub struct SubprocessCodexRunner {
+    bin: PathBuf,
+}
+
+impl SubprocessCodexRunner {
+    pub fn new(bin: PathBuf) -> Self {
+        Self { bin }
+    }
+}
+
+/// Render a value as a TOML literal for `-c key=value`.
+fn toml_literal<T: Serialize + ?Sized>(v: &T) -> Option<String> {
+    toml::Value::try_from(v).ok().map(|v| v.to_string())
+}
+
+/// Build `-c` overrides for the agent's run settings, MCP servers and model
+/// providers.
+///
+/// `codex exec` has no config keys for the plan, patch and image tools, so a
+/// denied patch tool becomes a read-only sandbox. Turning the other two off
+/// grants nothing, so those runs go ahead with a warning. MCP server environments (often secrets) are written to
+/// owner-only temp files kept in `env_files` for the run and read by the
+/// `mcp-filter` launcher.
+fn config_overrides(
+    prepared: &PreparedAgent,
+    instructions_file: Option<&std::path::Path>,
+    env_files: &mut Vec<tempfile::NamedTempFile>,
+) -> anyhow::Result<Vec<String>> {
+    if let Some(run) = &prepared.run {
+        for (enabled, name) in [
+            (run.include_plan_tool, "plan"),
+            (run.include_view_image_tool, "view_image"),
+        ] {
+            if enabled == Some(false) {
+                tracing::warn!(
+                    "agent '{}' turns off the {} tool, which `codex exec` cannot do; running with it available",
+                    prepared.name,
+                    name
+                );
+            }
+        }
+    }
+    let mut out = Vec::new();
+    let mut push = |key: &str, lit: Option<String>| {
+        if let Some(lit) = lit {
+            out.push(format!("{key}={lit}"));
+        }
+    };
+    if let Some(run) = &prepared.run {
+        push("model", run.model.as_ref().and_then(toml_literal));
+        push(
+            "model_provider",
+            run.model_provider.as_ref().and_then(toml_literal),
+        );
+        push(
+            "approval_policy",
+            run.approval_policy.as_ref().and_then(toml_literal),
+        );
+        push(
+            "disable_response_storage",
+            run.disable_response_storage.as_ref().and_then(toml_literal),
+        );
+        push(
+            "model_reasoning_effort",
+            run.model_reasoning_effort.as_ref().and_then(toml_literal),
+        );
+        push(
+            "model_reasoning_summary",
+            run.model_reasoning_summary.as_ref().and_then(toml_literal),
+        );
+        push(
+            "model_verbosity",
+            run.model_verbosity.as_ref().and_then(toml_literal),
+        );
+        push(
+            "chatgpt_base_url",
+            run.chatgpt_base_url.as_ref().and_then(toml_literal),
+        );
+        let sandbox = match run.include_apply_patch_tool {
+            Some(false) => Some(SandboxMode::ReadOnly),
+            _ => run.sandbox_mode,
+        };
+        push("sandbox_mode", sandbox.as_ref().and_then(toml_literal));
+        push(
+            "tools.web_search",
+            run.tools_web_search_request.as_ref().and_then(toml_literal),
+        );
+    }
+    if let Some(path) = instructions_file {
+        push(
+            "experimental_instructions_file",
+            toml_literal(&path.display().to_string()),
+        );
+    }
+    let mut keys: Vec<&String> = prepared.mcp_servers.keys().collect();
+    keys.sort();
+    for key in keys {
+        let mut srv = prepared.mcp_servers[key].clone();
+        if let Some(env) = srv.env.as_ref().filter(|e| !e.is_empty()) {
+            // tempfile creates the file readable by the owner only
+            let mut f = tempfile::Builder::new()
+                .prefix("switchboard-mcp-env-")
+                .suffix(".json")
+                .tempfile()
+                .context("create MCP env file")?;
+            std::io::Write::write_all(&mut f, serde_json::to_string(env)?.as_bytes())
+                .context("write MCP env file")?;
+            srv = crate::mcp::filter::with_env_file(srv, f.path())?;
+            env_files.push(f);
+        }
+        push(
+            &format!("mcp_servers.{key}.command"),
+            toml_literal(&srv.command),
+        );
+        if !srv.args.is_empty() {
+            push(&format!("mcp_servers.{key}.args"), toml_literal(&srv.args));
+        }
+    }
+    let mut ids: Vec<&String> = prepared.model_providers.keys().collect();
+    ids.sort();
+    for id in ids {
+        push(
+            &format!("model_providers.{id}"),
+            toml_literal(&prepared.model_providers[id]),
+        );
+    }
+    Ok(out)
+}
+
+/// Output accumulated from the JSONL event stream.
+#[derive(Default)]
+struct StreamState {
+    trace: RunTrace,
+    stdout: String,
+    stderr: String,
+    /// The agent's denylist; a matching `mcp_tool_call_begin` sets `trace.denied_tool`.
+    denied_tools: Vec<McpToolRef>,
+    /// Whether a `task_complete` event was seen.
+    completed: bool,
+}
+
+fn str_field(v: &JsonValue, key: &str) -> String {
+    v.get(key)
+        .and_then(|x| x.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn u64_field(v: &JsonValue, key: &str) -> u64 {
+    v.get(key).and_then(|x| x.as_u64()).unwrap_or(0)
+}
+
+/// Durations are serialized as `{ secs, nanos }`.
+fn duration_ms(v: &JsonValue) -> Option<u64> {
+    let d = v.get("duration")?;
+    let secs = d.get("secs")?.as_u64()?;
+    let nanos = d.get("nanos").and_then(|n| n.as_u64()).unwrap_or(0);
+    Some(secs * 1000 + nanos / 1_000_000)
+}
+
+impl StreamState {
+    /// Apply one JSONL line. Lines without a `msg.type` (config summary,
+    /// prompt echo) are ignored; non-JSON lines are kept as stderr.
+    fn apply_line(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        let Ok(v) = serde_json::from_str::<JsonValue>(line) else {
+            self.stderr.push_str(line);
+            self.stderr.push('\n');
+            return;
+        };
+        let Some(msg) = v.get("msg") else {
+            return;
+        };
+        let kind = msg.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let trace = &mut self.trace;
+        match kind {
+            "session_configured" => {
+                trace.session_id = msg
+                    .get("session_id")
+                    .and_then(|s| s.as_str())
+                    .map(|s| s.to_string());
+                trace.model = msg
+                    .get("model")
+                    .and_then(|s| s.as_str())
+                    .map(|s| s.to_string());
+            }
+            "task_complete" => {
+                self.completed = true;
+                if let Some(m) = msg.get("last_agent_message").and_then(|m| m.as_str()) {
+                    self.stdout.push_str(m);
+                    self.stdout.push('\n');
+                }
+            }
+            "token_count" => {
+                let usage = trace.usage.get_or_insert_with(TokenUsageRecord::default);
+                usage.input_tokens += u64_field(msg, "input_tokens");
+                usage.cached_input_tokens += u64_field(msg, "cached_input_tokens");
+                usage.output_tokens += u64_field(msg, "output_tokens");
+                usage.reasoning_output_tokens += u64_field(msg, "reasoning_output_tokens");
+                usage.total_tokens += u64_field(msg, "total_tokens");
+            }
+            "exec_command_begin" => trace.commands.push(CommandRecord {
+                call_id: str_field(msg, "call_id"),
+                command: msg
+                    .get("command")
+                    .and_then(|c| c.as_array())
+                    .map(|a| {
+                        a.iter()
+                            .filter_map(|s| s.as_str().map(|s| s.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                cwd: str_field(msg, "cwd"),
+                exit_code: None,
+                duration_ms: None,
+            }),
+            "exec_command_end" => {
+                let call_id = str_field(msg, "call_id");
+                if let Some(rec) = trace
+                    .commands
+                    .iter_mut()
+                    .rev()
+                    .find(|c| c.call_id == call_id)
+                {
+                    rec.exit_code = msg
+                        .get("exit_code")
+                        .and_then(|c| c.as_i64())
+                        .map(|c| c as i32);
+                    rec.duration_ms = duration_ms(msg);
+                }
+            }
+            "mcp_tool_call_begin" => {
+                let inv = msg.get("invocation").cloned().unwrap_or_default();
+                let (server, tool) = (str_field(&inv, "server"), str_field(&inv, "tool"));
+                if trace.denied_tool.is_none()
+                    && self.denied_tools.iter().any(|d| d.matches(&server, &tool))
+                {
+                    trace.denied_tool = Some(format!("{server}/{tool}"));
+                }
+            }
+            "mcp_tool_call_end" => {
+                let inv = msg.get("invocation").cloned().unwrap_or_default();
+                let ok = msg
+                    .get("result")
+                    .map(|r| r.get("Ok").is_some())
+                    .unwrap_or(false);
+                trace.mcp_calls.push(McpCallRecord {
+                    server: str_field(&inv, "server"),
+                    tool: str_field(&inv, "tool"),
+                    ok,
+                    duration_ms: duration_ms(msg).unwrap_or(0),
+                });
+            }
+            "patch_apply_begin" => {
+                let mut files: Vec<String> = msg
+                    .get("changes")
+                    .and_then(|c| c.as_object())
+                    .map(|m| m.keys().cloned().collect())
+                    .unwrap_or_default();
+                files.sort();
+                trace.patches.push(PatchRecord {
+                    call_id: str_field(msg, "call_id"),
+                    files,
+                    auto_approved: msg
+                        .get("auto_approved")
+                        .and_then(|b| b.as_bool())
+                        .unwrap_or(false),
+                    success: None,
+                });
+            }
+            "patch_apply_end" => {
+                let call_id = str_field(msg, "call_id");
+                if let Some(rec) = trace
+                    .patches
+                    .iter_mut()
+                    .rev()
+                    .find(|p| p.call_id == call_id)
+                {
+                    rec.success = msg.get("success").and_then(|b| b.as_bool());
+                }
+            }
+            "turn_diff" => {
+                trace.diff = msg
+                    .get("unified_diff")
+                    .and_then(|d| d.as_str())
+                    .map(|s| s.to_string());
+            }
+            "error" | "stream_error" | "background_event" => {
+                let prefix = if kind == "background_event" {
+                    String::new()
+                } else {
+                    format!("{kind}: ")
+                };
+                self.stderr
+                    .push_str(&format!("{prefix}{}\n", str_field(msg, "message")));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[async_trait]
+impl CodexRunner for SubprocessCodexRunner {
+    async fn exec_task(
+        &self,
+        prepared: &PreparedAgent,
+        tool: &str,
+        task: &str,
+        cwd: &str,
+    ) -> anyhow::Result<CodexRunOutput> {
+        let cwd_path = std::path::Path::new(cwd);
+        if !cwd_path.is_absolute() {
+            anyhow::bail!("cwd must be an absolute path");
+        }
+
+        // Base instructions go through a temp file kept alive for the run.
+        let instructions_file = match prepared.instructions.as_ref() {
+            Some(s) if !s.trim().is_empty() => {
+                let mut f = tempfile::Builder::new()
+                    .prefix("switchboard-instructions-")
+                    .suffix(".md")
+                    .tempfile()
+                    .context("create instructions file")?;
+                std::io::Write::write_all(&mut f, s.as_bytes())
+                    .context("write instructions file")?;
+                Some(f)
+            }
+            _ => None,
+        };
+
+        let mut cmd = tokio::process::Command::new(&self.bin);
+        cmd.arg("exec")
+            .arg("--json")
+            .arg("--skip-git-repo-check")
+            .arg("-C")
+            .arg(cwd);
+        let mut env_files = Vec::new();
+        let overrides = config_overrides(
+            prepared,
+            instructions_file.as_ref().map(|f| f.path()),
+            &mut env_files,
+        )?;
+        for ov in overrides {
+            cmd.arg("-c").arg(ov);
+        }
+        // `--` keeps a task starting with `-` from being read as a flag
+        cmd.arg("--")
+            .arg(task)
+            .current_dir(cwd_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        tracing::info!(
+            "subprocess codex: tool={}, agent={}, bin={}",
+            tool,
+            prepared.name,
+            self.bin.display()
+        );
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("failed to launch {}", self.bin.display()))?;
+        let stdout = child.stdout.take().context("capture codex stdout")?;
+        let mut stderr_pipe = child.stderr.take().context("capture codex stderr")?;
+        let stderr_task = tokio::spawn(async move {
+            let mut buf = String::new();
+            stderr_pipe.read_to_string(&mut buf).await.ok();
+            buf
+        });
+
+        let max_tokens = prepared.run.as_ref().and_then(|r| r.max_tokens_per_call);
+        let mut state = StreamState {
+            denied_tools: prepared.denied_tools.clone(),
+            ..Default::default()
+        };
+        let mut denied_stop = false;
+        let mut lines = BufReader::new(stdout).lines();
+        while let Some(line) = lines.next_line().await.context("read codex output")? {
+            state.apply_line(&line);
+            if let Some(tool) = state.trace.denied_tool.as_ref()
+                && !denied_stop
+            {
+                let msg = format!("agent called denied tool {tool}; terminating");
+                tracing::warn!("{}", msg);
+                state.stderr.push_str(&msg);
+                state.stderr.push('\n');
+                denied_stop = true;
+                child.start_kill().ok();
+            }
+            if let (Some(limit), Some(usage)) = (max_tokens, state.trace.usage.as_ref())
+                && usage.total_tokens > limit
+                && !state.trace.budget_exceeded
+            {
+                let msg = format!(
+                    "token budget exceeded: used {} of {} (run.max_tokens_per_call); terminating",
+                    usage.total_tokens, limit
+                );
+                tracing::warn!("{}", msg);
+                state.stderr.push_str(&msg);
+                state.stderr.push('\n');
+                state.trace.budget_exceeded = true;
+                child.start_kill().ok();
+            }
+        }
+        let status = child.wait().await.context("wait for codex")?;
+        state
+            .stderr
+            .push_str(&stderr_task.await.unwrap_or_default());
+
+        let mut ok = status.success();
+        if ok && !state.completed {
+            // An empty stream or one in a newer event format: nothing was parsed
+            ok = false;
+            state.stderr.push_str(
+                "codex produced no task_complete event; its --json output is empty or in an unsupported format\n",
+            );
+        }
+        if state.trace.budget_exceeded {
+            ok = false;
+            state
+                .stdout
+                .push_str("[run interrupted: token budget exceeded]\n");
+        }
+        if let Some(tool) = &state.trace.denied_tool {
+            ok = false;
+            state
+                .stdout
+                .push_str(&format!("[run interrupted: denied tool {tool}]\n"));
+        }
+        let status_code = if ok {
+            0
+        } else {
+            status.code().unwrap_or(1).max(1)
+        };
+        tracing::info!(
+            "subprocess codex finished: ok={}, status={}, stdout_len={}, stderr_len={}",
+            ok,
+            status_code,
+            state.stdout.len(),
+            state.stderr.len()
+        );
+        Ok(CodexRunOutput {
+            ok,
+            status: status_code,
+            stdout: state.stdout,
+            stderr: state.stderr,
+            trace: state.trace,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::AgentRun;
+
+    #[test]
+    fn denied_mcp_call_is_flagged() {
+        let mut st = StreamState {
+            denied_tools: vec![McpToolRef::parse("github/*")],
+            ..Default::default()
+        };
+        st.apply_line(
+            r#"{"id":"0","msg":{"type":"mcp_tool_call_begin","invocation":{"server":"mem","tool":"get"}}}"#,
+        );
+        assert!(st.trace.denied_tool.is_none());
+        st.apply_line(
+            r#"{"id":"0","msg":{"type":"mcp_tool_call_begin","invocation":{"server":"github","tool":"create_issue"}}}"#,
+        );
+        assert_eq!(st.trace.denied_tool.as_deref(), Some("github/create_issue"));
+    }
+
+    #[test]
+    fn jsonl_events_fold_into_trace() {
+        let mut st = StreamState::default();
+        for line in [
+            r#"{"model":"o3","sandbox":"read-only"}"#,
+            r#"{"prompt":"do it"}"#,
+            r#"{"id":"0","msg":{"type":"session_configured","session_id":"s-1","model":"gpt-5"}}"#,
+            r#"{"id":"0","msg":{"type":"exec_command_begin","call_id":"c1","command":["ls"],"cwd":"/w"}}"#,
+            r#"{"id":"0","msg":{"type":"exec_command_end","call_id":"c1","exit_code":0,"duration":{"secs":1,"nanos":500000000}}}"#,
+            r#"{"id":"0","msg":{"type":"mcp_tool_call_end","invocation":{"server":"mem","tool":"get"},"result":{"Ok":{}},"duration":{"secs":0,"nanos":0}}}"#,
+            r#"{"id":"0","msg":{"type":"patch_apply_begin","call_id":"p1","auto_approved":true,"changes":{"b.rs":{},"a.rs":{}}}}"#,
+            r#"{"id":"0","msg":{"type":"patch_apply_end","call_id":"p1","success":true}}"#,
+            r#"{"id":"0","msg":{"type":"token_count","input_tokens":10,"output_tokens":5,"total_tokens":15}}"#,
+            r#"{"id":"0","msg":{"type":"turn_diff","unified_diff":"--- a\n+++ b\n"}}"#,
+            r#"{"id":"0","msg":{"type":"error","message":"boom"}}"#,
+            r#"{"id":"0","msg":{"type":"task_complete","last_agent_message":"done"}}"#,
+            "not json",
+        ] {
+            st.apply_line(line);
+        }
+        assert_eq!(st.stdout, "done\n");
+        assert!(st.stderr.contains("error: boom"));
+        assert!(st.stderr.contains("not json"));
+        let t = &st.trace;
+        assert_eq!(t.model.as_deref(), Some("gpt-5"));
+        assert_eq!(t.commands[0].exit_code, Some(0));
+        assert_eq!(t.commands[0].duration_ms, Some(1500));
+        assert!(t.mcp_calls[0].ok);
+        assert_eq!(
+            t.patches[0].files,
+            vec!["a.rs".to_string(), "b.rs".to_string()]
+        );
+        assert_eq!(t.patches[0].success, Some(true));
+        assert_eq!(t.usage.as_ref().unwrap().total_tokens, 15);
+        assert!(t.diff.is_some());
+    }
+
+    #[test]
+    fn overrides_cover_run_settings() {
+        let agent = PreparedAgent {
+            tool_name: "agent_x".to_string(),
+            name: "X".to_string(),
+            description: "d".to_string(),
+            source_path: "/tmp/x.toml".into(),
+            run: Some(AgentRun {
+                model: Some("o3".to_string()),
+                include_plan_tool: Some(true),
+                ..Default::default()
+            }),
+            runner: Some("subprocess".to_string()),
+            ..Default::default()
+        };
+        let ov = config_overrides(
+            &agent,
+            Some(std::path::Path::new("/tmp/i.md")),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert!(ov.contains(&"model=\"o3\"".to_string()));
+        // Not a config.toml key; codex would ignore it
+        assert!(!ov.iter().any(|o| o.starts_with("include_plan_tool")));
+        assert!(ov.contains(&"experimental_instructions_file=\"/tmp/i.md\"".to_string()));
+    }
+
+    #[test]
+    fn unrecognized_streams_do_not_complete() {
+        let mut st = StreamState::default();
+        st.apply_line(r#"{"type":"item.completed","item":{"text":"done"}}"#);
+        assert!(!st.completed && st.stdout.is_empty());
+        st.apply_line(r#"{"id":"0","msg":{"type":"task_complete","last_agent_message":"done"}}"#);
+        assert!(st.completed);
+    }
+
+    #[test]
+    fn mcp_env_stays_off_the_command_line() {
+        let srv: codex_core::config_types::McpServerConfig =
+            toml::from_str("command = \"npx\"\nargs = [\"srv\"]\nenv = { TOKEN = \"s3cret\" }")
+                .unwrap();
+        let agent = PreparedAgent {
+            name: "X".to_string(),
+            mcp_servers: std::collections::HashMap::from([("gh".to_string(), srv)]),
+            ..Default::default()
+        };
+        let mut files = Vec::new();
+        let ov = config_overrides(&agent, None, &mut files).unwrap();
+        assert!(!ov.iter().any(|o| o.contains("s3cret")), "{ov:?}");
+        let args = ov
+            .iter()
+            .find(|o| o.starts_with("mcp_servers.gh.args="))
+            .unwrap();
+        assert!(
+            args.contains("--env-file") && args.contains("npx"),
+            "{args}"
+        );
+        let path = files[0].path();
+        assert!(std::fs::read_to_string(path).unwrap().contains("s3cret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o077, 0);
+        }
+    }
+
+    #[test]
+    fn builtin_denials_are_enforced_or_ignored() {
+        let mut agent = PreparedAgent {
+            name: "X".to_string(),
+            run: Some(AgentRun {
+                include_apply_patch_tool: Some(false),
+                sandbox_mode: Some(SandboxMode::WorkspaceWrite),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let ov = config_overrides(&agent, None, &mut Vec::new()).unwrap();
+        assert_eq!(ov, ["sandbox_mode=\"read-only\""]);
+
+        let run = agent.run.as_mut().unwrap();
+        run.include_plan_tool = Some(false);
+        run.include_view_image_tool = Some(false);
+        let ov = config_overrides(&agent, None, &mut Vec::new()).unwrap();
+        assert_eq!(ov, ["sandbox_mode=\"read-only\""]);
+    }
+}
@@ -0,0 +1,269 @@
+//! Recursive discovery of agent definition files.
+//!
+//! Each agent directory is walked up to `depth` levels of subfolders (none
+//! by default).
+//! Globs use `/` separators and match the path relative to the scanned
+//! directory; `*` and `?` stay within one path segment and `**` spans any
+//! number of them. A glob without `/` matches a file or folder name anywhere
+//! in the tree (`exclude = ["drafts"]`).
+//!
+//! Folders such as `node_modules` and `.git`, and Switchboard's own
+//! `mock`/`fragments` folders, are never scanned.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+
+use crate::config::ScanCfg;
+
+/// Folder names skipped during recursive scans.
+const IGNORED_DIRS: &[&str] = &[
+    "node_modules",
+    ".git",
+    "target",
+    ".venv",
+    "mock",
+    "fragments",
+];
+
+/// Default number of subfolder levels scanned below each agent directory;
+/// only the top level, as before recursive scanning existed.
+pub const DEFAULT_DEPTH: usize = 0;
+
+/// Shell-style glob compiled to a regex.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    re: regex::Regex,
+    /// Matches against the bare file or folder name instead of the relative path.
+    name_only: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> anyhow::Result<Self> {
+        let pattern = pattern.trim().trim_start_matches("./");
+        let mut re = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        re.push_str("(?:.*/)?");
+                    } else {
+                        re.push_str(".*");
+                    }
+                }
+                '*' => re.push_str("[^/]*"),
+                '?' => re.push_str("[^/]"),
+                c => re.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        re.push('$');
+        Ok(Self {
+            re: regex::Regex::new(&re).with_context(|| format!("invalid glob '{pattern}'"))?,
+            name_only: !pattern.contains('/'),
+        })
+    }
+
+    fn matches(&self, rel: &str) -> bool {
+        let target = if self.name_only {
+            rel.rsplit('/').next().unwrap_or(rel)
+        } else {
+            rel
+        };
+        self.re.is_match(target)
+    }
+}
+
+/// How one variant's directories are scanned.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Subfolder levels below each directory; 0 reads only the top level.
+    pub depth: usize,
+    /// When non-empty, only files matching one of these globs are loaded.
+    pub include: Vec<Glob>,
+    /// Files and folders matching any of these globs are skipped.
+    pub exclude: Vec<Glob>,
+    /// Add subfolder names to the agent's tags.
+    pub folder_tags: bool,
+    /// Prefix tool names with the subfolder path (`.agents/review/x.toml` → `agent_review_x`).
+    pub folder_namespace: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            depth: DEFAULT_DEPTH,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            folder_tags: false,
+            folder_namespace: false,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Options from a `[agents.scan.<variant>]` table; `depth` (from the
+    /// environment) wins over the table. Invalid globs are skipped with a warning.
+    pub fn from_cfg(cfg: Option<&ScanCfg>, depth: Option<usize>) -> Self {
+        let globs = |list: Option<&Vec<String>>| -> Vec<Glob> {
+            list.into_iter()
+                .flatten()
+                .filter_map(|p| match Glob::new(p) {
+                    Ok(g) => Some(g),
+                    Err(e) => {
+                        tracing::warn!("ignoring scan pattern: {:#}", e);
+                        None
+                    }
+                })
+                .collect()
+        };
+        Self {
+            depth: depth
+                .or_else(|| cfg.and_then(|c| c.depth))
+                .unwrap_or(DEFAULT_DEPTH),
+            include: globs(cfg.and_then(|c| c.include.as_ref())),
+            exclude: globs(cfg.and_then(|c| c.exclude.as_ref())),
+            folder_tags: cfg.and_then(|c| c.folder_tags).unwrap_or(false),
+            folder_namespace: cfg.and_then(|c| c.folder_namespace).unwrap_or(false),
+        }
+    }
+}
+
+/// A candidate agent file and the subfolders between the scan root and it.
+pub struct ScannedFile {
+    pub path: PathBuf,
+    pub folders: Vec<String>,
+}
+
+/// List files under `root`, in name order, honoring depth and globs.
+pub fn scan_dir(root: &Path, opts: &ScanOptions) -> anyhow::Result<Vec<ScannedFile>> {
+    let mut out = Vec::new();
+    walk(root, &mut Vec::new(), opts, &mut out)
+        .with_context(|| format!("read_dir {}", root.display()))?;
+    Ok(out)
+}
+
+fn walk(
+    dir: &Path,
+    folders: &mut Vec<String>,
+    opts: &ScanOptions,
+    out: &mut Vec<ScannedFile>,
+) -> std::io::Result<()> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+    for path in entries {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let rel = folders
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join("/");
+        if opts.exclude.iter().any(|g| g.matches(&rel)) {
+            continue;
+        }
+        if path.is_dir() {
+            if folders.len() >= opts.depth || IGNORED_DIRS.contains(&name) {
+                continue;
+            }
+            folders.push(name.to_string());
+            if let Err(e) = walk(&path, folders, opts, out) {
+                tracing::warn!("skipping {}: {}", path.display(), e);
+            }
+            folders.pop();
+        } else if path.is_file()
+            && (opts.include.is_empty() || opts.include.iter().any(|g| g.matches(&rel)))
+        {
+            out.push(ScannedFile {
+                path,
+                folders: folders.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(root: &Path, rel: &str) {
+        let p = root.join(rel);
+        std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+        std::fs::write(p, "").unwrap();
+    }
+
+    fn rels(root: &Path, opts: &ScanOptions) -> Vec<String> {
+        scan_dir(root, opts)
+            .unwrap()
+            .into_iter()
+            .map(|f| {
+                f.path
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn globs_match_segments_and_names() {
+        let g = Glob::new("review/**/*.toml").unwrap();
+        assert!(g.matches("review/a.toml"));
+        assert!(g.matches("review/deep/b.toml"));
+        assert!(!g.matches("ops/a.toml"));
+        let g = Glob::new("*.toml").unwrap();
+        assert!(g.matches("ops/deep/x.toml"));
+        assert!(!Glob::new("a?.md").unwrap().matches("a/b.md"));
+    }
+
+    #[test]
+    fn scans_nested_folders_with_depth_and_filters() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for rel in [
+            "top.toml",
+            "review/sec.toml",
+            "review/drafts/wip.toml",
+            "ops/deploy.toml",
+            "ops/a/b/c/too-deep.toml",
+            "node_modules/pkg/x.toml",
+            "mock/fixture.toml",
+        ] {
+            touch(root, rel);
+        }
+
+        assert_eq!(rels(root, &ScanOptions::default()), ["top.toml"]);
+        let opts = ScanOptions {
+            depth: 3,
+            ..ScanOptions::default()
+        };
+        assert_eq!(
+            rels(root, &opts),
+            [
+                "ops/deploy.toml",
+                "review/drafts/wip.toml",
+                "review/sec.toml",
+                "top.toml"
+            ]
+        );
+
+        let cfg = ScanCfg {
+            depth: Some(1),
+            include: Some(vec!["review/*".into(), "top.toml".into()]),
+            exclude: Some(vec!["drafts".into()]),
+            ..Default::default()
+        };
+        let opts = ScanOptions::from_cfg(Some(&cfg), None);
+        assert_eq!(rels(root, &opts), ["review/sec.toml", "top.toml"]);
+        let opts = ScanOptions::from_cfg(Some(&cfg), Some(0));
+        assert_eq!(rels(root, &opts), ["top.toml"]);
+    }
+}
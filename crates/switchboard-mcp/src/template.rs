@@ -0,0 +1,256 @@
+//! Per-call `{{...}}` templating of agent instructions.
+//!
+//! Opt-in per agent (`templating = true`). Variables:
+//! - `{{cwd}}`, `{{workspace}}`, `{{date}}` (local, `YYYY-MM-DD`)
+//! - `{{git.branch}}`, `{{git.head}}` (short hash) for the call's `cwd`
+//! - `{{agent.name}}`, `{{agent.tool}}`
+//! - `{{env.NAME}}`, for names on the server's `template_env_allow` list
+//! - custom `[vars]` entries as `{{name}}` or `{{vars.name}}`
+//!
+//! Unknown variables are left untouched so literal braces in examples survive.
+//!
+//! Prompt and command agents (`~/.codex/prompts`, `.claude/commands`) also
+//! turn each call's `task` into their template's `$ARGUMENTS` and `$1`..`$9`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::model::PreparedAgent;
+
+/// Replace each `{{ key }}` for which `lookup` returns a value.
+fn render(text: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        match lookup(after[..end].trim()) {
+            Some(v) => out.push_str(&v),
+            None => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Split arguments on whitespace, keeping single- or double-quoted runs together.
+fn split_args(s: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut quote: Option<char> = None;
+    let mut started = false;
+    for c in s.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => cur.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                started = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if started {
+                    out.push(std::mem::take(&mut cur));
+                    started = false;
+                }
+            }
+            (None, c) => {
+                cur.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        out.push(cur);
+    }
+    out
+}
+
+/// Fill a prompt template with a call's arguments: `$ARGUMENTS` is the whole
+/// task, `$1`..`$9` its (quote-aware) words and `$$` a literal `$`. A template
+/// without placeholders gets the task appended as `ARGUMENTS: <task>`.
+pub fn render_task(template: &str, task: &str) -> String {
+    let task = task.trim();
+    let words = split_args(task);
+    let mut out = String::with_capacity(template.len() + task.len());
+    let mut used = false;
+    let mut rest = template;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let digit = after.chars().next().filter(|c| ('1'..='9').contains(c));
+        if let Some(tail) = after.strip_prefix("ARGUMENTS") {
+            out.push_str(task);
+            used = true;
+            rest = tail;
+        } else if let Some(d) = digit {
+            let n = d as usize - '1' as usize;
+            out.push_str(words.get(n).map(String::as_str).unwrap_or(""));
+            used = true;
+            rest = &after[1..];
+        } else if let Some(tail) = after.strip_prefix('$') {
+            out.push('$');
+            rest = tail;
+        } else {
+            out.push('$');
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    if !used && !task.is_empty() {
+        out = format!("{}\n\nARGUMENTS: {}", out.trim_end(), task);
+    }
+    out
+}
+
+async fn git(cwd: &str, args: &[&str]) -> Option<String> {
+    let out = tokio::process::Command::new("git")
+        .arg("-C")
+        .arg(cwd)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    let s = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (out.status.success() && !s.is_empty()).then_some(s)
+}
+
+/// Render the agent's instructions for one call. Returns `None` when the
+/// agent does not opt in or has no instructions. `{{env.NAME}}` resolves only
+/// for names in `env_allow`; others are left as written.
+pub async fn render_instructions(
+    agent: &PreparedAgent,
+    cwd: &str,
+    workspace: Option<&Path>,
+    env_allow: &[String],
+) -> Option<String> {
+    let vars = agent.template_vars.as_ref()?;
+    let text = agent.instructions.as_deref()?;
+    let mut values: HashMap<String, String> = HashMap::new();
+    // Git lookups cost a process each; only run them when referenced.
+    if text.contains("git.") {
+        let branch = git(cwd, &["rev-parse", "--abbrev-ref", "HEAD"]).await;
+        let head = git(cwd, &["rev-parse", "--short", "HEAD"]).await;
+        values.insert(
+            "git.branch".to_string(),
+            branch.unwrap_or_else(|| "unknown".to_string()),
+        );
+        values.insert(
+            "git.head".to_string(),
+            head.unwrap_or_else(|| "unknown".to_string()),
+        );
+    }
+    values.insert("cwd".to_string(), cwd.to_string());
+    if let Some(ws) = workspace {
+        values.insert("workspace".to_string(), ws.display().to_string());
+    }
+    values.insert(
+        "date".to_string(),
+        chrono::Local::now().format("%Y-%m-%d").to_string(),
+    );
+    values.insert("agent.name".to_string(), agent.name.clone());
+    values.insert("agent.tool".to_string(), agent.tool_name.clone());
+    Some(render(text, |key| {
+        if let Some(v) = values.get(key) {
+            return Some(v.clone());
+        }
+        if let Some(name) = key.strip_prefix("env.") {
+            return env_allow
+                .iter()
+                .any(|a| a == name)
+                .then(|| std::env::var(name).ok())
+                .flatten();
+        }
+        let name = key.strip_prefix("vars.").unwrap_or(key);
+        vars.get(name).cloned()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(instructions: &str, vars: Option<HashMap<String, String>>) -> PreparedAgent {
+        PreparedAgent {
+            tool_name: "agent_dev".to_string(),
+            name: "Dev".to_string(),
+            description: "d".to_string(),
+            source_path: "/tmp/dev.toml".into(),
+            instructions: Some(instructions.to_string()),
+            template_vars: vars,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unknown_and_unterminated_placeholders_are_kept() {
+        let lookup = |k: &str| (k == "x").then(|| "1".to_string());
+        assert_eq!(render("a {{ x }} {{y}} {{x}}", lookup), "a 1 {{y}} 1");
+        assert_eq!(render("open {{x", lookup), "open {{x");
+    }
+
+    #[test]
+    fn task_fills_arguments_and_positionals() {
+        let t = "Review PR #$1 with priority $2. Notes: $ARGUMENTS ($$5)";
+        assert_eq!(
+            render_task(t, "42 high 'keep it short'"),
+            "Review PR #42 with priority high. Notes: 42 high 'keep it short' ($5)"
+        );
+        assert_eq!(render_task("Fix $3.", "a b"), "Fix .");
+        assert_eq!(
+            render_task("Write release notes.\n", "v1.2"),
+            "Write release notes.\n\nARGUMENTS: v1.2"
+        );
+        assert_eq!(render_task("Tidy up.", "  "), "Tidy up.");
+        assert_eq!(split_args(r#"a "b c" d"#), ["a", "b c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn renders_builtins_and_vars_only_when_enabled() {
+        let text =
+            "{{agent.name}} in {{cwd}} ({{workspace}}) on {{date}}; team={{team}}/{{vars.team}}";
+        let vars = HashMap::from([("team".to_string(), "core".to_string())]);
+        let out = render_instructions(
+            &agent(text, Some(vars)),
+            "/tmp",
+            Some(Path::new("/ws")),
+            &[],
+        )
+        .await
+        .unwrap();
+        assert!(out.starts_with("Dev in /tmp (/ws) on 20"), "{out}");
+        assert!(out.ends_with("team=core/core"), "{out}");
+        assert!(
+            render_instructions(&agent(text, None), "/tmp", None, &[])
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn git_values_fall_back_outside_a_repository() {
+        let tmp = tempfile::tempdir().unwrap();
+        let a = agent("branch={{git.branch}}", Some(HashMap::new()));
+        let out = render_instructions(&a, tmp.path().to_str().unwrap(), None, &[])
+            .await
+            .unwrap();
+        assert_eq!(out, "branch=unknown");
+    }
+
+    #[tokio::test]
+    async fn env_values_need_the_allowlist() {
+        let a = agent("home={{env.HOME}} path={{env.PATH}}", Some(HashMap::new()));
+        let out = render_instructions(&a, "/tmp", None, &["HOME".to_string()])
+            .await
+            .unwrap();
+        let home = std::env::var("HOME").unwrap_or_else(|_| "{{env.HOME}}".to_string());
+        assert_eq!(out, format!("home={home} path={{{{env.PATH}}}}"));
+        let out = render_instructions(&a, "/tmp", None, &[]).await.unwrap();
+        assert_eq!(out, "home={{env.HOME}} path={{env.PATH}}");
+    }
+}
@@ -1,3 +1,5 @@
+use codex_protocol::config_types::SandboxMode;
+
 use crate::mcp::types::{
     McpProvider, McpServerOrigin, McpToolRef, McpTransport, NormalizedMcpServer,
 };
@@ -15,7 +17,7 @@ pub fn apply_mapping(
     let Some(refs) = agent.mcp_tool_refs.take() else {
         return custom_servers;
     };
-    let (table, _name) = match provider {
+    let (table, name) = match provider {
         ProviderId::Vscode => (&mapping.vscode, "vscode"),
         ProviderId::Anthropic => (&mapping.anthropic, "anthropic"),
         ProviderId::Codex => (&mapping.anthropic, "codex"), // codex rarely uses mcp_tool_refs; reuse builtin mapping
@@ -26,9 +28,9 @@ pub fn apply_mapping(
 
     for r in refs {
         match r {
-            McpToolRef::Namespaced { server_key, tool } => {
+            McpToolRef::Namespaced { .. } | McpToolRef::Server { .. } => {
                 // Already explicit; keep as-is
-                new_refs.push(McpToolRef::Namespaced { server_key, tool });
+                new_refs.push(r);
             }
             McpToolRef::Bare { tool } => {
                 // Resolve alias
@@ -58,7 +60,7 @@ pub fn apply_mapping(
                             server_key: server_key.clone(),
                             tool: tool.clone(),
                         });
-                        if allow_custom_servers {
+                        if allow_custom_servers && let Some(command) = command {
                             custom_servers.push(NormalizedMcpServer {
                                 key: server_key.clone(),
                                 transport: McpTransport::Stdio {
@@ -69,13 +71,23 @@ pub fn apply_mapping(
                                 origin: McpServerOrigin {
                                     provider: McpProvider::Mapping,
                                     path: None,
-                                    note: Some("toolmap default".to_string()),
+                                    note: Some("tool map".to_string()),
                                 },
                             });
                         }
                     }
+                    Some(MappingDest::Drop) => {}
                     None => {
-                        // Unknown; keep as bare for now
+                        // Unknown; keep as bare so enumeration can still find an MCP tool
+                        // with that name. Placeholders listed in the aliases are known.
+                        if provider == ProviderId::Vscode && !table.aliases.contains_key(&tool) {
+                            tracing::warn!(
+                                "agent '{}': unknown {} tool '{}' (kept as a bare MCP tool ref)",
+                                agent.name,
+                                name,
+                                tool
+                            );
+                        }
                         new_refs.push(McpToolRef::Bare { tool: key });
                     }
                 }
@@ -92,6 +104,79 @@ pub fn apply_mapping(
     custom_servers
 }
 
+/// Apply the agent's `deny_tools` after mapping so denials always win:
+/// mapped builtins are forced off, terminal tools (`Bash`, `runCommands`)
+/// clamp the sandbox to read-only, and MCP denials are removed from the tool
+/// list. Only the MCP denials remain in `deny_tools`, for server attachment
+/// and the runners. Permission rules such as `Bash(rm:*)` deny the whole
+/// tool; Claude names that map to nothing are dropped with a warning, since
+/// Claude refers to MCP tools as `mcp__server__tool`.
+pub fn apply_denials(provider: ProviderId, agent: &mut AgentConfig, mapping: &LoadedMapping) {
+    let Some(denied) = agent.deny_tools.take() else {
+        return;
+    };
+    let table = match provider {
+        ProviderId::Vscode => &mapping.vscode,
+        ProviderId::Anthropic | ProviderId::Codex => &mapping.anthropic,
+    };
+    let mut run = agent.run.take().unwrap_or_default();
+    let mut mcp: Vec<McpToolRef> = Vec::new();
+    for r in denied {
+        let r = match r {
+            McpToolRef::Bare { tool } if tool.contains('(') => McpToolRef::Bare {
+                tool: tool
+                    .split('(')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            },
+            r => r,
+        };
+        let dest = match &r {
+            McpToolRef::Bare { tool } => {
+                let key = table.aliases.get(tool).unwrap_or(tool);
+                table.map.get(key)
+            }
+            _ => None,
+        };
+        match dest {
+            Some(MappingDest::Builtin(bt)) => match bt {
+                BuiltinToggle::Plan => run.include_plan_tool = Some(false),
+                BuiltinToggle::ApplyPatch => run.include_apply_patch_tool = Some(false),
+                BuiltinToggle::ViewImage => run.include_view_image_tool = Some(false),
+                BuiltinToggle::WebSearch => run.tools_web_search_request = Some(false),
+                BuiltinToggle::TerminalAccess => run.sandbox_mode = Some(SandboxMode::ReadOnly),
+            },
+            Some(MappingDest::McpTool {
+                server_key, tool, ..
+            }) => mcp.push(McpToolRef::Namespaced {
+                server_key: server_key.clone(),
+                tool: tool.clone(),
+            }),
+            Some(MappingDest::Drop) => {}
+            None => match r {
+                McpToolRef::Bare { tool } if provider == ProviderId::Anthropic => {
+                    tracing::warn!(
+                        "agent '{}': unknown Claude tool '{}' in disallowedTools (ignored)",
+                        agent.name,
+                        tool
+                    );
+                }
+                r => mcp.push(r),
+            },
+        }
+    }
+    if let Some(refs) = agent.mcp_tool_refs.as_mut() {
+        refs.retain(|r| !mcp.iter().any(|d| d.covers(r)));
+        if refs.is_empty() {
+            agent.mcp_tool_refs = None;
+        }
+    }
+    agent.deny_tools = (!mcp.is_empty()).then_some(mcp);
+    agent.run = Some(run);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,8 +186,6 @@ mod tests {
         AgentConfig {
             name: "t".into(),
             description: "d".into(),
-            tags: None,
-            toggles: None,
             mcp_tool_refs: Some(
                 tools
                     .into_iter()
@@ -111,10 +194,7 @@ mod tests {
                     })
                     .collect(),
             ),
-            instructions_file: None,
-            instructions: None,
-            run: None,
-            mcp_servers: None,
+            ..Default::default()
         }
     }
 
@@ -165,4 +245,90 @@ mod tests {
         assert_eq!(run.tools_web_search_request, None);
         assert_eq!(run.include_plan_tool, None);
     }
+
+    #[test]
+    fn vscode_server_refs_are_kept_next_to_toggles() {
+        let mapping = super::super::types::default_mapping();
+        let mut agent = stub_agent_with_tools(vec![]);
+        agent.mcp_tool_refs = Some(vec![
+            McpToolRef::parse("#github"),
+            McpToolRef::parse("edit"),
+            McpToolRef::parse("memory/*"),
+        ]);
+        let _ = apply_mapping(ProviderId::Vscode, &mut agent, &mapping, false);
+        let refs = agent.mcp_tool_refs.unwrap();
+        assert_eq!(
+            refs,
+            vec![
+                McpToolRef::Server {
+                    server_key: "github".to_string()
+                },
+                McpToolRef::Server {
+                    server_key: "memory".to_string()
+                },
+            ]
+        );
+        assert_eq!(agent.run.unwrap().include_apply_patch_tool, Some(true));
+    }
+
+    #[test]
+    fn denials_override_toggles_and_refs() {
+        let mapping = super::super::types::default_mapping();
+        let mut agent = stub_agent_with_tools(vec![]);
+        agent.mcp_tool_refs = Some(vec![
+            McpToolRef::parse("Edit"),
+            McpToolRef::parse("WebSearch"),
+            McpToolRef::parse("github/get_me"),
+            McpToolRef::parse("github/create_issue"),
+        ]);
+        agent.deny_tools = Some(vec![
+            McpToolRef::parse("Write"),
+            McpToolRef::parse("Bash"),
+            McpToolRef::parse("github/create_issue"),
+        ]);
+        let _ = apply_mapping(ProviderId::Anthropic, &mut agent, &mapping, false);
+        apply_denials(ProviderId::Anthropic, &mut agent, &mapping);
+        let run = agent.run.unwrap();
+        assert_eq!(run.include_apply_patch_tool, Some(false));
+        assert_eq!(run.tools_web_search_request, Some(true));
+        assert!(matches!(run.sandbox_mode, Some(SandboxMode::ReadOnly)));
+        assert_eq!(
+            agent.mcp_tool_refs.unwrap(),
+            vec![McpToolRef::parse("github/get_me")]
+        );
+        assert_eq!(
+            agent.deny_tools.unwrap(),
+            vec![McpToolRef::parse("github/create_issue")]
+        );
+    }
+
+    #[test]
+    fn claude_builtin_denials_stay_off_the_mcp_filter() {
+        let mapping = super::super::types::default_mapping();
+        let mut agent = stub_agent_with_tools(vec![]);
+        agent.deny_tools = Some(vec![
+            McpToolRef::parse("Task"),
+            McpToolRef::parse("Glob"),
+            McpToolRef::parse("Edit(src/**)"),
+            McpToolRef::parse("Frobnicate"),
+            McpToolRef::parse("mcp__github__create_issue"),
+        ]);
+        apply_denials(ProviderId::Anthropic, &mut agent, &mapping);
+        let run = agent.run.unwrap();
+        assert_eq!(run.include_apply_patch_tool, Some(false));
+        assert!(run.sandbox_mode.is_none());
+        assert_eq!(
+            agent.deny_tools.unwrap(),
+            vec![McpToolRef::parse("github/create_issue")]
+        );
+
+        let mut agent = stub_agent_with_tools(vec![]);
+        agent.deny_tools = Some(vec![McpToolRef::parse("Bash(rm:*)")]);
+        apply_denials(ProviderId::Anthropic, &mut agent, &mapping);
+        assert!(matches!(
+            agent.run.unwrap().sandbox_mode,
+            Some(SandboxMode::ReadOnly)
+        ));
+        assert!(agent.deny_tools.is_none());
+    }
 }
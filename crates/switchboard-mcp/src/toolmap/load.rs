@@ -0,0 +1,214 @@
+//! User tool maps (`tool-map.toml`) layered over the default mapping.
+//!
+//! ```toml
+//! [anthropic.aliases]
+//! Task = "plan"
+//!
+//! [anthropic.tools]
+//! NotebookRead = { builtin = "view_image" }
+//! Search = { server = "brave", tool = "brave_web_search", command = "npx", args = ["-y", "@modelcontextprotocol/server-brave-search"] }
+//!
+//! [vscode]
+//! drop = ["extensions", "vscodeAPI"]
+//! ```
+//!
+//! Builtins: `plan`, `apply_patch`, `view_image`, `web_search`, `terminal`.
+//! Entries replace defaults with the same name; later files win.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, bail};
+use serde::Deserialize;
+
+use super::types::{BuiltinToggle, LoadedMapping, MappingDest, ToolMappingTable, default_mapping};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawToolMap {
+    vscode: Option<RawTable>,
+    #[serde(alias = "claude")]
+    anthropic: Option<RawTable>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTable {
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+    #[serde(default)]
+    tools: HashMap<String, RawDest>,
+    #[serde(default)]
+    drop: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDest {
+    builtin: Option<String>,
+    server: Option<String>,
+    tool: Option<String>,
+    command: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+}
+
+impl RawDest {
+    fn into_dest(self, name: &str) -> anyhow::Result<MappingDest> {
+        match (self.builtin, self.server) {
+            (Some(b), None) => {
+                if self.tool.is_some() || self.command.is_some() {
+                    bail!("tool '{name}': `builtin` cannot be combined with server fields");
+                }
+                BuiltinToggle::from_name(&b)
+                    .map(MappingDest::Builtin)
+                    .with_context(|| format!("tool '{name}': unknown builtin '{b}'"))
+            }
+            (None, Some(server_key)) => Ok(MappingDest::McpTool {
+                server_key,
+                tool: self.tool.unwrap_or_else(|| name.to_string()),
+                command: self.command,
+                args: self.args,
+                env: self.env,
+            }),
+            (Some(_), Some(_)) => {
+                bail!("tool '{name}': set either `builtin` or `server`, not both")
+            }
+            (None, None) => bail!("tool '{name}': needs `builtin` or `server`"),
+        }
+    }
+}
+
+/// Apply one provider's section on top of `table`.
+fn layer(table: &mut ToolMappingTable, raw: RawTable) -> anyhow::Result<()> {
+    let mut tools = Vec::with_capacity(raw.tools.len());
+    for (name, dest) in raw.tools {
+        let dest = dest.into_dest(&name)?;
+        tools.push((name, dest));
+    }
+    for (alias, canon) in raw.aliases {
+        table.aliases.insert(alias, canon);
+    }
+    for (name, dest) in tools {
+        table.aliases.remove(&name);
+        table.map.insert(name, dest);
+    }
+    for name in raw.drop {
+        table.aliases.remove(&name);
+        table.map.insert(name, MappingDest::Drop);
+    }
+    Ok(())
+}
+
+/// Layer a `tool-map.toml` document over `mapping`. Nothing is applied when
+/// any entry is invalid.
+pub fn layer_toml_str(mapping: &mut LoadedMapping, s: &str) -> anyhow::Result<()> {
+    let raw: RawToolMap = toml::from_str(s)?;
+    let mut next = mapping.clone();
+    if let Some(t) = raw.vscode {
+        layer(&mut next.vscode, t).context("[vscode]")?;
+    }
+    if let Some(t) = raw.anthropic {
+        layer(&mut next.anthropic, t).context("[anthropic]")?;
+    }
+    *mapping = next;
+    Ok(())
+}
+
+/// Default mapping with each existing file in `files` layered on in order.
+/// Missing files are skipped; invalid files are skipped with a warning.
+pub fn load_layered(files: &[PathBuf]) -> LoadedMapping {
+    let mut mapping = default_mapping();
+    for path in files {
+        match layer_file(&mut mapping, path) {
+            Ok(true) => tracing::info!("loaded tool map from {}", path.display()),
+            Ok(false) => tracing::debug!("no tool map at {}", path.display()),
+            Err(e) => tracing::warn!("failed to load tool map {}: {:#}", path.display(), e),
+        }
+    }
+    mapping
+}
+
+fn layer_file(mapping: &mut LoadedMapping, path: &Path) -> anyhow::Result<bool> {
+    if !path.is_file() {
+        return Ok(false);
+    }
+    let content = std::fs::read_to_string(path)?;
+    layer_toml_str(mapping, &content)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_aliases_destinations_and_drops() {
+        let mut m = default_mapping();
+        layer_toml_str(
+            &mut m,
+            r#"
+[claude.aliases]
+Task = "plan"
+
+[claude.tools]
+Grep = { server = "search", tool = "ripgrep" }
+Lookup = { server = "brave", command = "npx", args = ["-y", "brave"] }
+
+[vscode]
+drop = ["Find Usages", "extensions"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(m.anthropic.aliases.get("Task").unwrap(), "plan");
+        assert!(matches!(
+            m.anthropic.map.get("Grep"),
+            Some(MappingDest::McpTool { server_key, tool, command: None, .. })
+                if server_key == "search" && tool == "ripgrep"
+        ));
+        assert!(matches!(
+            m.anthropic.map.get("Lookup"),
+            Some(MappingDest::McpTool { tool, command: Some(_), .. }) if tool == "Lookup"
+        ));
+        assert!(!m.vscode.aliases.contains_key("Find Usages"));
+        assert!(matches!(
+            m.vscode.map.get("extensions"),
+            Some(MappingDest::Drop)
+        ));
+        // Untouched defaults survive
+        assert!(m.vscode.map.contains_key("edit"));
+    }
+
+    #[test]
+    fn invalid_entries_leave_the_mapping_unchanged() {
+        let mut m = default_mapping();
+        let err = layer_toml_str(
+            &mut m,
+            r#"
+[anthropic]
+drop = ["Bash"]
+[anthropic.tools]
+Edit = { builtin = "teleport" }
+"#,
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("unknown builtin 'teleport'"));
+        assert!(matches!(
+            m.anthropic.map.get("Bash"),
+            Some(MappingDest::Builtin(BuiltinToggle::TerminalAccess))
+        ));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let user = tmp.path().join("user.toml");
+        let ws = tmp.path().join("ws.toml");
+        std::fs::write(&user, "[anthropic.tools]\nBash = { builtin = \"plan\" }\n").unwrap();
+        std::fs::write(&ws, "[anthropic]\ndrop = [\"Bash\"]\n").unwrap();
+        let m = load_layered(&[user, tmp.path().join("missing.toml"), ws]);
+        assert!(matches!(
+            m.anthropic.map.get("Bash"),
+            Some(MappingDest::Drop)
+        ));
+    }
+}
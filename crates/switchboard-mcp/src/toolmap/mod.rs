@@ -1,7 +1,11 @@
 //! Provider tool mapping: aliases → builtins and optional custom servers.
 
 pub mod apply;
+pub mod load;
+pub mod toolsets;
 pub mod types;
 
 pub use apply::*;
+pub use load::load_layered;
+pub use toolsets::ToolSets;
 pub use types::*;
@@ -0,0 +1,198 @@
+//! VS Code tool sets (`.vscode/*.toolsets.jsonc`).
+//!
+//! A tool set groups tools under one name:
+//! `{ "reader": { "tools": ["changes", "usages", "github/*"] } }`.
+//! Chatmodes may list the set (`reader` or `#reader`) in `tools`; it is
+//! replaced by its members, which may name other sets.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde_json::Value as JsonValue;
+
+use crate::mcp::types::McpToolRef;
+
+/// Tool sets by name with their member tool entries.
+#[derive(Debug, Default)]
+pub struct ToolSets {
+    sets: HashMap<String, Vec<String>>,
+}
+
+impl ToolSets {
+    /// Load every `*.toolsets.jsonc` file in `dir`; unreadable files are skipped.
+    pub fn load(dir: &Path) -> Self {
+        let mut out = Self::default();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return out;
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.ends_with(".toolsets.jsonc"))
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            let parsed = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| {
+                    serde_json::from_str::<JsonValue>(&strip_jsonc(&s)).map_err(|e| e.to_string())
+                });
+            match parsed {
+                Ok(v) => out.merge(&v),
+                Err(e) => tracing::warn!("skipping tool sets {}: {}", path.display(), e),
+            }
+        }
+        if !out.sets.is_empty() {
+            tracing::debug!("loaded {} VS Code tool set(s)", out.sets.len());
+        }
+        out
+    }
+
+    fn merge(&mut self, v: &JsonValue) {
+        let Some(obj) = v.as_object() else {
+            return;
+        };
+        for (name, def) in obj {
+            let tools: Vec<String> = def
+                .get("tools")
+                .and_then(|t| t.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|t| t.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default();
+            self.sets.insert(name.clone(), tools);
+        }
+    }
+
+    /// Replace tool set references with their member tools (deduplicated).
+    pub fn expand(&self, refs: Vec<McpToolRef>) -> Vec<McpToolRef> {
+        let mut out = Vec::new();
+        let mut stack = Vec::new();
+        for r in refs {
+            self.expand_into(r, &mut stack, &mut out);
+        }
+        out
+    }
+
+    fn expand_into(&self, r: McpToolRef, stack: &mut Vec<String>, out: &mut Vec<McpToolRef>) {
+        let name = match &r {
+            McpToolRef::Bare { tool } => Some(tool.as_str()),
+            McpToolRef::Server { server_key } => Some(server_key.as_str()),
+            McpToolRef::Namespaced { .. } => None,
+        };
+        match name.and_then(|n| self.sets.get_key_value(n)) {
+            Some((name, members)) if !stack.contains(name) => {
+                stack.push(name.clone());
+                for m in members {
+                    self.expand_into(McpToolRef::parse(m), stack, out);
+                }
+                stack.pop();
+            }
+            Some((name, _)) => tracing::warn!("tool set '{}' includes itself", name),
+            None => {
+                if !out.contains(&r) {
+                    out.push(r);
+                }
+            }
+        }
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// Remove `//` and `/* */` comments and trailing commas so JSONC parses as JSON.
+fn strip_jsonc(s: &str) -> String {
+    let mut no_comments = String::with_capacity(s.len());
+    scan_json(s, skip_comment, &mut no_comments);
+    let mut out = String::with_capacity(no_comments.len());
+    scan_json(&no_comments, skip_trailing_comma, &mut out);
+    out
+}
+
+fn skip_comment(c: char, rest: &mut Chars<'_>, out: &mut String) {
+    match (c, rest.peek()) {
+        ('/', Some('/')) => while rest.next_if(|&n| n != '\n').is_some() {},
+        ('/', Some('*')) => {
+            rest.next();
+            let mut prev = ' ';
+            for n in rest.by_ref() {
+                if prev == '*' && n == '/' {
+                    break;
+                }
+                prev = n;
+            }
+        }
+        _ => out.push(c),
+    }
+}
+
+fn skip_trailing_comma(c: char, rest: &mut Chars<'_>, out: &mut String) {
+    let trailing = c == ',' && matches!(rest.clone().find(|n| !n.is_whitespace()), Some('}' | ']'));
+    if !trailing {
+        out.push(c);
+    }
+}
+
+/// Copy JSON strings verbatim and hand every other character to `f`.
+fn scan_json(s: &str, f: fn(char, &mut Chars<'_>, &mut String), out: &mut String) {
+    let mut chars = s.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+            out.push(c);
+        } else {
+            f(c, &mut chars, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_jsonc_and_expands_nested_sets() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp.path().join("team.toolsets.jsonc"),
+            r##"{
+  // Read-only tools
+  "reader": {
+    "tools": ["usages", "github/*"],
+    "description": "See https://example.com/tools", // not a comment start
+  },
+  /* editing */
+  "writer": { "tools": ["#reader", "edit", "writer"] },
+}"##,
+        )
+        .unwrap();
+        let sets = ToolSets::load(tmp.path());
+        let refs = sets.expand(vec![
+            McpToolRef::parse("writer"),
+            McpToolRef::parse("edit"),
+            McpToolRef::parse("memory::memory"),
+        ]);
+        assert_eq!(
+            refs,
+            vec![
+                McpToolRef::parse("usages"),
+                McpToolRef::parse("github/*"),
+                McpToolRef::parse("edit"),
+                McpToolRef::parse("memory::memory"),
+            ]
+        );
+    }
+}
@@ -21,19 +21,34 @@ pub enum BuiltinToggle {
     TerminalAccess,
 }
 
-impl BuiltinToggle {}
+impl BuiltinToggle {
+    /// Parse a toggle name as written in `tool-map.toml`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "plan" => Some(Self::Plan),
+            "apply_patch" => Some(Self::ApplyPatch),
+            "view_image" => Some(Self::ViewImage),
+            "web_search" => Some(Self::WebSearch),
+            "terminal" => Some(Self::TerminalAccess),
+            _ => None,
+        }
+    }
+}
 
-/// Destination for a mapped tool: either a built-in Codex toggle or a named MCP tool with launch details.
+/// Destination for a mapped tool: a built-in Codex toggle, a named MCP tool
+/// (launched by Switchboard when `command` is set), or dropped entirely.
 #[derive(Debug, Clone)]
 pub enum MappingDest {
     Builtin(BuiltinToggle),
     McpTool {
         server_key: String,
         tool: String,
-        command: String,
+        command: Option<String>,
         args: Vec<String>,
         env: HashMap<String, String>,
     },
+    /// Consume the tool without a toggle or reference.
+    Drop,
 }
 
 /// Mapping table and aliases for a single provider.
@@ -59,7 +74,7 @@ pub fn default_mapping() -> LoadedMapping {
         MappingDest::McpTool {
             server_key: "memory".to_string(),
             tool: "memory".to_string(),
-            command: "npx".to_string(),
+            command: Some("npx".to_string()),
             args: vec![
                 "-y".to_string(),
                 "@modelcontextprotocol/server-memory".to_string(),
@@ -136,13 +151,27 @@ pub fn default_mapping() -> LoadedMapping {
         .aliases
         .insert("TodoWrite".to_string(), "plan".to_string());
 
-    // Terminal-related Claude tools are provided by default terminal access → consume
-    for terminal_tool in ["Bash", "Glob", "Grep", "Read", "BashOutput", "KillBash"] {
+    // Shell tools are provided by default terminal access → consume; denying
+    // them clamps the sandbox to read-only
+    for terminal_tool in ["Bash", "BashOutput", "KillBash"] {
         anthropic.map.insert(
             terminal_tool.to_string(),
             MappingDest::Builtin(BuiltinToggle::TerminalAccess),
         );
     }
+    // Read-only and session tools have no Codex counterpart → consume
+    for k in [
+        "Read",
+        "Grep",
+        "Glob",
+        "LS",
+        "NotebookRead",
+        "Task",
+        "ExitPlanMode",
+        "SlashCommand",
+    ] {
+        anthropic.map.insert(k.to_string(), MappingDest::Drop);
+    }
 
     LoadedMapping { vscode, anthropic }
 }
@@ -0,0 +1,241 @@
+//! Token usage ledger and daily budgets.
+//!
+//! Usage is summed per UTC day into `SWITCHBOARD_HOME/usage/YYYY-MM-DD.json`,
+//! keyed by agent tool name and by tag. Daily caps from `[budgets]` in
+//! `config.toml` are checked before a call starts. When the day's file cannot
+//! be read or parsed, budgeted calls are refused rather than let through.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::codex_runner::TokenUsageRecord;
+use crate::model::PreparedAgent;
+use crate::replay::write_atomic;
+
+/// Accumulated usage for one agent or tag.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, usage: &TokenUsageRecord) {
+        self.calls += 1;
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.total_tokens += usage.total_tokens;
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DayUsage {
+    #[serde(default)]
+    agents: BTreeMap<String, UsageTotals>,
+    #[serde(default)]
+    tags: BTreeMap<String, UsageTotals>,
+}
+
+/// Daily token caps. Agent caps match the tool name or the agent name.
+#[derive(Debug, Clone, Default)]
+pub struct Budgets {
+    /// Cap applied to every agent without an explicit entry.
+    pub daily_per_agent: Option<u64>,
+    pub agents: HashMap<String, u64>,
+    pub tags: HashMap<String, u64>,
+}
+
+impl Budgets {
+    pub fn is_empty(&self) -> bool {
+        self.daily_per_agent.is_none() && self.agents.is_empty() && self.tags.is_empty()
+    }
+}
+
+/// Per-day usage files guarded by a process-wide lock.
+pub struct UsageLedger {
+    dir: PathBuf,
+    budgets: Budgets,
+    lock: Mutex<()>,
+}
+
+impl UsageLedger {
+    pub fn open(dir: PathBuf, budgets: Budgets) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("create usage dir {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            budgets,
+            lock: Mutex::new(()),
+        })
+    }
+
+    pub fn dir(&self) -> &std::path::Path {
+        &self.dir
+    }
+
+    fn today_path(&self) -> PathBuf {
+        self.dir
+            .join(format!("{}.json", Utc::now().format("%Y-%m-%d")))
+    }
+
+    /// Today's totals; a missing file is an empty day, an unreadable or
+    /// corrupt one an error.
+    fn load(&self, path: &std::path::Path) -> anyhow::Result<DayUsage> {
+        let s = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(DayUsage::default()),
+            Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+        };
+        serde_json::from_str(&s).with_context(|| format!("parse {}", path.display()))
+    }
+
+    /// Add one call's usage to today's totals for the agent and its tags.
+    pub fn record(&self, agent: &PreparedAgent, usage: &TokenUsageRecord) -> anyhow::Result<()> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| anyhow::anyhow!("usage lock poisoned"))?;
+        let path = self.today_path();
+        // Never overwrite a file we could not read; that would reset the day
+        let mut day = self.load(&path)?;
+        day.agents
+            .entry(agent.tool_name.clone())
+            .or_default()
+            .add(usage);
+        for tag in agent.tags.iter().flatten() {
+            day.tags.entry(tag.clone()).or_default().add(usage);
+        }
+        let json = serde_json::to_string_pretty(&day).context("serialize usage")?;
+        write_atomic(&path, json.as_bytes())
+    }
+
+    /// Refuse the call when today's usage already reached a configured cap.
+    pub fn check(&self, agent: &PreparedAgent) -> Result<(), String> {
+        if self.budgets.is_empty() {
+            return Ok(());
+        }
+        let day = match self.lock.lock() {
+            Ok(_guard) => self.load(&self.today_path()),
+            Err(_) => Err(anyhow::anyhow!("usage lock poisoned")),
+        }
+        .map_err(|e| {
+            tracing::error!("usage ledger unavailable: {:#}", e);
+            format!("daily token budgets cannot be checked ({e:#}); refusing the call")
+        })?;
+        let agent_cap = self
+            .budgets
+            .agents
+            .get(&agent.tool_name)
+            .or_else(|| self.budgets.agents.get(&agent.name))
+            .copied()
+            .or(self.budgets.daily_per_agent);
+        if let Some(cap) = agent_cap {
+            let used = day
+                .agents
+                .get(&agent.tool_name)
+                .map(|t| t.total_tokens)
+                .unwrap_or(0);
+            if used >= cap {
+                return Err(format!(
+                    "daily token budget exhausted for agent '{}': used {} of {} tokens today (UTC)",
+                    agent.tool_name, used, cap
+                ));
+            }
+        }
+        for tag in agent.tags.iter().flatten() {
+            let Some(cap) = self.budgets.tags.get(tag) else {
+                continue;
+            };
+            let used = day.tags.get(tag).map(|t| t.total_tokens).unwrap_or(0);
+            if used >= *cap {
+                return Err(format!(
+                    "daily token budget exhausted for tag '{}': used {} of {} tokens today (UTC)",
+                    tag, used, cap
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(tags: &[&str]) -> PreparedAgent {
+        PreparedAgent {
+            tool_name: "agent_sample".to_string(),
+            name: "Sample".to_string(),
+            description: "Sample agent".to_string(),
+            tags: Some(tags.iter().map(|s| s.to_string()).collect()),
+            source_path: "/tmp/sample.toml".into(),
+            ..Default::default()
+        }
+    }
+
+    fn usage(total: u64) -> TokenUsageRecord {
+        TokenUsageRecord {
+            input_tokens: total / 2,
+            output_tokens: total - total / 2,
+            total_tokens: total,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn agent_cap_refuses_after_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let budgets = Budgets {
+            agents: HashMap::from([("Sample".to_string(), 1000)]),
+            ..Default::default()
+        };
+        let ledger = UsageLedger::open(tmp.path().join("usage"), budgets).unwrap();
+        let a = agent(&[]);
+        assert!(ledger.check(&a).is_ok());
+        ledger.record(&a, &usage(600)).unwrap();
+        assert!(ledger.check(&a).is_ok());
+        ledger.record(&a, &usage(600)).unwrap();
+        let err = ledger.check(&a).unwrap_err();
+        assert!(err.contains("agent_sample"), "{err}");
+        assert!(err.contains("1200 of 1000"), "{err}");
+    }
+
+    #[test]
+    fn tag_cap_applies_across_agents() {
+        let tmp = tempfile::tempdir().unwrap();
+        let budgets = Budgets {
+            tags: HashMap::from([("review".to_string(), 100)]),
+            ..Default::default()
+        };
+        let ledger = UsageLedger::open(tmp.path().join("usage"), budgets).unwrap();
+        let tagged = agent(&["review"]);
+        ledger.record(&tagged, &usage(150)).unwrap();
+        assert!(ledger.check(&tagged).unwrap_err().contains("tag 'review'"));
+        assert!(ledger.check(&agent(&["other"])).is_ok());
+    }
+
+    #[test]
+    fn corrupt_day_file_fails_closed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let budgets = Budgets {
+            daily_per_agent: Some(1000),
+            ..Default::default()
+        };
+        let ledger = UsageLedger::open(tmp.path().join("usage"), budgets).unwrap();
+        let a = agent(&[]);
+        ledger.record(&a, &usage(10)).unwrap();
+        let path = ledger.today_path();
+        std::fs::write(&path, "{\"agents\": {").unwrap();
+        let err = ledger.check(&a).unwrap_err();
+        assert!(err.contains("cannot be checked"), "{err}");
+        assert!(ledger.record(&a, &usage(10)).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"agents\": {");
+    }
+}